@@ -5,23 +5,20 @@ set -e
 rustup target add x86_64-unknown-linux-gnu
 cargo build --target x86_64-unknown-linux-gnu
 cargo test --target x86_64-unknown-linux-gnu
+cargo build --target x86_64-unknown-linux-gnu --no-default-features
+cargo build --target x86_64-unknown-linux-gnu --no-default-features --features defmt
+cargo build --target x86_64-unknown-linux-gnu --features serde
+cargo test --target x86_64-unknown-linux-gnu --features alloc
+cargo test --target x86_64-unknown-linux-gnu --features testing
+cargo test --target x86_64-unknown-linux-gnu --features std
 
 cargo fmt -- --check
 cargo clippy
 
-# build for non x86 platform should fail
-set +e
-rustup target add thumbv6m-none-eabi 1>/dev/null 2>&1
-cargo build --target thumbv6m-none-eabi 1>/dev/null 2>&1
-
-RETURN_CODE=$?
-
-set -e
-
-if [ $RETURN_CODE -ne 0 ]
-then
-  echo "failed as expected (wrong platform)"
-else
-  echo "should have failed (wrong platform)"
-  exit 1
-fi
+# build for non x86 platform should work as well (without CPUID checks)
+rustup target add thumbv6m-none-eabi
+cargo build --target thumbv6m-none-eabi
+rustup target add aarch64-unknown-none
+cargo build --target aarch64-unknown-none
+rustup target add riscv64gc-unknown-none-elf
+cargo build --target riscv64gc-unknown-none-elf
@@ -0,0 +1,124 @@
+//! aarch64 backend. As there is no CPUID on ARM, detection relies on the device tree
+//! (see [`crate::DeviceTreeProbe`]) and on the SMCCC vendor-specific hypervisor service
+//! calls, which are only available at EL1 under a hypervisor.
+
+use crate::{DeviceTree, Probe, ProbeResult};
+
+/// Function ID of the SMCCC "Vendor Specific Hypervisor Service Call UID" query.
+const SMCCC_VENDOR_HYP_CALL_UID: u32 = 0x8600_ff01;
+
+/// Return value of an SMCCC call that is not supported.
+const SMCCC_RET_NOT_SUPPORTED: u64 = u64::MAX;
+
+/// UID that KVM returns for [`hypervisor_uid`], as registers `w0..=w3`
+/// (`28b46fb6-2ec5-11e9-a9ca-4b564d003a74`).
+pub const KVM_HYPERVISOR_UID: [u32; 4] = [0xb66f_b428, 0xe911_c52e, 0x564b_caa9, 0x743a_004d];
+
+/// Instruction that is used to issue an SMCCC call.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SmcccConduit {
+    /// Hypervisor call (`hvc #0`). Traps to EL2.
+    Hvc,
+    /// Secure monitor call (`smc #0`). Traps to EL3 (or EL2, if trapped by a hypervisor).
+    Smc,
+}
+
+impl SmcccConduit {
+    /// Reads the conduit from the `method` property of the `/psci` node of the device
+    /// tree (`"hvc"` or `"smc"`). QEMU's `virt` machine always provides this node.
+    pub fn from_device_tree(device_tree: &DeviceTree) -> Option<Self> {
+        match device_tree.property("/psci", "method")? {
+            b"hvc\0" => Some(Self::Hvc),
+            b"smc\0" => Some(Self::Smc),
+            _ => None,
+        }
+    }
+}
+
+/// Queries the UID of the hypervisor via the SMCCC "Vendor Specific Hypervisor Service
+/// Call UID" function. Returns the registers `w0..=w3` or `None`, if the call is not
+/// supported. Compare the result with [`KVM_HYPERVISOR_UID`].
+///
+/// # Safety
+/// The conduit must be usable at the current exception level. An `hvc` without a
+/// hypervisor or an `smc` without secure firmware is an undefined instruction. Use
+/// [`SmcccConduit::from_device_tree`] to get the conduit that the platform supports.
+pub unsafe fn hypervisor_uid(conduit: SmcccConduit) -> Option<[u32; 4]> {
+    let (r0, r1, r2, r3) = smccc_call(conduit, SMCCC_VENDOR_HYP_CALL_UID);
+    if r0 == SMCCC_RET_NOT_SUPPORTED {
+        return None;
+    }
+    Some([r0 as u32, r1 as u32, r2 as u32, r3 as u32])
+}
+
+/// Issues an SMCCC fast call without arguments and returns `x0..=x3`.
+unsafe fn smccc_call(conduit: SmcccConduit, function: u32) -> (u64, u64, u64, u64) {
+    let (r0, r1, r2, r3);
+    // SMCCC v1.0 allows the callee to clobber x4..=x17
+    match conduit {
+        SmcccConduit::Hvc => core::arch::asm!(
+            "hvc #0",
+            inout("x0") function as u64 => r0,
+            lateout("x1") r1, lateout("x2") r2, lateout("x3") r3,
+            lateout("x4") _, lateout("x5") _, lateout("x6") _, lateout("x7") _,
+            lateout("x8") _, lateout("x9") _, lateout("x10") _, lateout("x11") _,
+            lateout("x12") _, lateout("x13") _, lateout("x14") _, lateout("x15") _,
+            lateout("x16") _, lateout("x17") _,
+            options(nomem, nostack)
+        ),
+        SmcccConduit::Smc => core::arch::asm!(
+            "smc #0",
+            inout("x0") function as u64 => r0,
+            lateout("x1") r1, lateout("x2") r2, lateout("x3") r3,
+            lateout("x4") _, lateout("x5") _, lateout("x6") _, lateout("x7") _,
+            lateout("x8") _, lateout("x9") _, lateout("x10") _, lateout("x11") _,
+            lateout("x12") _, lateout("x13") _, lateout("x14") _, lateout("x15") _,
+            lateout("x16") _, lateout("x17") _,
+            options(nomem, nostack)
+        ),
+    }
+    (r0, r1, r2, r3)
+}
+
+/// Probe that queries the hypervisor UID via SMCCC (see [`hypervisor_uid`]).
+///
+/// Returns [`ProbeResult::Hint`], if the hypervisor is KVM, which is most likely driven
+/// by QEMU, and [`ProbeResult::Inconclusive`] otherwise. QEMU without KVM (TCG) doesn't
+/// implement the call.
+#[derive(Copy, Clone, Debug)]
+pub struct SmcccProbe {
+    conduit: SmcccConduit,
+}
+
+impl SmcccProbe {
+    /// Creates a new probe that uses the given conduit.
+    ///
+    /// # Safety
+    /// See [`hypervisor_uid`].
+    pub const unsafe fn new(conduit: SmcccConduit) -> Self {
+        Self { conduit }
+    }
+}
+
+impl Probe for SmcccProbe {
+    fn name(&self) -> &'static str {
+        "smccc"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `SmcccProbe::new`
+        match unsafe { hypervisor_uid(self.conduit) } {
+            Some(KVM_HYPERVISOR_UID) => {
+                debug!("SMCCC hypervisor UID belongs to KVM.");
+                ProbeResult::Hint
+            }
+            Some(uid) => {
+                debug!("SMCCC hypervisor UID {:?} is unknown.", uid);
+                ProbeResult::Inconclusive
+            }
+            None => ProbeResult::Inconclusive,
+        }
+    }
+}
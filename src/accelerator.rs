@@ -0,0 +1,81 @@
+//! Module for [`accelerator`], which tells which accelerator QEMU uses. Unlike
+//! [`crate::DetectionReport::accelerator`], it also looks behind the Hyper-V interface
+//! and at hidden hypervisor flags.
+
+use crate::cpuid::{self, HYPERVISOR_INFO_LEAF, HYPERV_EMULATOR_INFO_LEAF};
+use crate::hypervisor::{SIGNATURE_HYPERV, SIGNATURE_KVM, SIGNATURE_TCG, SIGNATURE_XEN};
+use crate::{xen, Accelerator, VmmKind};
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Determines the accelerator that QEMU uses, from the signatures in the hypervisor info
+/// leaves `0x4000_0000` and `0x4000_0100` and the CPU brand string. Returns `None`, if
+/// the accelerator is unknown or the VMM isn't QEMU with one of the accelerators.
+///
+/// The leaves are read even without the hypervisor flag, so TCG and KVM with
+/// `-cpu ...,-hypervisor` are recognized. The signature of TCG always means
+/// [`Accelerator::Tcg`], and the signature of KVM in either leaf, including KVM with
+/// Hyper-V enlightenments or a custom `hv-vendor-id`, means [`Accelerator::Kvm`]. Other
+/// KVM-based VMMs are reported as [`Accelerator::Kvm`] as well. The Hyper-V interface
+/// without KVM behind it only means [`Accelerator::Whpx`] with QEMU's CPU brand string,
+/// as it is plain Hyper-V otherwise. Xen means [`Accelerator::Xen`] for HVM and PVH
+/// guests.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{accelerator, Accelerator};
+///
+/// if accelerator() == Some(Accelerator::Tcg) {
+///     println!("TCG, benchmark results are meaningless");
+/// }
+/// ```
+pub fn accelerator() -> Option<Accelerator> {
+    accelerator_with(&CpuIdReaderNative)
+}
+
+/// Like [`accelerator`] but reads CPUID through the given [`CpuIdReader`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{accelerator_with, Accelerator};
+///
+/// // QEMU with WHPX: the Hyper-V interface and QEMU's CPU model
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x1, 0, 0, 0),
+///         0x1 => (0, 0, 1 << 31, 0),
+///         // "Microsoft Hv"
+///         0x4000_0000 => (0x4000_0006, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+///         0x8000_0000 => (0x8000_0004, 0, 0, 0),
+///         // "QEMU Virtual CPU version 2.5+"
+///         0x8000_0002 => (0x554d_4551, 0x7269_5620, 0x6c61_7574, 0x5550_4320),
+///         0x8000_0003 => (0x7265_7620, 0x6e6f_6973, 0x352e_3220, 0x0000_002b),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// assert_eq!(accelerator_with(&reader), Some(Accelerator::Whpx));
+/// ```
+pub fn accelerator_with<R: CpuIdReader>(reader: &R) -> Option<Accelerator> {
+    let signature = cpuid::read_signature(reader, HYPERVISOR_INFO_LEAF);
+    let emulator = cpuid::read_signature(reader, HYPERV_EMULATOR_INFO_LEAF);
+    let brand_string_mentions_qemu = cpuid::brand_string_mentions_qemu_with(reader);
+    let accelerator = if &signature == SIGNATURE_TCG {
+        Some(Accelerator::Tcg)
+    } else if &signature == SIGNATURE_KVM || &emulator == SIGNATURE_KVM {
+        Some(Accelerator::Kvm)
+    } else if &signature == SIGNATURE_XEN || &emulator == SIGNATURE_XEN {
+        xen::xen_info_with(reader)
+            .filter(|info| info.hvm_container)
+            .map(|_| Accelerator::Xen)
+    } else if &signature == SIGNATURE_HYPERV {
+        brand_string_mentions_qemu.then_some(Accelerator::Whpx)
+    } else {
+        let vmm = VmmKind::identify(&signature, brand_string_mentions_qemu);
+        Accelerator::identify(&signature, vmm)
+    };
+    debug!("Accelerator: {:?}", accelerator);
+    accelerator
+}
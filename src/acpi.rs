@@ -0,0 +1,260 @@
+//! Module for [`AcpiProbe`], which checks the OEM IDs of the ACPI tables. The firmware of
+//! QEMU (SeaBIOS and OVMF with QEMU's tables) uses the OEM ID `BOCHS ` and OEM table IDs
+//! starting with `BXPC`, unless the user overrides them via `-machine oem-id=...`. The
+//! tables are also present with TCG and without the hypervisor flag in CPUID.
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::bios;
+use crate::{cpuid, Probe, ProbeResult};
+
+/// Signature of the RSDP.
+pub(crate) const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+/// Size of the RSDP of ACPI 1.0, which the checksum covers.
+pub(crate) const RSDP_V1_LEN: usize = 20;
+/// Size of the RSDP of ACPI 2.0 and later.
+const RSDP_V2_LEN: usize = 36;
+/// Size of the header of each system description table.
+const SDT_HEADER_LEN: usize = 36;
+/// Signature of the FADT.
+const FADT_SIGNATURE: &[u8; 4] = b"FACP";
+
+/// OEM ID of the firmware of QEMU and Bochs.
+const BOCHS_OEM_ID: &[u8; 6] = b"BOCHS ";
+/// Prefix of the OEM table IDs of QEMU and Bochs, e.g. `BXPCFACP` or `BXPC    `.
+const BOCHS_OEM_TABLE_ID_PREFIX: &[u8; 4] = b"BXPC";
+/// OEM IDs of VMMs that can't be QEMU.
+const NON_QEMU_OEM_IDS: [&[u8; 6]; 5] = [
+    b"VBOX  ", // VirtualBox
+    b"VRTUAL", // Hyper-V
+    b"BHYVE ", // bhyve
+    b"CLOUDH", // cloud-hypervisor
+    b"FIRECK", // Firecracker
+];
+
+/// OEM ID and OEM table ID of an ACPI table. The RSDP has no OEM table ID.
+type OemIds = ([u8; 6], Option<[u8; 8]>);
+
+/// Probe that checks the OEM IDs of the RSDP, the RSDT or XSDT, and the FADT.
+///
+/// Returns [`ProbeResult::NotQemu`], if one belongs to a VMM that can't be QEMU, such as
+/// VirtualBox. If one is `BOCHS ` or starts with `BXPC`, returns [`ProbeResult::Qemu`],
+/// unless only the hypervisor flag is clear on x86. Bochs uses the same IDs but never
+/// sets the flag, so the result is [`ProbeResult::Hint`] then; see
+/// [`crate::BochsProbe`] to tell both apart. Otherwise, returns
+/// [`ProbeResult::Inconclusive`].
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{AcpiProbe, Detector};
+///
+/// // e.g. passed by the boot loader
+/// let rsdp = 0xf_5a90 as *const u8;
+/// // SAFETY: runs with identity-mapped memory
+/// let probe = unsafe { AcpiProbe::new(rsdp) };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct AcpiProbe {
+    rsdp: *const u8,
+}
+
+impl AcpiProbe {
+    /// Creates a new probe that inspects the ACPI tables of the given RSDP.
+    ///
+    /// # Safety
+    /// `ptr` must point to a readable RSDP. The RSDT or XSDT and the tables that it
+    /// references must be readable at their physical addresses, i.e. physical memory must
+    /// be identity-mapped.
+    pub const unsafe fn new(rsdp: *const u8) -> Self {
+        Self { rsdp }
+    }
+
+    /// Creates a new probe for the RSDP that the legacy BIOS provides in the first KiB of
+    /// the EBDA or in `0xe0000..0x100000`. Returns `None`, if there is no RSDP, e.g. on
+    /// UEFI systems.
+    ///
+    /// # Safety
+    /// The first MiB of physical memory must be identity-mapped and readable. Apart from
+    /// that, see [`Self::new`].
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub unsafe fn find() -> Option<Self> {
+        bios::find_rsdp().map(|rsdp| Self::new(rsdp))
+    }
+
+    /// Calls `f` for the OEM IDs of the RSDP, the RSDT or XSDT, and the FADT. Tables
+    /// with a wrong signature are skipped.
+    ///
+    /// # Safety
+    /// See [`Self::new`].
+    unsafe fn for_each_oem_ids(&self, mut f: impl FnMut(OemIds)) {
+        let rsdp = core::slice::from_raw_parts(self.rsdp, RSDP_V1_LEN);
+        if !rsdp_valid(rsdp) {
+            return;
+        }
+        f((rsdp[9..15].try_into().unwrap(), None));
+        let Some(root) = root_table(self.rsdp) else {
+            return;
+        };
+        f(sdt_oem_ids(root.table));
+        if let Some(fadt) = root
+            .entries()
+            .find_map(|address| sdt(address, FADT_SIGNATURE))
+        {
+            f(sdt_oem_ids(fadt));
+        }
+    }
+}
+
+impl Probe for AcpiProbe {
+    fn name(&self) -> &'static str {
+        "acpi"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let mut bochs = false;
+        let mut non_qemu = false;
+        // SAFETY: guaranteed by the caller of `AcpiProbe::new`
+        unsafe {
+            self.for_each_oem_ids(|(oem_id, oem_table_id)| {
+                bochs |= &oem_id == BOCHS_OEM_ID
+                    || oem_table_id.is_some_and(|id| id.starts_with(BOCHS_OEM_TABLE_ID_PREFIX));
+                non_qemu |= NON_QEMU_OEM_IDS.contains(&&oem_id);
+            })
+        };
+        if non_qemu {
+            debug!("ACPI OEM ID of a VMM that isn't QEMU.");
+            ProbeResult::NotQemu
+        } else if !bochs {
+            ProbeResult::Inconclusive
+        } else if cfg!(any(target_arch = "x86", target_arch = "x86_64"))
+            && !cpuid::hypervisor_flag_set()
+        {
+            debug!("ACPI OEM IDs of QEMU or Bochs, but no hypervisor flag.");
+            ProbeResult::Hint
+        } else {
+            debug!("ACPI OEM IDs of QEMU.");
+            ProbeResult::Qemu
+        }
+    }
+}
+
+/// Returns if `rsdp` starts with the signature of the RSDP and has a valid checksum.
+pub(crate) fn rsdp_valid(rsdp: &[u8]) -> bool {
+    rsdp.len() >= RSDP_V1_LEN
+        && rsdp.starts_with(RSDP_SIGNATURE)
+        && rsdp[..RSDP_V1_LEN]
+            .iter()
+            .fold(0_u8, |sum, &b| sum.wrapping_add(b))
+            == 0
+}
+
+/// The RSDT or the XSDT.
+#[derive(Copy, Clone, Debug)]
+struct RootTable<'a> {
+    table: &'a [u8],
+    /// Size of an entry: 4 bytes for the RSDT, 8 bytes for the XSDT.
+    entry_len: usize,
+}
+
+impl<'a> RootTable<'a> {
+    /// Returns an iterator over the physical addresses of the referenced tables.
+    fn entries(self) -> impl Iterator<Item = u64> + 'a {
+        let entry_len = self.entry_len;
+        self.table[SDT_HEADER_LEN..]
+            .chunks_exact(entry_len)
+            .map(move |entry| {
+                if entry_len == 8 {
+                    read_u64(entry, 0)
+                } else {
+                    u64::from(read_u32(entry, 0))
+                }
+            })
+    }
+}
+
+/// Returns the XSDT of the RSDP at `rsdp`, if the revision is 2 or later, and the RSDT
+/// otherwise. Returns `None`, if the RSDP or the table is invalid.
+///
+/// # Safety
+/// See [`AcpiProbe::new`].
+unsafe fn root_table<'a>(rsdp: *const u8) -> Option<RootTable<'a>> {
+    let rsdp_v1 = core::slice::from_raw_parts(rsdp, RSDP_V1_LEN);
+    if !rsdp_valid(rsdp_v1) {
+        return None;
+    }
+    let (root, signature, entry_len) = if rsdp_v1[15] >= 2 {
+        let rsdp = core::slice::from_raw_parts(rsdp, RSDP_V2_LEN);
+        (read_u64(rsdp, 24), b"XSDT", 8)
+    } else {
+        (u64::from(read_u32(rsdp_v1, 16)), b"RSDT", 4)
+    };
+    let table = sdt(root, signature)?;
+    Some(RootTable { table, entry_len })
+}
+
+/// Returns the first table with the given signature, for which `predicate` returns
+/// `true`, among the tables that the RSDT or XSDT of the RSDP at `rsdp` references. Some
+/// signatures, such as `SSDT`, are used by multiple tables.
+///
+/// # Safety
+/// See [`AcpiProbe::new`].
+pub(crate) unsafe fn find_table<'a>(
+    rsdp: *const u8,
+    signature: &[u8; 4],
+    mut predicate: impl FnMut(&[u8]) -> bool,
+) -> Option<&'a [u8]> {
+    root_table(rsdp)?
+        .entries()
+        .filter_map(|address| sdt(address, signature))
+        .find(|table| predicate(table))
+}
+
+/// Returns the OEM table ID from the header of a system description table.
+pub(crate) fn sdt_oem_table_id(sdt: &[u8]) -> &[u8; 8] {
+    sdt[16..24].try_into().unwrap()
+}
+
+/// Returns the body of a system description table, i.e. the bytes after the header.
+pub(crate) fn sdt_body(sdt: &[u8]) -> &[u8] {
+    &sdt[SDT_HEADER_LEN..]
+}
+
+/// Returns the system description table at the physical `address`, if it has the given
+/// signature.
+///
+/// # Safety
+/// The table must be readable at `address`.
+unsafe fn sdt<'a>(address: u64, signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let address = usize::try_from(address)
+        .ok()
+        .filter(|&address| address != 0)?;
+    let header = core::slice::from_raw_parts(address as *const u8, SDT_HEADER_LEN);
+    if !header.starts_with(signature) {
+        return None;
+    }
+    let len = usize::try_from(read_u32(header, 4)).ok()?;
+    if len < SDT_HEADER_LEN {
+        return None;
+    }
+    Some(core::slice::from_raw_parts(address as *const u8, len))
+}
+
+/// Returns the OEM ID and the OEM table ID from the header of a system description table.
+fn sdt_oem_ids(sdt: &[u8]) -> OemIds {
+    (
+        sdt[10..16].try_into().unwrap(),
+        Some(*sdt_oem_table_id(sdt)),
+    )
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads a little-endian `u64` at `offset`.
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
@@ -0,0 +1,180 @@
+//! Module for [`Weights`] and the aggregation of all evidence into a verdict and a score.
+//! Each source of evidence only reports what it found; this module decides what it means.
+
+use crate::{Check, CheckOutcome, ProbeResult, QemuCertainty};
+
+/// Contribution of each piece of evidence to the score of a [`crate::DetectionReport`].
+/// Positive weights point towards QEMU, negative weights against it. Set them via
+/// [`crate::DetectorBuilder::weights`].
+///
+/// New sources of evidence may add fields in the future, hence the struct is
+/// `#[non_exhaustive]`. Start from [`Weights::DEFAULT`] and override single fields.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, Weights};
+///
+/// // I always run QEMU with "-cpu host", so the brand string is worthless for me.
+/// let mut weights = Weights::DEFAULT;
+/// weights.brand_string = 0;
+/// let report = Detector::builder().weights(weights).build().detect();
+/// println!("score: {}", report.score());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Weights {
+    /// Contribution of a passed [`Check::HypervisorFlag`].
+    pub hypervisor_flag: i32,
+    /// Contribution of a failed [`Check::HypervisorFlag`].
+    pub no_hypervisor_flag: i32,
+    /// Contribution of a passed [`Check::HypervisorId`].
+    pub hypervisor_id: i32,
+    /// Contribution of a failed [`Check::VmmSignature`].
+    pub foreign_vmm: i32,
+    /// Contribution of a passed [`Check::BrandString`].
+    pub brand_string: i32,
+    /// Contribution of a [`ProbeResult::Qemu`].
+    pub probe_qemu: i32,
+    /// Contribution of a [`ProbeResult::Hint`].
+    pub probe_hint: i32,
+    /// Contribution of a [`ProbeResult::NotQemu`].
+    pub probe_not_qemu: i32,
+}
+
+impl Weights {
+    /// The default weights. A set hypervisor flag is worth 30 points, a QEMU hypervisor
+    /// ID 70, a QEMU brand string 60, a [`ProbeResult::Qemu`] 50 and a
+    /// [`ProbeResult::Hint`] 10. A [`ProbeResult::NotQemu`] or a missing hypervisor flag,
+    /// which QEMU can hide, subtract 50 points, and the signature of a foreign VMM 100.
+    pub const DEFAULT: Self = Self {
+        hypervisor_flag: 30,
+        no_hypervisor_flag: -50,
+        hypervisor_id: 70,
+        foreign_vmm: -100,
+        brand_string: 60,
+        probe_qemu: 50,
+        probe_hint: 10,
+        probe_not_qemu: -50,
+    };
+
+    /// Returns the contribution of a check outcome.
+    pub const fn check(&self, check: Check, outcome: CheckOutcome) -> i32 {
+        match (check, outcome) {
+            (Check::HypervisorFlag, CheckOutcome::Passed) => self.hypervisor_flag,
+            (Check::HypervisorFlag, CheckOutcome::Failed) => self.no_hypervisor_flag,
+            (Check::HypervisorId, CheckOutcome::Passed) => self.hypervisor_id,
+            (Check::VmmSignature, CheckOutcome::Failed) => self.foreign_vmm,
+            (Check::BrandString, CheckOutcome::Passed) => self.brand_string,
+            _ => 0,
+        }
+    }
+
+    /// Returns the contribution of a probe result.
+    pub const fn probe(&self, result: ProbeResult) -> i32 {
+        match result {
+            ProbeResult::Qemu => self.probe_qemu,
+            ProbeResult::Hint => self.probe_hint,
+            ProbeResult::NotQemu => self.probe_not_qemu,
+            ProbeResult::Inconclusive | ProbeResult::Unavailable | ProbeResult::Faulted => 0,
+        }
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Accumulates the evidence of all sources. Produces the coarse verdict and the raw score.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Aggregation {
+    weights: Weights,
+    score: i32,
+    qemu: bool,
+    /// Evidence for QEMU that can't come from another hypervisor.
+    unambiguous_qemu: bool,
+    not_qemu: bool,
+    hint: bool,
+}
+
+impl Aggregation {
+    pub(crate) const fn new(weights: Weights) -> Self {
+        Self {
+            weights,
+            score: 0,
+            qemu: false,
+            unambiguous_qemu: false,
+            not_qemu: false,
+            hint: false,
+        }
+    }
+
+    /// Adds the verdict of the CPUID checks and the outcome of each check.
+    pub(crate) fn add_cpuid(
+        &mut self,
+        certainty: QemuCertainty,
+        checks: impl Iterator<Item = (Check, CheckOutcome)>,
+    ) {
+        match certainty {
+            QemuCertainty::VeryLikely => self.qemu = true,
+            QemuCertainty::Maybe => self.hint = true,
+            QemuCertainty::DefinitelyNot | QemuCertainty::Unknown => self.not_qemu = true,
+        }
+        for (check, outcome) in checks {
+            if (check, outcome) == (Check::HypervisorId, CheckOutcome::Passed) {
+                self.unambiguous_qemu = true;
+            }
+            self.score += self.weights.check(check, outcome);
+        }
+    }
+
+    /// Adds the result of a probe. `weight` is the contribution to the score.
+    /// `unambiguous` tells if a [`ProbeResult::Qemu`] is a signature that only QEMU
+    /// provides, see [`crate::Probe::unambiguous`].
+    pub(crate) fn add_probe(&mut self, result: ProbeResult, weight: i32, unambiguous: bool) {
+        self.score += weight;
+        match result {
+            ProbeResult::Qemu => {
+                self.qemu = true;
+                self.unambiguous_qemu |= unambiguous;
+            }
+            ProbeResult::Hint => self.hint = true,
+            ProbeResult::NotQemu => self.not_qemu = true,
+            ProbeResult::Inconclusive | ProbeResult::Unavailable | ProbeResult::Faulted => {}
+        }
+    }
+
+    /// The weights that are used for the score.
+    pub(crate) const fn weights(&self) -> &Weights {
+        &self.weights
+    }
+
+    /// Returns if any source found evidence for QEMU that can't come from another
+    /// hypervisor.
+    pub(crate) const fn unambiguous_qemu(&self) -> bool {
+        self.unambiguous_qemu
+    }
+
+    /// Returns the raw sum of all weights.
+    pub(crate) const fn score(&self) -> i32 {
+        self.score
+    }
+
+    /// Returns the coarse verdict before any [`crate::DetectionPolicy`] is applied.
+    pub(crate) fn certainty(&self) -> QemuCertainty {
+        if self.qemu {
+            QemuCertainty::VeryLikely
+        } else if self.not_qemu {
+            QemuCertainty::DefinitelyNot
+        } else if self.hint {
+            QemuCertainty::Maybe
+        } else {
+            debug!("Unknown. No detection source gathered a signal.");
+            QemuCertainty::Unknown
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! AWS EC2 backend. Nitro instances report the KVM signature and the DMI system vendor
+//! `Amazon EC2`. Older instance types run on Xen with an Amazon-specific BIOS version,
+//! such as `4.11.amazon`.
+
+use crate::{detect_hypervisor, vmm_from_dmi, Probe, ProbeResult, VmmKind};
+
+/// Identifies the EC2 hypervisor by the DMI system vendor and BIOS version, as found in
+/// `/sys/class/dmi/id/sys_vendor` and `/sys/class/dmi/id/bios_version` on Linux, and by
+/// the hypervisor signature. Returns `None`, if the code doesn't run in an EC2 virtual
+/// machine. Bare-metal instances also report the vendor `Amazon EC2` but have no
+/// hypervisor; on x86, they aren't reported.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{aws_vmm, VmmKind};
+///
+/// // e.g. read from /sys/class/dmi/id/ on Linux
+/// match aws_vmm("LENOVO", "N32ET75W (1.51 )") {
+///     Some(VmmKind::AwsNitro) => println!("EC2 Nitro instance"),
+///     Some(VmmKind::AwsXen) => println!("EC2 Xen instance"),
+///     _ => println!("not EC2"),
+/// }
+/// ```
+pub fn aws_vmm(sys_vendor: &str, bios_version: &str) -> Option<VmmKind> {
+    let hypervisor = detect_hypervisor();
+    let hypervisor_present =
+        hypervisor.is_some() || cfg!(not(any(target_arch = "x86", target_arch = "x86_64")));
+    if hypervisor_present && vmm_from_dmi(sys_vendor, "") == Some(VmmKind::AwsNitro) {
+        Some(VmmKind::AwsNitro)
+    } else if hypervisor == Some(VmmKind::Xen)
+        && bios_version
+            .as_bytes()
+            .windows(6)
+            .any(|window| window.eq_ignore_ascii_case(b"amazon"))
+    {
+        Some(VmmKind::AwsXen)
+    } else {
+        None
+    }
+}
+
+/// Probe that checks if the code runs in an EC2 virtual machine (see [`aws_vmm`]).
+///
+/// Returns [`ProbeResult::NotQemu`] on Nitro instances. Xen-based instances use QEMU as
+/// device model, so the probe returns [`ProbeResult::Inconclusive`] there, like in all
+/// other cases.
+#[derive(Copy, Clone, Debug)]
+pub struct AwsProbe<'a> {
+    sys_vendor: &'a str,
+    bios_version: &'a str,
+}
+
+impl<'a> AwsProbe<'a> {
+    /// Creates a new probe that inspects the given DMI system vendor and BIOS version.
+    pub const fn new(sys_vendor: &'a str, bios_version: &'a str) -> Self {
+        Self {
+            sys_vendor,
+            bios_version,
+        }
+    }
+}
+
+impl Probe for AwsProbe<'_> {
+    fn name(&self) -> &'static str {
+        "aws"
+    }
+
+    fn run(&self) -> ProbeResult {
+        match aws_vmm(self.sys_vendor, self.bios_version) {
+            Some(VmmKind::AwsNitro) => {
+                debug!("Runs in an EC2 Nitro instance.");
+                ProbeResult::NotQemu
+            }
+            _ => ProbeResult::Inconclusive,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Microsoft Azure backend. Azure runs on Hyper-V and sets a well-known DMI chassis
+//! asset tag, which tells Azure apart from on-premises Hyper-V.
+
+use crate::{detect_hypervisor, VmmKind};
+
+/// DMI chassis asset tag of Azure virtual machines.
+const AZURE_CHASSIS_ASSET_TAG: &str = "7783-7084-3265-9085-8269-3286-77";
+
+/// Identifies Azure by the DMI chassis asset tag, as found in
+/// `/sys/class/dmi/id/chassis_asset_tag` on Linux, and the Hyper-V signature. Returns
+/// [`VmmKind::Azure`] inside Azure, [`VmmKind::HyperV`] under other Hyper-V hosts, and
+/// `None` otherwise. On architectures without CPUID, only the asset tag is checked.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{azure_vmm, VmmKind};
+///
+/// // e.g. read from /sys/class/dmi/id/chassis_asset_tag on Linux
+/// match azure_vmm("7783-7084-3265-9085-8269-3286-77") {
+///     Some(VmmKind::Azure) => println!("Azure"),
+///     Some(VmmKind::HyperV) => println!("on-premises Hyper-V"),
+///     _ => println!("not Hyper-V"),
+/// }
+/// ```
+pub fn azure_vmm(chassis_asset_tag: &str) -> Option<VmmKind> {
+    let azure_tag = chassis_asset_tag.trim() == AZURE_CHASSIS_ASSET_TAG;
+    match detect_hypervisor() {
+        Some(VmmKind::HyperV) if azure_tag => Some(VmmKind::Azure),
+        Some(VmmKind::HyperV) => Some(VmmKind::HyperV),
+        None if azure_tag && cfg!(not(any(target_arch = "x86", target_arch = "x86_64"))) => {
+            Some(VmmKind::Azure)
+        }
+        _ => None,
+    }
+}
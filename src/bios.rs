@@ -0,0 +1,147 @@
+//! Module for [`BiosAreaProbe`], which scans the legacy BIOS area `0xe0000..0x100000`
+//! for the SMBIOS entry point and for the signatures of SeaBIOS and the Bochs BIOS. Meant
+//! for boot loaders and kernels that run on legacy BIOS systems, before or without
+//! paging.
+
+use crate::acpi::{self, RSDP_V1_LEN};
+use crate::smbios::{Smbios, SmbiosProbe};
+use crate::{Probe, ProbeResult};
+
+/// Physical start address of the legacy BIOS area.
+const BIOS_AREA_START: usize = 0xe_0000;
+/// Size of the legacy BIOS area.
+const BIOS_AREA_LEN: usize = 0x2_0000;
+/// Alignment of the entry points in the legacy BIOS area (a "paragraph").
+const PARAGRAPH: usize = 16;
+/// Physical address of the segment of the EBDA in the BIOS data area.
+const EBDA_SEGMENT_ADDRESS: usize = 0x40e;
+/// Size of the part of the EBDA that may contain the RSDP.
+const EBDA_RSDP_AREA_LEN: usize = 1024;
+/// Range of physical addresses in which the EBDA can start, below the VGA memory.
+const EBDA_RANGE: core::ops::Range<usize> = 0x8_0000..0xa_0000;
+/// Number of bytes that [`Smbios::from_entry_point`] reads.
+const ENTRY_POINT_READ_LEN: usize = 32;
+
+/// Anchors of the SMBIOS entry points, in the order they are preferred.
+const SMBIOS_ANCHORS: [&[u8]; 2] = [b"_SM3_", b"_SM_"];
+/// Version string of SeaBIOS, e.g. `SeaBIOS (version 1.16.3-debian-1.16.3-2)`.
+const SEABIOS_SIGNATURE: &[u8] = b"SeaBIOS";
+/// Signature of the Bochs BIOS (rombios), which old QEMU versions used.
+const BOCHS_BIOS_SIGNATURE: &[u8] = b"Bochs BIOS";
+
+/// Returns the legacy BIOS area.
+///
+/// # Safety
+/// Physical memory `0xe0000..0x100000` must be identity-mapped and readable for `'a`.
+pub(crate) unsafe fn bios_area<'a>() -> &'a [u8] {
+    core::slice::from_raw_parts(BIOS_AREA_START as *const u8, BIOS_AREA_LEN)
+}
+
+/// Searches the legacy BIOS area for an SMBIOS entry point and returns the tables it
+/// references. The 64-bit entry point is preferred.
+///
+/// # Safety
+/// See [`BiosAreaProbe::new`].
+pub(crate) unsafe fn find_smbios() -> Option<Smbios<'static>> {
+    let area = bios_area();
+    SMBIOS_ANCHORS.iter().find_map(|anchor| {
+        (0..=BIOS_AREA_LEN - ENTRY_POINT_READ_LEN)
+            .step_by(PARAGRAPH)
+            .filter(|&offset| area[offset..].starts_with(anchor))
+            .find_map(|offset| Smbios::from_entry_point(area[offset..].as_ptr()))
+    })
+}
+
+/// Searches the first KiB of the EBDA and the legacy BIOS area for the RSDP of ACPI.
+/// The EBDA is skipped, if the BDA holds no segment or one outside of the usual range
+/// `0x80000..0xa0000`.
+///
+/// # Safety
+/// The first MiB of physical memory must be identity-mapped and readable.
+pub(crate) unsafe fn find_rsdp() -> Option<*const u8> {
+    let ebda_segment = (EBDA_SEGMENT_ADDRESS as *const u16).read_unaligned();
+    let ebda_start = usize::from(ebda_segment) << 4;
+    let ebda = (EBDA_RANGE.contains(&ebda_start)
+        && ebda_start + EBDA_RSDP_AREA_LEN <= EBDA_RANGE.end)
+        .then(|| core::slice::from_raw_parts(ebda_start as *const u8, EBDA_RSDP_AREA_LEN));
+    debug!("EBDA at {:?}.", ebda.map(<[u8]>::as_ptr));
+    ebda.into_iter().chain([bios_area()]).find_map(|area| {
+        (0..=area.len() - RSDP_V1_LEN)
+            .step_by(PARAGRAPH)
+            .map(|offset| &area[offset..])
+            .find(|rsdp| acpi::rsdp_valid(rsdp))
+            .map(<[u8]>::as_ptr)
+    })
+}
+
+/// Returns if the legacy BIOS area contains the signature of SeaBIOS.
+///
+/// # Safety
+/// See [`bios_area`].
+pub(crate) unsafe fn seabios_signature_present() -> bool {
+    contains(bios_area(), SEABIOS_SIGNATURE)
+}
+
+/// Returns if `needle` occurs in `haystack`.
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+/// Probe that scans the legacy BIOS area `0xe0000..0x100000`.
+///
+/// If it finds SMBIOS tables, it evaluates them like [`SmbiosProbe`] and
+/// returns its result, unless it is [`ProbeResult::Inconclusive`]. Otherwise, returns
+/// [`ProbeResult::Hint`], if the area contains the signature of SeaBIOS or the Bochs
+/// BIOS, the firmware of QEMU's PC machines, and [`ProbeResult::Inconclusive`] if not.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{BiosAreaProbe, Detector};
+///
+/// // SAFETY: runs with identity-mapped memory on a legacy BIOS system
+/// let probe = unsafe { BiosAreaProbe::new() };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct BiosAreaProbe {
+    _private: (),
+}
+
+impl BiosAreaProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// Physical memory `0xe0000..0x100000` must be identity-mapped and readable. If it
+    /// contains an SMBIOS entry point, the referenced tables must be identity-mapped and
+    /// readable as well. This is usually the case on legacy BIOS systems before paging is
+    /// enabled. UEFI systems might not map the area at all.
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for BiosAreaProbe {
+    fn name(&self) -> &'static str {
+        "bios-area"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `BiosAreaProbe::new`
+        let (smbios, area) = unsafe { (find_smbios(), bios_area()) };
+        let result = smbios.map_or(ProbeResult::Inconclusive, |smbios| {
+            SmbiosProbe::new(smbios).run()
+        });
+        if result != ProbeResult::Inconclusive {
+            result
+        } else if contains(area, SEABIOS_SIGNATURE) || contains(area, BOCHS_BIOS_SIGNATURE) {
+            debug!("Legacy BIOS area contains the signature of SeaBIOS or the Bochs BIOS.");
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Module for [`BlockDeviceProbe`], which checks the identities of the block devices on
+//! Linux. Needs the `std` feature. QEMU's emulated IDE, AHCI, and SCSI devices report
+//! fixed model strings, such as `QEMU HARDDISK`, that users rarely change.
+
+use crate::{Probe, ProbeResult};
+use std::fs;
+
+/// Directory with one subdirectory per block device.
+const SYSFS_BLOCK_DIR: &str = "/sys/block";
+/// Vendor of QEMU's emulated SCSI devices.
+const QEMU_VENDOR: &str = "QEMU";
+/// Models of QEMU's emulated ATA and SCSI devices.
+const QEMU_MODELS: &[&str] = &["QEMU HARDDISK", "QEMU DVD-ROM", "QEMU CD-ROM"];
+
+/// Probe that reads `/sys/block/*/device/vendor` and `/sys/block/*/device/model`.
+///
+/// Returns [`ProbeResult::Qemu`], if a block device has the vendor `QEMU` or one of
+/// QEMU's model strings, and [`ProbeResult::Inconclusive`] otherwise, e.g. with
+/// virtio-blk devices, which have no vendor and model.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{BlockDeviceProbe, Detector};
+///
+/// let probe = BlockDeviceProbe;
+/// let report = Detector::builder().probes(&[&probe]).build().detect();
+/// if report.certainty().is_very_likely() {
+///     println!("QEMU");
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BlockDeviceProbe;
+
+impl Probe for BlockDeviceProbe {
+    fn name(&self) -> &'static str {
+        "block-device"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let Ok(devices) = fs::read_dir(SYSFS_BLOCK_DIR) else {
+            return ProbeResult::Inconclusive;
+        };
+        let qemu_device = devices.flatten().any(|device| {
+            let device = device.path().join("device");
+            let vendor = fs::read_to_string(device.join("vendor")).unwrap_or_default();
+            let model = fs::read_to_string(device.join("model")).unwrap_or_default();
+            vendor.trim() == QEMU_VENDOR || QEMU_MODELS.contains(&model.trim())
+        });
+        if qemu_device {
+            debug!("Found a block device emulated by QEMU.");
+            ProbeResult::Qemu
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+}
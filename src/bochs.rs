@@ -0,0 +1,67 @@
+//! Bochs backend. Bochs and QEMU share their firmware heritage: both use the ACPI OEM ID
+//! `BOCHS ` and OEM table IDs starting with `BXPC`. Unlike QEMU, Bochs neither sets the
+//! hypervisor flag nor emulates fw_cfg. [`BochsProbe`] uses this to tell both apart.
+
+use crate::{cpuid, fw_cfg, Probe, ProbeResult};
+
+/// ACPI OEM ID of the firmware of Bochs and QEMU.
+const ACPI_OEM_ID: &[u8; 6] = b"BOCHS ";
+
+/// Probe that tells Bochs apart from QEMU, given the OEM ID of the ACPI tables.
+///
+/// Returns [`ProbeResult::Inconclusive`], if the OEM ID isn't `BOCHS ` or the hypervisor
+/// flag is set. Otherwise, returns [`ProbeResult::Qemu`], if fw_cfg is present (QEMU
+/// with `-cpu ...,-hypervisor`), and [`ProbeResult::NotQemu`] (Bochs) if not.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{BochsProbe, Detector};
+///
+/// // OEM ID of the RSDP, e.g. found by scanning 0xe0000..0x100000 for "RSD PTR "
+/// let oem_id = *b"BOCHS ";
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// let probe = unsafe { BochsProbe::new(oem_id) };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct BochsProbe {
+    acpi_oem_id: [u8; 6],
+}
+
+impl BochsProbe {
+    /// Creates a new probe that inspects the given ACPI OEM ID, e.g. from the RSDP.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access fw_cfg while the probe
+    /// runs. The probe only accesses the ports, if the OEM ID is `BOCHS `.
+    pub const unsafe fn new(acpi_oem_id: [u8; 6]) -> Self {
+        Self { acpi_oem_id }
+    }
+}
+
+impl Probe for BochsProbe {
+    fn name(&self) -> &'static str {
+        "bochs"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        if &self.acpi_oem_id != ACPI_OEM_ID || cpuid::hypervisor_flag_set() {
+            return ProbeResult::Inconclusive;
+        }
+        // SAFETY: guaranteed by the caller of `BochsProbe::new`
+        if unsafe { fw_cfg::signature_present() } {
+            debug!("Bochs firmware heritage and fw_cfg, QEMU without hypervisor flag.");
+            ProbeResult::Qemu
+        } else {
+            debug!("Bochs firmware heritage without fw_cfg and hypervisor flag, Bochs.");
+            ProbeResult::NotQemu
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Module for [`runs_inside_qemu_cached`], which only executes the CPUID checks once, and
+//! for its interrupt-safe counterpart [`runs_inside_qemu_irqsafe`].
+
+use crate::{runs_inside_qemu, QemuCertainty};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Marker that the cache is not populated yet.
+pub(crate) const UNINITIALIZED: u8 = 0;
+
+/// Cached result of [`runs_inside_qemu`]. Holds [`UNINITIALIZED`] or an encoded [`QemuCertainty`].
+static CACHE: AtomicU8 = AtomicU8::new(UNINITIALIZED);
+
+/// Like [`runs_inside_qemu`] but only executes the checks on the first invocation.
+/// All subsequent invocations return the cached result, which is a single atomic load.
+/// Useful for hot paths (e.g. per-interrupt logging decisions), because each CPUID
+/// instruction inside a virtual machine causes a costly VM exit.
+///
+/// If multiple CPUs call this function concurrently for the first time, each of them
+/// may execute the checks. This is harmless, as all of them come to the same result.
+pub fn runs_inside_qemu_cached() -> QemuCertainty {
+    #[cfg(feature = "testing")]
+    if let Some(certainty) = crate::testing::mock_result() {
+        return certainty;
+    }
+    if let Some(certainty) = decode(CACHE.load(Ordering::Relaxed)) {
+        return certainty;
+    }
+    init()
+}
+
+/// Executes the checks and stores the result in the cache that [`runs_inside_qemu_cached`]
+/// and [`runs_inside_qemu_irqsafe`] read. Call this once during boot, before interrupts
+/// are enabled. Calling it again re-executes the checks and overwrites the cache. A mocked
+/// result (feature `testing`) is returned without touching the cache.
+pub fn init() -> QemuCertainty {
+    #[cfg(feature = "testing")]
+    if let Some(certainty) = crate::testing::mock_result() {
+        return certainty;
+    }
+    let certainty = runs_inside_qemu();
+    CACHE.store(encode(certainty), Ordering::Relaxed);
+    certainty
+}
+
+/// Returns the result that [`init`] or [`runs_inside_qemu_cached`] stored in the cache,
+/// or [`QemuCertainty::Unknown`] if the cache is not populated yet.
+///
+/// This never executes CPUID or any other instruction that may trap to the hypervisor.
+/// It is a single lock-free atomic load, so it can be called from any context, including
+/// interrupt and NMI handlers, and concurrently from multiple CPUs.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{init, runs_inside_qemu_irqsafe};
+///
+/// // during boot
+/// let certainty = init();
+///
+/// // later, e.g. in an NMI handler
+/// assert_eq!(runs_inside_qemu_irqsafe(), certainty);
+/// ```
+pub fn runs_inside_qemu_irqsafe() -> QemuCertainty {
+    #[cfg(feature = "testing")]
+    if let Some(certainty) = crate::testing::mock_result() {
+        return certainty;
+    }
+    decode(CACHE.load(Ordering::Relaxed)).unwrap_or(QemuCertainty::Unknown)
+}
+
+/// Encodes a [`QemuCertainty`] as non-zero value.
+pub(crate) const fn encode(certainty: QemuCertainty) -> u8 {
+    match certainty {
+        QemuCertainty::DefinitelyNot => 1,
+        QemuCertainty::Maybe => 2,
+        QemuCertainty::VeryLikely => 3,
+        QemuCertainty::Unknown => 4,
+    }
+}
+
+/// Decodes a value of [`encode`]. Returns `None` for [`UNINITIALIZED`].
+pub(crate) const fn decode(value: u8) -> Option<QemuCertainty> {
+    match value {
+        1 => Some(QemuCertainty::DefinitelyNot),
+        2 => Some(QemuCertainty::Maybe),
+        3 => Some(QemuCertainty::VeryLikely),
+        4 => Some(QemuCertainty::Unknown),
+        _ => None,
+    }
+}
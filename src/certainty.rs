@@ -0,0 +1,127 @@
+//! Module for [`QemuCertainty`].
+
+use core::fmt;
+use core::str::FromStr;
+
+/// Result of [`crate::runs_inside_qemu`] that tells with what certainty the code runs inside QEMU.
+///
+/// The variants are ordered by certainty, i.e. `Unknown < DefinitelyNot < Maybe < VeryLikely`.
+/// [`Self::Unknown`] is the smallest, so that comparisons like `certainty >= Maybe` stay
+/// conservative. The [`Display`](fmt::Display) and [`FromStr`] implementations use the
+/// kebab-case names `unknown`, `definitely-not`, `maybe`, and `very-likely`.
+///
+/// New variants may be added in the future, hence the enum is `#[non_exhaustive]`.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::QemuCertainty;
+///
+/// let certainty = QemuCertainty::VeryLikely;
+/// assert_eq!(certainty.to_string(), "very-likely");
+/// assert_eq!("very-likely".parse(), Ok(certainty));
+/// assert!(QemuCertainty::Maybe < QemuCertainty::VeryLikely);
+/// assert!(QemuCertainty::Unknown < QemuCertainty::DefinitelyNot);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum QemuCertainty {
+    /// No detection source could be consulted, so nothing is known about the environment.
+    /// This is returned if CPUID is not available (e.g. on non-x86 architectures) or
+    /// disabled via [`crate::DetectorBuilder::cpuid`], and no [`crate::Probe`] gathered a
+    /// signal that points towards or against QEMU.
+    Unknown,
+
+    /// The code definitely doesn't run inside QEMU, because a detection source rules it out
+    /// and none found evidence for QEMU. On x86, the Hypervisor-flag is not set or CPUID
+    /// reports a hypervisor that can't be QEMU, such as VMware. On all architectures, a
+    /// [`crate::Probe`] may return [`crate::ProbeResult::NotQemu`], e.g. for Firecracker.
+    DefinitelyNot,
+
+    /// The code maybe runs inside QEMU. This is returned
+    /// if the application runs inside KVM as hypervisor, but
+    /// the CPU is not QEMU. If you know, that your development
+    /// setup only uses QEMU/KVM as Hypervisor, this is equal
+    /// to [`Self::VeryLikely`].
+    ///
+    /// This happens, if you use something like "-cpu host" instead of "-cpu qemu64" as QEMU option.
+    Maybe,
+
+    /// The information obtained by CPUID clearly states, that the code runs inside QEMU.
+    /// Be aware, that Hypervisors could implement similar behaviour as QEMU does.
+    ///
+    /// `High` is the case, if the Hypervisor ID is the one from QEMU or if the Hypervisor-ID
+    /// is `KVM` and the ID of the CPU brand string contains `QEMU`.
+    VeryLikely,
+}
+
+impl QemuCertainty {
+    /// Returns if no detection source could be consulted.
+    pub fn is_unknown(self) -> bool {
+        self == Self::Unknown
+    }
+
+    /// Returns if certainty is low/definitely not.
+    pub fn is_definitely_not(self) -> bool {
+        self == Self::DefinitelyNot
+    }
+
+    /// Returns if certainty is high/very likely.
+    pub fn is_very_likely(self) -> bool {
+        self == Self::VeryLikely
+    }
+
+    /// Returns if certainty is maybe or high.
+    pub fn is_maybe_or_very_likely(self) -> bool {
+        self == Self::Maybe || self == Self::VeryLikely
+    }
+}
+
+impl QemuCertainty {
+    /// Returns the kebab-case name that is also used by [`fmt::Display`].
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::DefinitelyNot => "definitely-not",
+            Self::Maybe => "maybe",
+            Self::VeryLikely => "very-likely",
+        }
+    }
+}
+
+impl fmt::Display for QemuCertainty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for QemuCertainty {
+    type Err = ParseQemuCertaintyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(Self::Unknown),
+            "definitely-not" => Ok(Self::DefinitelyNot),
+            "maybe" => Ok(Self::Maybe),
+            "very-likely" => Ok(Self::VeryLikely),
+            _ => Err(ParseQemuCertaintyError),
+        }
+    }
+}
+
+/// Error of the [`FromStr`] implementation of [`QemuCertainty`]. The string is not one of
+/// `unknown`, `definitely-not`, `maybe`, or `very-likely`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseQemuCertaintyError;
+
+impl fmt::Display for ParseQemuCertaintyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "invalid QEMU certainty, expected one of: unknown, definitely-not, maybe, very-likely",
+        )
+    }
+}
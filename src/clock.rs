@@ -0,0 +1,110 @@
+//! Module for [`clock_capabilities`], which collects the CPUID bits that timekeeping code
+//! needs to choose its clock source in a QEMU/KVM guest: kvmclock, an invariant TSC, the
+//! TSC deadline timer, and the TSC frequency that the hypervisor reports.
+
+use crate::cpuid::HYPERVISOR_INFO_LEAF;
+use crate::kvm::{kvm_features_with, KVM_FEATURE_CLOCKSOURCE2};
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Leaf with the feature information.
+const FEATURE_INFO_LEAF: u32 = 0x1;
+/// Flag in `ecx` of leaf `0x1` that announces the TSC deadline mode of the local APIC.
+const FEATURE_TSC_DEADLINE: u32 = 1 << 24;
+/// Leaf with the advanced power management information.
+const POWER_MANAGEMENT_LEAF: u32 = 0x8000_0007;
+/// Flag in `edx` of leaf `0x8000_0007` that announces an invariant TSC.
+const FEATURE_INVARIANT_TSC: u32 = 1 << 8;
+/// Leaf with the TSC frequency in kHz (`eax`), as introduced by VMware and provided by
+/// QEMU with `-cpu ...,+vmware-cpuid-freq,+invtsc`.
+const TIMING_INFO_LEAF: u32 = 0x4000_0010;
+
+/// The clocks that are available in the guest. Result of [`clock_capabilities`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ClockCapabilities {
+    /// kvmclock is available via `MSR_KVM_SYSTEM_TIME_NEW` (`KVM_FEATURE_CLOCKSOURCE2`).
+    pub kvmclock: bool,
+    /// The stable bit of kvmclock is reliable (`KVM_FEATURE_CLOCKSOURCE_STABLE_BIT`).
+    pub kvmclock_stable: bool,
+    /// The TSC runs at a constant rate in all power states (leaf `0x8000_0007`, `edx[8]`).
+    /// QEMU only announces it with `-cpu ...,+invtsc`, which prevents live migration.
+    pub invariant_tsc: bool,
+    /// The local APIC supports the TSC deadline mode (leaf `0x1`, `ecx[24]`).
+    pub tsc_deadline: bool,
+    /// The TSC frequency in kHz that the hypervisor reports in leaf `0x4000_0010`, if any.
+    pub tsc_khz: Option<u32>,
+}
+
+/// Reads the clock capabilities from CPUID. All flags are `false` if CPUID doesn't
+/// announce them, e.g. kvmclock outside of KVM.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::clock_capabilities;
+///
+/// let clocks = clock_capabilities();
+/// if clocks.invariant_tsc {
+///     println!("use the TSC");
+/// } else if clocks.kvmclock {
+///     println!("use kvmclock");
+/// }
+/// ```
+pub fn clock_capabilities() -> ClockCapabilities {
+    clock_capabilities_with(&CpuIdReaderNative)
+}
+
+/// Like [`clock_capabilities`] but reads CPUID through the given [`CpuIdReader`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::clock_capabilities_with;
+///
+/// // QEMU/KVM with -cpu host,+invtsc
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x1, 0, 0, 0),
+///         0x1 => (0, 0, 1 << 31 | 1 << 24, 0),
+///         // "KVMKVMKVM"
+///         0x4000_0000 => (0x4000_0010, 0x4b4d_564b, 0x564b_4d56, 0x0000_004d),
+///         0x4000_0001 => (0x0100_00fb, 0, 0, 0),
+///         0x4000_0010 => (2_995_200, 1_000_000, 0, 0),
+///         0x8000_0000 => (0x8000_0007, 0, 0, 0),
+///         0x8000_0007 => (0, 0, 0, 1 << 8),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let clocks = clock_capabilities_with(&reader);
+/// assert!(clocks.kvmclock);
+/// assert!(clocks.kvmclock_stable);
+/// assert!(clocks.invariant_tsc);
+/// assert!(clocks.tsc_deadline);
+/// assert_eq!(clocks.tsc_khz, Some(2_995_200));
+/// ```
+pub fn clock_capabilities_with<R: CpuIdReader>(reader: &R) -> ClockCapabilities {
+    let max_leaf = reader.cpuid1(0).eax;
+    let tsc_deadline = max_leaf >= FEATURE_INFO_LEAF
+        && reader.cpuid1(FEATURE_INFO_LEAF).ecx & FEATURE_TSC_DEADLINE != 0;
+    let max_extended_leaf = reader.cpuid1(0x8000_0000).eax;
+    let invariant_tsc = max_extended_leaf >= POWER_MANAGEMENT_LEAF
+        && reader.cpuid1(POWER_MANAGEMENT_LEAF).edx & FEATURE_INVARIANT_TSC != 0;
+    let kvm = kvm_features_with(reader);
+    let tsc_khz = crate::cpuid::hypervisor_signature_with(reader)
+        .filter(|_| reader.cpuid1(HYPERVISOR_INFO_LEAF).eax >= TIMING_INFO_LEAF)
+        .map(|_| reader.cpuid1(TIMING_INFO_LEAF).eax)
+        .filter(|&khz| khz != 0);
+    let capabilities = ClockCapabilities {
+        kvmclock: kvm.is_some_and(|kvm| kvm.features & KVM_FEATURE_CLOCKSOURCE2 != 0),
+        kvmclock_stable: kvm.is_some_and(|kvm| kvm.kvmclock_stable),
+        invariant_tsc,
+        tsc_deadline,
+        tsc_khz,
+    };
+    debug!("Clock capabilities: {:?}", capabilities);
+    capabilities
+}
@@ -0,0 +1,86 @@
+//! Module for [`CmdlineProbe`], which checks the kernel command line for fragments that
+//! are typical for QEMU guests. The command line comes from the caller: in `no_std`
+//! environments from [`crate::FwCfgProbe::cmdline`], with the `std` feature from
+//! `proc_cmdline()`.
+
+use crate::{Probe, ProbeResult};
+
+/// Fragments of command lines that QEMU users typically pass via `-append`: virtio-mmio
+/// devices of the `microvm` machine and a virtio-blk root device.
+const QEMU_FRAGMENTS: &[&str] = &["virtio_mmio.device=", "root=/dev/vda"];
+
+/// Path of the kernel command line in the procfs of Linux.
+#[cfg(feature = "std")]
+const PROC_CMDLINE: &str = "/proc/cmdline";
+
+/// Probe that checks a kernel command line.
+///
+/// Returns [`ProbeResult::Hint`], if the command line contains a fragment that is typical
+/// for QEMU guests, e.g. `root=/dev/vda`, and [`ProbeResult::Inconclusive`] otherwise.
+/// Other VMMs, such as Firecracker, use the same fragments.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{CmdlineProbe, Probe, ProbeResult};
+///
+/// let probe = CmdlineProbe::new("console=ttyS0 root=/dev/vda1 rw");
+/// assert_eq!(probe.cmdline(), "console=ttyS0 root=/dev/vda1 rw");
+/// assert_eq!(probe.run(), ProbeResult::Hint);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct CmdlineProbe<'a> {
+    cmdline: &'a str,
+}
+
+impl<'a> CmdlineProbe<'a> {
+    /// Creates a new probe that checks the given kernel command line.
+    pub const fn new(cmdline: &'a str) -> Self {
+        Self { cmdline }
+    }
+
+    /// Returns the kernel command line.
+    pub const fn cmdline(&self) -> &'a str {
+        self.cmdline
+    }
+}
+
+impl Probe for CmdlineProbe<'_> {
+    fn name(&self) -> &'static str {
+        "cmdline"
+    }
+
+    fn run(&self) -> ProbeResult {
+        match QEMU_FRAGMENTS
+            .iter()
+            .find(|fragment| self.cmdline.contains(*fragment))
+        {
+            Some(fragment) => {
+                debug!("Kernel command line contains '{}'.", fragment);
+                ProbeResult::Hint
+            }
+            None => ProbeResult::Inconclusive,
+        }
+    }
+}
+
+/// Returns the kernel command line from `/proc/cmdline`, or `None` if it isn't readable,
+/// e.g. on operating systems other than Linux. Needs the `std` feature.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{proc_cmdline, CmdlineProbe, Detector};
+///
+/// if let Some(cmdline) = proc_cmdline() {
+///     let probe = CmdlineProbe::new(&cmdline);
+///     let report = Detector::new(&[&probe]).detect();
+///     println!("{}: {:?}", cmdline, report.certainty());
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub fn proc_cmdline() -> Option<std::string::String> {
+    std::fs::read_to_string(PROC_CMDLINE)
+        .ok()
+        .map(|cmdline| std::string::String::from(cmdline.trim()))
+}
@@ -0,0 +1,111 @@
+//! Module for [`CmosProbe`], a heuristic that checks how the firmware of QEMU fills the
+//! CMOS memory of the RTC. QEMU writes the memory size to the CMOS before the firmware
+//! starts and caps the extended memory at 65535 KiB, while the BIOSes of real chipsets
+//! cap it at 63 MiB (64512 KiB).
+
+use crate::{io, Probe, ProbeResult, Weights};
+
+/// I/O port of the CMOS index register.
+const CMOS_INDEX: u16 = 0x70;
+/// I/O port of the CMOS data register.
+const CMOS_DATA: u16 = 0x71;
+/// RTC status register A.
+const REG_STATUS_A: u8 = 0x0a;
+/// Value of status register A after QEMU's reset: 32.768 kHz time base and a periodic
+/// rate of 1024 Hz.
+const STATUS_A_RESET: u8 = 0x26;
+/// Low and high byte of the extended memory between 1 MiB and 64 MiB in KiB.
+const REG_EXT_MEM: (u8, u8) = (0x17, 0x18);
+/// Copy of [`REG_EXT_MEM`] that the BIOS reports.
+const REG_EXT_MEM_COPY: (u8, u8) = (0x30, 0x31);
+/// Low and high byte of the memory between 16 MiB and 4 GiB in 64 KiB units.
+const REG_HIGH_MEM: (u8, u8) = (0x34, 0x35);
+/// QEMU's cap of the extended memory in KiB.
+const QEMU_EXT_MEM_CAP: u16 = 0xffff;
+
+/// Probe that reads the RTC status and the memory size from the CMOS.
+///
+/// Returns [`ProbeResult::Hint`], if status register A has its reset value, both copies
+/// of the extended memory are capped at 65535 KiB, and memory above 16 MiB is reported,
+/// as QEMU does. Returns [`ProbeResult::Inconclusive`] otherwise, e.g. on machines with
+/// less than 64 MiB of memory. Contributes half the weight of an ordinary hint, as
+/// firmwares may rewrite the CMOS.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{CmosProbe, Detector};
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// let probe = unsafe { CmosProbe::new() };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct CmosProbe {
+    _private: (),
+}
+
+impl CmosProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access the CMOS while the
+    /// probe runs, e.g. an RTC driver. The probe leaves NMIs enabled.
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for CmosProbe {
+    fn name(&self) -> &'static str {
+        "cmos"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `CmosProbe::new`
+        let (status_a, ext_mem, ext_mem_copy, high_mem) = unsafe {
+            (
+                read_cmos(REG_STATUS_A),
+                read_cmos_u16(REG_EXT_MEM),
+                read_cmos_u16(REG_EXT_MEM_COPY),
+                read_cmos_u16(REG_HIGH_MEM),
+            )
+        };
+        debug!(
+            "CMOS status A: {}, extended memory: {} KiB, memory above 16 MiB: {}.",
+            status_a, ext_mem, high_mem
+        );
+        if status_a == STATUS_A_RESET
+            && ext_mem == QEMU_EXT_MEM_CAP
+            && ext_mem_copy == QEMU_EXT_MEM_CAP
+            && high_mem != 0
+        {
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+
+    fn weight(&self, result: ProbeResult, weights: &Weights) -> i32 {
+        weights.probe(result) / 2
+    }
+}
+
+/// Reads the CMOS register `index`.
+///
+/// # Safety
+/// See [`io`]. Nobody else may access the CMOS concurrently.
+unsafe fn read_cmos(index: u8) -> u8 {
+    io::outb(CMOS_INDEX, index & 0x7f);
+    io::inb(CMOS_DATA)
+}
+
+/// Reads a little-endian `u16` from the CMOS registers `(low, high)`.
+///
+/// # Safety
+/// See [`read_cmos`].
+unsafe fn read_cmos_u16((low, high): (u8, u8)) -> u16 {
+    u16::from_le_bytes([read_cmos(low), read_cmos(high)])
+}
@@ -0,0 +1,196 @@
+//! Module for [`confidential_mode`], which detects guests of AMD SEV, SEV-ES, SEV-SNP,
+//! and Intel TDX, as QEMU starts them with `-machine confidential-guest-support=...`.
+
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Leaf with the AMD memory encryption capabilities.
+const AMD_MEMORY_ENCRYPTION_LEAF: u32 = 0x8000_001f;
+/// Flag in `eax` of leaf `0x8000_001f` that announces SEV.
+const FEATURE_SEV: u32 = 1 << 1;
+/// Flag in `eax` of leaf `0x8000_001f` that announces SEV-ES.
+const FEATURE_SEV_ES: u32 = 1 << 3;
+/// Flag in `eax` of leaf `0x8000_001f` that announces SEV-SNP.
+const FEATURE_SEV_SNP: u32 = 1 << 4;
+/// Hypervisor flag in `ecx` of leaf `0x1`.
+const HYPERVISOR_FLAG: u32 = 1 << 31;
+/// Leaf with the signature of the TDX module.
+const TDX_LEAF: u32 = 0x21;
+/// Signature in `ebx`, `edx`, and `ecx` of the TDX leaf.
+const TDX_SIGNATURE: &[u8; 12] = b"IntelTDX    ";
+/// `MSR_AMD64_SEV`: the SEV features that are active in the guest.
+#[cfg(feature = "ring0")]
+const MSR_AMD64_SEV: u32 = 0xc001_0131;
+
+/// A confidential computing technology. Result of [`confidential_mode`].
+///
+/// In a confidential guest, the host is not trusted, but the CPUID leaves that the
+/// detection of QEMU relies on are still under its control: with SEV and SEV-ES, the
+/// hypervisor provides all leaves. With SEV-SNP, the AMD secure processor validates the
+/// CPUID page against the capabilities of the CPU, and with TDX, the TDX module
+/// virtualizes most leaves, but in both cases the hypervisor leaves from `0x4000_0000`
+/// are provided by the host. A QEMU verdict therefore only tells what the host claims;
+/// only the attestation report can be trusted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ConfidentialMode {
+    /// AMD Secure Encrypted Virtualization: the memory of the guest is encrypted.
+    Sev,
+    /// AMD SEV with Encrypted State: additionally, the register state is encrypted.
+    SevEs,
+    /// AMD SEV with Secure Nested Paging: additionally, the integrity of the memory is
+    /// protected.
+    SevSnp,
+    /// Intel Trust Domain Extensions.
+    Tdx,
+}
+
+impl ConfidentialMode {
+    /// Returns if the platform validates the CPUID leaves that describe the CPU, i.e.
+    /// for SEV-SNP and TDX. The hypervisor leaves from `0x4000_0000`, and thus the
+    /// detection of the VMM, are provided by the host in all modes.
+    pub const fn cpuid_validated(self) -> bool {
+        matches!(self, Self::SevSnp | Self::Tdx)
+    }
+}
+
+/// Detects a confidential guest via CPUID: the TDX leaf `0x21` and the SEV flags in leaf
+/// `0x8000_001f`, which QEMU only sets for SEV guests. Returns `None` for other guests
+/// and without the hypervisor flag, as the SEV flags only list the capabilities of the
+/// CPU and are also set on the host, e.g. on bare-metal AMD EPYC.
+///
+/// As the host provides these leaves, a malicious host can hide or fake them. Only
+/// [`sev_status`] (`MSR_AMD64_SEV`, feature `ring0`) proves that SEV is active. See the
+/// [`ConfidentialMode`] for what this means for the QEMU verdict.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{confidential_mode, runs_inside_qemu};
+///
+/// match confidential_mode() {
+///     Some(mode) => println!("{:?} guest, QEMU verdict is untrusted", mode),
+///     None => println!("{:?}", runs_inside_qemu()),
+/// }
+/// ```
+pub fn confidential_mode() -> Option<ConfidentialMode> {
+    confidential_mode_with(&CpuIdReaderNative)
+}
+
+/// Like [`confidential_mode`] but reads CPUID through the given [`CpuIdReader`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{confidential_mode_with, ConfidentialMode};
+///
+/// // QEMU with -object sev-snp-guest
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x10, 0, 0, 0),
+///         0x1 => (0, 0, 1 << 31, 0),
+///         0x8000_0000 => (0x8000_001f, 0, 0, 0),
+///         0x8000_001f => (0x1_001b, 0x33, 0, 0),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let mode = confidential_mode_with(&reader);
+/// assert_eq!(mode, Some(ConfidentialMode::SevSnp));
+/// assert!(mode.unwrap().cpuid_validated());
+/// ```
+///
+/// On the host, the CPU announces the same capabilities, but without the hypervisor
+/// flag:
+///
+/// ```rust
+/// use runs_inside_qemu::confidential_mode_with;
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+///
+/// // bare-metal AMD EPYC with SEV-SNP support
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x10, 0, 0, 0),
+///         0x8000_0000 => (0x8000_0028, 0, 0, 0),
+///         0x8000_001f => (0x1_001b, 0x33, 0, 0),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// assert_eq!(confidential_mode_with(&reader), None);
+/// ```
+pub fn confidential_mode_with<R: CpuIdReader>(reader: &R) -> Option<ConfidentialMode> {
+    let mode = if reader.cpuid1(0x1).ecx & HYPERVISOR_FLAG == 0 {
+        None
+    } else if reader.cpuid1(0).eax >= TDX_LEAF && tdx_signature_present(reader) {
+        Some(ConfidentialMode::Tdx)
+    } else if reader.cpuid1(0x8000_0000).eax >= AMD_MEMORY_ENCRYPTION_LEAF {
+        sev_mode(
+            reader.cpuid1(AMD_MEMORY_ENCRYPTION_LEAF).eax,
+            [FEATURE_SEV, FEATURE_SEV_ES, FEATURE_SEV_SNP],
+        )
+    } else {
+        None
+    };
+    debug!("Confidential computing mode: {:?}", mode);
+    mode
+}
+
+/// Reads the active SEV features from `MSR_AMD64_SEV`. Unlike CPUID, the MSR is not
+/// intercepted by the hypervisor, so the host can't fake it, and it reads as zero on
+/// the host. It is therefore the only proof that SEV is active. Returns `None`, if CPUID
+/// doesn't announce SEV (see [`confidential_mode`]) or no SEV feature is active.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::sev_status;
+///
+/// // SAFETY: runs in ring 0
+/// if let Some(mode) = unsafe { sev_status() } {
+///     println!("{:?} is active", mode);
+/// }
+/// ```
+///
+/// # Safety
+/// The caller must run in ring 0. Otherwise, `rdmsr` raises a general protection fault.
+#[cfg(feature = "ring0")]
+pub unsafe fn sev_status() -> Option<ConfidentialMode> {
+    let reader = CpuIdReaderNative;
+    if reader.cpuid1(0x8000_0000).eax < AMD_MEMORY_ENCRYPTION_LEAF
+        || reader.cpuid1(AMD_MEMORY_ENCRYPTION_LEAF).eax & FEATURE_SEV == 0
+    {
+        return None;
+    }
+    // bit 0: SEV, bit 1: SEV-ES, bit 2: SEV-SNP
+    let status = crate::msr::rdmsr(MSR_AMD64_SEV) as u32;
+    let mode = sev_mode(status, [1 << 0, 1 << 1, 1 << 2]);
+    debug!("MSR_AMD64_SEV: {}, mode {:?}", status, mode);
+    mode
+}
+
+/// Returns the most advanced SEV mode whose flag in `flags` is set in `value`. `flags`
+/// are the flags of SEV, SEV-ES, and SEV-SNP.
+fn sev_mode(value: u32, [sev, sev_es, sev_snp]: [u32; 3]) -> Option<ConfidentialMode> {
+    if value & sev == 0 {
+        None
+    } else if value & sev_snp != 0 {
+        Some(ConfidentialMode::SevSnp)
+    } else if value & sev_es != 0 {
+        Some(ConfidentialMode::SevEs)
+    } else {
+        Some(ConfidentialMode::Sev)
+    }
+}
+
+/// Returns if the TDX leaf contains the signature of the TDX module.
+fn tdx_signature_present<R: CpuIdReader>(reader: &R) -> bool {
+    let leaf = reader.cpuid2(TDX_LEAF, 0);
+    let mut signature = [0; 12];
+    signature[0..4].copy_from_slice(&leaf.ebx.to_le_bytes());
+    signature[4..8].copy_from_slice(&leaf.edx.to_le_bytes());
+    signature[8..12].copy_from_slice(&leaf.ecx.to_le_bytes());
+    &signature == TDX_SIGNATURE
+}
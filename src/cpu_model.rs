@@ -0,0 +1,165 @@
+//! Module for [`cpu_model`], which tells if QEMU passes the host CPU through to the guest
+//! (`-cpu host`) or emulates one of its own CPU models, such as `qemu64`, `kvm64`, or a
+//! named model like `Skylake-Client`.
+//!
+//! With `-cpu host`, the brand string is the one of the host, so
+//! [`crate::QemuCertainty::Maybe`] is common and needs other probes to disambiguate. The
+//! generic models `qemu64` and `kvm64` lack most features of today's CPUs, e.g. AVX.
+
+use raw_cpuid::{CpuId, CpuIdReader, CpuIdReaderNative};
+
+/// Family, model, and stepping of `qemu64`.
+const QEMU64_FMS: (u8, u8, u8) = (15, 107, 1);
+/// Family, model, and stepping of `qemu32`.
+const QEMU32_FMS: (u8, u8, u8) = (6, 6, 3);
+/// Family, model, and stepping of `kvm64` and `kvm32`.
+const KVM64_FMS: (u8, u8, u8) = (15, 6, 1);
+/// Brand strings of QEMU's AMD and Hygon models that don't end with the model name in
+/// parentheses, e.g. `AMD EPYC-Rome Processor`. Real EPYC CPUs have a number after
+/// `EPYC`, e.g. `AMD EPYC 7763 64-Core Processor`.
+const NAMED_MODEL_PREFIXES: [&str; 3] =
+    ["AMD EPYC Processor", "AMD EPYC-", "Hygon Dhyana Processor"];
+
+/// The CPU model of a QEMU guest. Result of [`cpu_model`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum CpuModel {
+    /// The host CPU is passed through, i.e. `-cpu host` or `-cpu max` with a hardware
+    /// accelerator. The brand string is the one of the host.
+    HostPassthrough,
+    /// `-cpu max` with TCG, which enables all features that TCG emulates.
+    Max,
+    /// `-cpu qemu64`, the default of `qemu-system-x86_64`.
+    Qemu64,
+    /// `-cpu qemu32`, the default of `qemu-system-i386`.
+    Qemu32,
+    /// `-cpu kvm64`.
+    Kvm64,
+    /// `-cpu kvm32`.
+    Kvm32,
+    /// A named model that resembles a CPU generation, such as `Skylake-Client` or
+    /// `EPYC-Rome`.
+    Named,
+}
+
+impl CpuModel {
+    /// Returns if the model is one of the generic models `qemu64`, `qemu32`, `kvm64`, or
+    /// `kvm32`, which lack most features of today's CPUs, e.g. SSE4.2, AVX, and AES-NI.
+    pub const fn is_generic(self) -> bool {
+        matches!(
+            self,
+            Self::Qemu64 | Self::Qemu32 | Self::Kvm64 | Self::Kvm32
+        )
+    }
+
+    /// Returns if the host CPU is passed through.
+    pub const fn is_host_passthrough(self) -> bool {
+        matches!(self, Self::HostPassthrough)
+    }
+
+    /// Classifies a CPU by its brand string and its family, model, and stepping.
+    fn classify(brand_string: Option<&str>, fms: Option<(u8, u8, u8)>) -> Self {
+        match brand_string
+            .map(str::trim)
+            .filter(|brand| !brand.is_empty())
+        {
+            Some(brand) if brand.starts_with("QEMU TCG CPU") => Self::Max,
+            Some(brand) if brand.starts_with("QEMU Virtual CPU") => {
+                if fms == Some(QEMU32_FMS) {
+                    Self::Qemu32
+                } else {
+                    Self::Qemu64
+                }
+            }
+            Some("Common KVM processor") => Self::Kvm64,
+            Some("Common 32-bit KVM processor") => Self::Kvm32,
+            Some(brand) if is_named_model(brand) => Self::Named,
+            Some(_) => Self::HostPassthrough,
+            // QEMU's models always have a brand string. Without it, only the family,
+            // model, and stepping remain, e.g. with `-cpu qemu64,model-id=`.
+            None => match fms {
+                Some(QEMU64_FMS) => Self::Qemu64,
+                Some(QEMU32_FMS) => Self::Qemu32,
+                Some(KVM64_FMS) => Self::Kvm64,
+                _ => Self::HostPassthrough,
+            },
+        }
+    }
+}
+
+/// Returns if the brand string is one of QEMU's named models. The Intel models, e.g.
+/// `Intel Core Processor (Skylake)` or `Intel Xeon E312xx (Sandy Bridge)`, have the
+/// model name in parentheses at the end and, unlike real Intel CPUs, no `(R)`.
+fn is_named_model(brand: &str) -> bool {
+    (brand.ends_with(')') && !brand.contains("(R)") && !brand.contains("(TM)"))
+        || NAMED_MODEL_PREFIXES
+            .iter()
+            .any(|prefix| brand.starts_with(prefix))
+}
+
+/// Classifies the CPU model of the guest by the brand string and the family, model, and
+/// stepping. Returns `None`, if neither the hypervisor flag is set nor the brand string
+/// mentions QEMU, i.e. on real hardware.
+///
+/// The classification is best-effort: the brand string can be changed with
+/// `-cpu ...,model-id=...`.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::cpu_model;
+///
+/// if cpu_model().is_some_and(|model| model.is_generic()) {
+///     println!("warning: generic QEMU CPU model, use -cpu host or a named model");
+/// }
+/// ```
+pub fn cpu_model() -> Option<CpuModel> {
+    cpu_model_with(&CpuIdReaderNative)
+}
+
+/// Like [`cpu_model`] but reads CPUID through the given [`CpuIdReader`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{cpu_model_with, CpuModel};
+///
+/// // QEMU with -cpu qemu64
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0xd, 0, 0, 0),
+///         0x1 => (0x0006_0fb1, 0, 1 << 31, 0),
+///         0x8000_0000 => (0x8000_0004, 0, 0, 0),
+///         // "QEMU Virtual CPU version 2.5+"
+///         0x8000_0002 => (0x554d_4551, 0x7269_5620, 0x6c61_7574, 0x5550_4320),
+///         0x8000_0003 => (0x7265_7620, 0x6e6f_6973, 0x352e_3220, 0x0000_002b),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let model = cpu_model_with(&reader).unwrap();
+/// assert_eq!(model, CpuModel::Qemu64);
+/// assert!(model.is_generic());
+/// ```
+pub fn cpu_model_with<R: CpuIdReader>(reader: &R) -> Option<CpuModel> {
+    let id = CpuId::with_cpuid_reader(reader.clone());
+    let feature_info = id.get_feature_info();
+    let hypervisor_flag = feature_info
+        .as_ref()
+        .is_some_and(|info| info.has_hypervisor());
+    let brand_string = id.get_processor_brand_string();
+    let brand_string = brand_string.as_ref().map(|brand| brand.as_str());
+    if !hypervisor_flag && !brand_string.is_some_and(|brand| brand.contains("QEMU")) {
+        return None;
+    }
+    let fms = feature_info.map(|info| (info.family_id(), info.model_id(), info.stepping_id()));
+    let model = CpuModel::classify(brand_string, fms);
+    debug!(
+        "CPU model: {:?} (brand string {:?}, FMS {:?})",
+        model, brand_string, fms
+    );
+    Some(model)
+}
@@ -0,0 +1,414 @@
+//! The CPUID-based checks that are the foundation of the detection. They run before
+//! any user-provided [`crate::Probe`]. On architectures other than x86/x86_64, CPUID
+//! is not available and all functions of this module report so.
+//!
+//! On x86/x86_64, each check has a `*_with` variant that reads CPUID through a
+//! user-provided [`CpuIdReader`], for example to replay captured leaves.
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::hyperv::HYPERV_INTERFACE_SIGNATURE;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::hypervisor::{
+    SIGNATURE_HYPERV, SIGNATURE_KVM, SIGNATURE_NONE, SIGNATURE_TCG, SIGNATURE_XEN,
+};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::report::str_from_padded;
+use crate::report::HYPERVISOR_SIGNATURE_LEN;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::{
+    fixed_str::FixedStr, Accelerator, Check, CheckOutcome, InterfaceVersion, QemuCertainty,
+};
+use crate::{DetectionReport, VmmKind};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use raw_cpuid::{CpuId, CpuIdReader, CpuIdReaderNative, Hypervisor};
+
+/// The hypervisor info leaf with the vendor signature of the hypervisor.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) const HYPERVISOR_INFO_LEAF: u32 = 0x4000_0000;
+
+/// Leaf with the signature of KVM or Xen, if they also emulate the Hyper-V interface in
+/// the leaves starting at [`HYPERVISOR_INFO_LEAF`].
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) const HYPERV_EMULATOR_INFO_LEAF: u32 = 0x4000_0100;
+
+/// Selects which of the optional CPUID-based checks run. The hypervisor flag check
+/// always runs, as all other checks depend on it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CpuidChecks {
+    pub(crate) hypervisor_id: bool,
+    pub(crate) brand_string: bool,
+}
+
+impl CpuidChecks {
+    pub(crate) const ALL: Self = Self {
+        hypervisor_id: true,
+        brand_string: true,
+    };
+}
+
+/// Runs the selected CPUID-based checks and records their outcome and the resulting
+/// certainty in the report. Returns `false`, if CPUID is not available.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn run_checks(report: &mut DetectionReport, checks: CpuidChecks) -> bool {
+    run_checks_with(CpuIdReaderNative, report, checks)
+}
+
+/// Like [`run_checks`] but reads CPUID through the given reader.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn run_checks_with<R: CpuIdReader>(
+    reader: R,
+    report: &mut DetectionReport,
+    checks: CpuidChecks,
+) -> bool {
+    let id = CpuId::with_cpuid_reader(reader.clone());
+    report.certainty = QemuCertainty::DefinitelyNot;
+
+    // ########## CHECK 1 ##########
+    // The `x86` library first checks if the Hypervisor flag is present in the `cpuid` features.
+    // If yes, it reads the Hypervisor info leaf from `cpuid`.
+    // Also see https://lwn.net/Articles/301888/)
+    let hypervisor_flag = id
+        .get_feature_info()
+        .map(|info| info.has_hypervisor())
+        .unwrap_or(false);
+    let hypervisor_info = id.get_hypervisor_info();
+    if hypervisor_info.is_none() {
+        let outcome = if hypervisor_flag {
+            CheckOutcome::Unavailable
+        } else {
+            CheckOutcome::Failed
+        };
+        report.record(Check::HypervisorFlag, outcome);
+        run_checks_without_hypervisor_info(&reader, report, checks);
+        return true;
+    }
+    let hypervisor_info = hypervisor_info.unwrap();
+    report.record(Check::HypervisorFlag, CheckOutcome::Passed);
+    report.hypervisor_id = Some(read_signature(&reader, HYPERVISOR_INFO_LEAF));
+    report.hypervisor_max_leaf = Some(reader.cpuid1(HYPERVISOR_INFO_LEAF).eax);
+    report.interface_version = interface_version_with(&reader, HYPERVISOR_INFO_LEAF);
+    report.vmm = vmm_with(&reader);
+    report.accelerator = accelerator_of_qemu_with(&reader, report.vmm);
+
+    // if this returns false, because the hypervisor ID can be "KVM",
+    // we still could be executed by QEMU -> further checks needed
+    if checks.hypervisor_id {
+        if matches!(hypervisor_info.identify(), Hypervisor::QEMU) {
+            debug!("Runs very likely in QEMU. QEMU is the direct hypervisor (no KVM etc.).");
+            report.record(Check::HypervisorId, CheckOutcome::Passed);
+            report.certainty = QemuCertainty::VeryLikely;
+            return true;
+        }
+        report.record(Check::HypervisorId, CheckOutcome::Failed);
+
+        if let Some(vmm) = report.vmm.filter(VmmKind::rules_out_qemu) {
+            debug!("Definitely not QEMU. Hypervisor is {:?}.", vmm);
+            report.record(Check::VmmSignature, CheckOutcome::Failed);
+            return true;
+        }
+        report.record(Check::VmmSignature, CheckOutcome::Passed);
+    }
+
+    if !checks.brand_string {
+        debug!("Maybe QEMU. Hypervisor is present, brand string check is disabled.");
+        report.certainty = QemuCertainty::Maybe;
+        return true;
+    }
+
+    // ########## CHECK 2 ##########
+    // now check the extended CPU brand string (which is specific for QEMU)
+    let brand_string = id.get_processor_brand_string();
+    if brand_string.is_none() {
+        debug!(
+            "Maybe QEMU. CPU brand string not available, can't verify if code runs inside QEMU."
+        );
+        report.record(Check::BrandString, CheckOutcome::Unavailable);
+        report.certainty = QemuCertainty::Maybe;
+        return true;
+    }
+    let brand_string = brand_string.unwrap();
+    let brand_string = brand_string.as_str();
+    report.brand_string = Some(FixedStr::new(brand_string));
+
+    let cpu_brand_string_contains_qemu = brand_string.contains("QEMU");
+    if cpu_brand_string_contains_qemu {
+        // "QEMU Virtual CPU version 2.5+"
+        debug!(
+            "Runs very likely in QEMU with {} as accelerator.",
+            report.hypervisor_id().unwrap_or("")
+        );
+        report.record(Check::BrandString, CheckOutcome::Passed);
+        report.certainty = QemuCertainty::VeryLikely;
+    } else {
+        debug!(
+            "Maybe QEMU. Hypervisor is {} but CPU brand string is not the one from QEMU.",
+            report.hypervisor_id().unwrap_or("")
+        );
+        report.record(Check::BrandString, CheckOutcome::Failed);
+        report.certainty = QemuCertainty::Maybe;
+    }
+    true
+}
+
+/// Looks for QEMU although no hypervisor info leaf is announced, e.g. with
+/// `-cpu host,-hypervisor` or TCG and `-cpu qemu64,-hypervisor`. QEMU still answers leaf
+/// `0x4000_0000` with the signature of KVM or TCG (unless `kvm=off`), and its CPU models
+/// still report `QEMU Virtual CPU` as brand string. On real hardware, leaf `0x4000_0000`
+/// returns zeros or the values of the highest basic leaf, which never match a known
+/// signature.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn run_checks_without_hypervisor_info<R: CpuIdReader>(
+    reader: &R,
+    report: &mut DetectionReport,
+    checks: CpuidChecks,
+) {
+    let mut brand_string_mentions_qemu = false;
+    if checks.brand_string {
+        let brand_string = CpuId::with_cpuid_reader(reader.clone()).get_processor_brand_string();
+        if let Some(brand_string) = brand_string {
+            let brand_string = brand_string.as_str();
+            report.brand_string = Some(FixedStr::new(brand_string));
+            brand_string_mentions_qemu = brand_string.contains("QEMU");
+            let outcome = if brand_string_mentions_qemu {
+                CheckOutcome::Passed
+            } else {
+                CheckOutcome::Failed
+            };
+            report.record(Check::BrandString, outcome);
+        }
+    }
+
+    let signature = read_signature(reader, HYPERVISOR_INFO_LEAF);
+    let vmm = VmmKind::identify(&signature, brand_string_mentions_qemu);
+    if checks.hypervisor_id && &signature != SIGNATURE_NONE && !matches!(vmm, VmmKind::Unknown(_)) {
+        debug!("Hypervisor flag is hidden, but {:?} announces itself.", vmm);
+        report.hypervisor_id = Some(signature);
+        report.hypervisor_max_leaf = Some(reader.cpuid1(HYPERVISOR_INFO_LEAF).eax);
+        report.interface_version = interface_version_with(reader, HYPERVISOR_INFO_LEAF);
+        report.vmm = Some(vmm);
+        report.accelerator = accelerator_of_qemu_with(reader, report.vmm);
+        if &signature == SIGNATURE_TCG {
+            debug!("Runs very likely in QEMU. TCG signature without hypervisor info leaf.");
+            report.record(Check::HypervisorId, CheckOutcome::Passed);
+            report.certainty = QemuCertainty::VeryLikely;
+            return;
+        }
+        report.record(Check::HypervisorId, CheckOutcome::Failed);
+        if vmm.rules_out_qemu() {
+            debug!("Definitely not QEMU. Hypervisor is {:?}.", vmm);
+            report.record(Check::VmmSignature, CheckOutcome::Failed);
+            return;
+        }
+        report.record(Check::VmmSignature, CheckOutcome::Passed);
+        report.certainty = QemuCertainty::Maybe;
+    }
+
+    if brand_string_mentions_qemu {
+        debug!("Runs very likely in QEMU. QEMU brand string without hypervisor flag.");
+        report.certainty = QemuCertainty::VeryLikely;
+    } else if report.certainty == QemuCertainty::DefinitelyNot {
+        debug!("Definitely not QEMU. Hypervisor flag is not set, no hypervisor info available.");
+    }
+}
+
+/// Fallback for architectures without CPUID.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub(crate) fn run_checks(_report: &mut DetectionReport, _checks: CpuidChecks) -> bool {
+    debug!("CPUID checks skipped. CPUID is not available on this architecture.");
+    false
+}
+
+/// Returns the raw 12 byte vendor signature from the hypervisor info leaf `0x4000_0000`,
+/// if a hypervisor is present.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn hypervisor_signature() -> Option<[u8; HYPERVISOR_SIGNATURE_LEN]> {
+    hypervisor_signature_with(&CpuIdReaderNative)
+}
+
+/// Like [`hypervisor_signature`] but reads CPUID through the given reader.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn hypervisor_signature_with<R: CpuIdReader>(
+    reader: &R,
+) -> Option<[u8; HYPERVISOR_SIGNATURE_LEN]> {
+    CpuId::with_cpuid_reader(reader.clone()).get_hypervisor_info()?;
+    Some(read_signature(reader, HYPERVISOR_INFO_LEAF))
+}
+
+/// Fallback for architectures without CPUID.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub(crate) fn hypervisor_signature() -> Option<[u8; HYPERVISOR_SIGNATURE_LEN]> {
+    None
+}
+
+/// Identifies the VMM, if a hypervisor is present. If the hypervisor signature is the
+/// one from Hyper-V, but KVM or Xen announces itself in leaf `0x4000_0100`, the VMM is
+/// KVM with Hyper-V enlightenments (e.g. QEMU with `-cpu ...,hv-relaxed`) or Xen with
+/// Viridian enlightenments, not Hyper-V.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn vmm() -> Option<VmmKind> {
+    vmm_with(&CpuIdReaderNative)
+}
+
+/// Like [`vmm`] but reads CPUID through the given reader.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn vmm_with<R: CpuIdReader>(reader: &R) -> Option<VmmKind> {
+    let mut signature = hypervisor_signature_with(reader)?;
+    if &signature == SIGNATURE_HYPERV {
+        let emulator = read_signature(reader, HYPERV_EMULATOR_INFO_LEAF);
+        if &emulator == SIGNATURE_KVM || &emulator == SIGNATURE_XEN {
+            debug!(
+                "Hyper-V interface is emulated by {}.",
+                str_from_padded(&emulator)
+            );
+            signature = emulator;
+        }
+    }
+    let vmm = VmmKind::identify(&signature, brand_string_mentions_qemu_with(reader));
+    debug!("Identified VMM: {:?}", vmm);
+    Some(vmm)
+}
+
+/// Returns the accelerator of [`crate::accelerator_with`], if `vmm` is QEMU, so that the
+/// accelerator of the report always agrees with its VMM.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn accelerator_of_qemu_with<R: CpuIdReader>(
+    reader: &R,
+    vmm: Option<VmmKind>,
+) -> Option<Accelerator> {
+    crate::accelerator_with(reader).filter(|_| vmm == Some(VmmKind::Qemu))
+}
+
+/// Fallback for architectures without CPUID.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub(crate) fn vmm() -> Option<VmmKind> {
+    None
+}
+
+/// Returns if KVM announces itself in leaf `0x4000_0100`, which it does if it emulates
+/// the Hyper-V interface in the leaves starting at `0x4000_0000`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn kvm_emulates_hyperv_with<R: CpuIdReader>(reader: &R) -> bool {
+    &read_signature(reader, HYPERV_EMULATOR_INFO_LEAF) == SIGNATURE_KVM
+}
+
+/// Returns if the Hypervisor-flag is set in CPUID leaf `0x1`. This is the primitive
+/// signal behind [`crate::Check::HypervisorFlag`]. It is set by virtually all hypervisors,
+/// but some of them can be configured to hide it. Always `false` on architectures
+/// without CPUID.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn hypervisor_flag_set() -> bool {
+    CpuId::new()
+        .get_feature_info()
+        .map(|info| info.has_hypervisor())
+        .unwrap_or(false)
+}
+
+/// Fallback for architectures without CPUID.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub fn hypervisor_flag_set() -> bool {
+    false
+}
+
+/// Returns if the hypervisor ID in CPUID leaf `0x4000_0000` is the one from QEMU
+/// (`TCGTCGTCGTCG`), i.e. QEMU runs without an accelerator. This is the primitive
+/// signal behind [`crate::Check::HypervisorId`]. Always `false` on architectures
+/// without CPUID.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn hypervisor_is_qemu() -> bool {
+    CpuId::new()
+        .get_hypervisor_info()
+        .map(|hypervisor_info| matches!(hypervisor_info.identify(), Hypervisor::QEMU))
+        .unwrap_or(false)
+}
+
+/// Fallback for architectures without CPUID.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub fn hypervisor_is_qemu() -> bool {
+    false
+}
+
+/// Returns if the processor brand string is available and contains `QEMU`, as in
+/// `QEMU Virtual CPU version 2.5+`. This is the primitive signal behind
+/// [`crate::Check::BrandString`]. Always `false` on architectures without CPUID.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{brand_string_mentions_qemu, hypervisor_flag_set, hypervisor_is_qemu};
+///
+/// // custom policy: only trust QEMU-specific signals
+/// let qemu = hypervisor_flag_set() && (hypervisor_is_qemu() || brand_string_mentions_qemu());
+/// println!("QEMU: {}", qemu);
+/// ```
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn brand_string_mentions_qemu() -> bool {
+    brand_string_mentions_qemu_with(&CpuIdReaderNative)
+}
+
+/// Like [`brand_string_mentions_qemu`] but reads CPUID through the given reader.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn brand_string_mentions_qemu_with<R: CpuIdReader>(reader: &R) -> bool {
+    CpuId::with_cpuid_reader(reader.clone())
+        .get_processor_brand_string()
+        .map(|brand_string| brand_string.as_str().contains("QEMU"))
+        .unwrap_or(false)
+}
+
+/// Fallback for architectures without CPUID.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub fn brand_string_mentions_qemu() -> bool {
+    false
+}
+
+/// Reads the raw 12 byte vendor signature from `ebx`, `ecx`, and `edx` of a hypervisor
+/// info leaf such as `0x4000_0000`. Only meaningful, if the leaf is available.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn read_signature<R: CpuIdReader>(
+    reader: &R,
+    leaf: u32,
+) -> [u8; HYPERVISOR_SIGNATURE_LEN] {
+    let res = reader.cpuid1(leaf);
+    let mut signature = [0; HYPERVISOR_SIGNATURE_LEN];
+    signature[0..4].copy_from_slice(&res.ebx.to_le_bytes());
+    signature[4..8].copy_from_slice(&res.ecx.to_le_bytes());
+    signature[8..12].copy_from_slice(&res.edx.to_le_bytes());
+    signature
+}
+
+/// Reads the version information of the hypervisor interface at `base_leaf`, such as
+/// `0x4000_0000`. Returns `None`, if the interface is neither KVM, Xen, nor Hyper-V, or
+/// if KVM reports no leaf after the base leaf. The Hyper-V interface is recognized by
+/// `Hv#1` in leaf base + 1, independent of the (configurable) vendor signature.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn interface_version_with<R: CpuIdReader>(
+    reader: &R,
+    base_leaf: u32,
+) -> Option<InterfaceVersion> {
+    let signature = read_signature(reader, base_leaf);
+    let max_leaf = match reader.cpuid1(base_leaf).eax {
+        // old KVM versions report 0 as highest leaf, which means base + 1
+        0 if &signature == SIGNATURE_KVM => base_leaf + 1,
+        max_leaf => max_leaf,
+    };
+    let leaf1 = reader.cpuid1(base_leaf + 1);
+    if &signature == SIGNATURE_KVM && max_leaf > base_leaf {
+        Some(InterfaceVersion::Kvm {
+            features: leaf1.eax,
+            hints: leaf1.edx,
+        })
+    } else if &signature == SIGNATURE_XEN {
+        Some(InterfaceVersion::Xen {
+            major: (leaf1.eax >> 16) as u16,
+            minor: leaf1.eax as u16,
+        })
+    } else if max_leaf >= base_leaf + 2 && leaf1.eax == HYPERV_INTERFACE_SIGNATURE {
+        let version = reader.cpuid1(base_leaf + 2);
+        Some(InterfaceVersion::HyperV {
+            major: (version.ebx >> 16) as u16,
+            minor: version.ebx as u16,
+            build: version.eax,
+        })
+    } else {
+        None
+    }
+}
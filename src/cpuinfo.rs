@@ -0,0 +1,70 @@
+//! Module for [`CpuinfoProbe`], which evaluates the views of the Linux kernel on the
+//! CPU and the hypervisor instead of executing CPUID. Needs the `std` feature. Some
+//! container runtimes and debuggers intercept CPUID, but not the kernel, which read
+//! CPUID during boot.
+
+use crate::{Probe, ProbeResult};
+use std::fs;
+
+/// CPU information of the kernel.
+const PROC_CPUINFO: &str = "/proc/cpuinfo";
+/// Type of the hypervisor, e.g. `xen`. Only some hypervisors provide it.
+const SYS_HYPERVISOR_TYPE: &str = "/sys/hypervisor/type";
+
+/// Probe that reads `/proc/cpuinfo` and `/sys/hypervisor/type`.
+///
+/// Returns [`ProbeResult::Qemu`], if the model name mentions QEMU, as in
+/// `QEMU Virtual CPU version 2.5+`, and [`ProbeResult::Hint`], if the kernel reports the
+/// `hypervisor` CPU flag or a hypervisor type. Otherwise, or if the files aren't
+/// readable, returns [`ProbeResult::Inconclusive`], as the hypervisor flag might be
+/// hidden.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{CpuinfoProbe, Detector};
+///
+/// // don't execute CPUID, it might be intercepted
+/// let probe = CpuinfoProbe;
+/// let report = Detector::builder().cpuid(false).probes(&[&probe]).build().detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CpuinfoProbe;
+
+impl Probe for CpuinfoProbe {
+    fn name(&self) -> &'static str {
+        "cpuinfo"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let cpuinfo = fs::read_to_string(PROC_CPUINFO).unwrap_or_default();
+        let value = |key: &str| {
+            cpuinfo
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.trim() == key)
+                .map(|(_, value)| value.trim())
+        };
+        if let Some(model_name) = value("model name").filter(|name| name.contains("QEMU")) {
+            debug!("CPU model name '{}' is from QEMU.", model_name);
+            return ProbeResult::Qemu;
+        }
+        let hypervisor_flag = value("flags")
+            .map(|flags| flags.split_whitespace().any(|flag| flag == "hypervisor"))
+            .unwrap_or(false);
+        let hypervisor_type = fs::read_to_string(SYS_HYPERVISOR_TYPE).unwrap_or_default();
+        if hypervisor_flag {
+            debug!("Kernel reports the hypervisor flag.");
+            ProbeResult::Hint
+        } else if !hypervisor_type.trim().is_empty() {
+            debug!(
+                "Kernel reports hypervisor type '{}'.",
+                hypervisor_type.trim()
+            );
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+}
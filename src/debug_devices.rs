@@ -0,0 +1,109 @@
+//! Module for [`debug_devices`], which tells which of QEMU's debug devices are present,
+//! so that the guest knows if writing to them is meaningful: the debugcon (`-debugcon`
+//! or `-device isa-debugcon`), `isa-debug-exit`, pvpanic, and the serial port.
+
+use crate::{fw_cfg, io, machine_type, pci, MachineType};
+
+/// I/O port of the debugcon, e.g. `-debugcon stdio`.
+pub(crate) const DEBUGCON_PORT: u16 = 0xe9;
+/// Value that the debugcon returns on reads (`readback` property).
+pub(crate) const DEBUGCON_READBACK: u8 = 0xe9;
+/// fw_cfg file with the I/O port of the ISA pvpanic device.
+pub(crate) const PVPANIC_FW_CFG_FILE: &str = "etc/pvpanic-port";
+/// PCI vendor ID and device ID of the PCI pvpanic device.
+pub(crate) const PVPANIC_PCI: (u16, u16) = (0x1b36, 0x0011);
+/// PCI vendor ID and device IDs of `pci-serial`, `pci-serial-2x`, and `pci-serial-4x`.
+const SERIAL_PCI: [(u16, u16); 3] = [(0x1b36, 0x0002), (0x1b36, 0x0003), (0x1b36, 0x0004)];
+/// Scratch register of the 16550 UART of COM1 at `0x3f8`.
+const COM1_SCRATCH_PORT: u16 = 0x3f8 + 7;
+
+/// The debug devices of QEMU that are present. Result of [`debug_devices`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct DebugDevices {
+    /// The debugcon on I/O port `0xe9`, detected by its read-back value `0xe9`. A
+    /// debugcon on another port, e.g. `-device isa-debugcon,iobase=0x402`, isn't found.
+    pub debugcon: bool,
+    /// Whether the machine can have `isa-debug-exit`, i.e. it is a `pc`, `q35`, or
+    /// `microvm` machine (see [`MachineType`]). The device is write-only and can't be
+    /// detected, so this only tells that writing its port is harmless.
+    pub isa_debug_exit: bool,
+    /// The ISA pvpanic device (fw_cfg file `etc/pvpanic-port`) or the PCI pvpanic device
+    /// (`1b36:0011`). See [`crate::Pvpanic`].
+    pub pvpanic: bool,
+    /// A 16550 UART on COM1 (`0x3f8`), detected via its scratch register, or one of
+    /// QEMU's PCI serial devices (`1b36:0002` to `1b36:0004`).
+    pub serial: bool,
+}
+
+/// Determines which of QEMU's debug devices are present. All of them are QEMU-specific
+/// except the serial port, so check [`crate::runs_inside_qemu`] first: on other machines,
+/// the ports might belong to other devices.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use core::fmt::Write;
+/// use runs_inside_qemu::{debug_devices, runs_inside_qemu, DebugconWriter};
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// if runs_inside_qemu().is_very_likely() && unsafe { debug_devices() }.debugcon {
+///     // SAFETY: the debugcon is present on its default port
+///     let mut debugcon = unsafe { DebugconWriter::new_unchecked(DebugconWriter::DEFAULT_PORT) };
+///     writeln!(debugcon, "Hello").unwrap();
+/// }
+/// ```
+///
+/// # Safety
+/// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+/// sufficient I/O privilege level. Nobody else may access fw_cfg, the PCI configuration
+/// space, or the scratch register of COM1 meanwhile. The scratch register is written.
+pub unsafe fn debug_devices() -> DebugDevices {
+    let debugcon = io::inb(DEBUGCON_PORT) == DEBUGCON_READBACK;
+    let isa_debug_exit = isa_debug_exit_possible();
+    let pvpanic_isa = fw_cfg::read_file(PVPANIC_FW_CFG_FILE, &mut [0; 2]).is_some();
+    let (mut pvpanic_pci, mut serial_pci) = (false, false);
+    if pci::mechanism_present() {
+        for device in pci::devices() {
+            let id = (device.vendor_id, device.device_id);
+            pvpanic_pci |= id == PVPANIC_PCI;
+            serial_pci |= SERIAL_PCI.contains(&id);
+        }
+    }
+    let devices = DebugDevices {
+        debugcon,
+        isa_debug_exit,
+        pvpanic: pvpanic_isa || pvpanic_pci,
+        serial: serial_pci || com1_present(),
+    };
+    debug!("Debug devices: {:?}", devices);
+    devices
+}
+
+/// Returns if the machine type can have `isa-debug-exit`, see
+/// [`DebugDevices::isa_debug_exit`].
+///
+/// # Safety
+/// See [`machine_type`].
+pub(crate) unsafe fn isa_debug_exit_possible() -> bool {
+    matches!(
+        machine_type(),
+        Some(MachineType::Pc | MachineType::Q35 | MachineType::Microvm)
+    )
+}
+
+/// Returns if the scratch register of COM1 keeps the written values.
+///
+/// # Safety
+/// See [`debug_devices`].
+unsafe fn com1_present() -> bool {
+    let previous = io::inb(COM1_SCRATCH_PORT);
+    let present = [0x5a, 0xa5].into_iter().all(|value| {
+        io::outb(COM1_SCRATCH_PORT, value);
+        io::inb(COM1_SCRATCH_PORT) == value
+    });
+    io::outb(COM1_SCRATCH_PORT, previous);
+    present
+}
@@ -0,0 +1,119 @@
+//! Module for [`exit_qemu`], which terminates QEMU via the `isa-debug-exit` device
+//! (`-device isa-debug-exit,iobase=0xf4,iosize=0x04`). Test harnesses of kernels use it
+//! to report their result as exit status of QEMU.
+
+use crate::debug_devices::isa_debug_exit_possible;
+use crate::{io, runs_inside_qemu};
+
+/// Default I/O port of `isa-debug-exit`, as in `-device isa-debug-exit,iobase=0xf4`.
+pub const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+/// Value written to `isa-debug-exit`. QEMU exits with the status `(value << 1) | 1`, so
+/// it can't exit with status `0` and the status of a successful run must be chosen such
+/// that it differs from the one of QEMU itself, e.g. when it fails to start.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::QemuExitCode;
+///
+/// assert_eq!(QemuExitCode::SUCCESS.exit_status(), 33);
+/// assert_eq!(QemuExitCode::FAILURE.exit_status(), 35);
+/// assert_eq!(QemuExitCode::new(0x7f).exit_status(), 255);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QemuExitCode(u32);
+
+impl QemuExitCode {
+    /// The conventional value for a successful run, `0x10`. QEMU exits with status `33`.
+    pub const SUCCESS: Self = Self(0x10);
+    /// The conventional value for a failed run, `0x11`. QEMU exits with status `35`.
+    pub const FAILURE: Self = Self(0x11);
+
+    /// Creates an exit code from the raw value that is written to the device.
+    pub const fn new(value: u32) -> Self {
+        Self(value)
+    }
+
+    /// Returns the raw value that is written to the device.
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the exit status of QEMU, i.e. `(value << 1) | 1` truncated to 8 bits, as
+    /// seen by the shell on Unix hosts.
+    pub const fn exit_status(self) -> u8 {
+        ((self.0 << 1) | 1) as u8
+    }
+}
+
+/// Terminates QEMU with `code` via `isa-debug-exit` on its default port
+/// [`ISA_DEBUG_EXIT_PORT`]. See [`exit_qemu_with_port`].
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{exit_qemu, QemuExitCode};
+///
+/// // SAFETY: runs in ring 0 inside QEMU with `-device isa-debug-exit,iobase=0xf4`
+/// unsafe { exit_qemu(QemuExitCode::SUCCESS) };
+/// ```
+///
+/// # Safety
+/// See [`exit_qemu_with_port`].
+pub unsafe fn exit_qemu(code: QemuExitCode) -> ! {
+    exit_qemu_with_port(ISA_DEBUG_EXIT_PORT, code)
+}
+
+/// Terminates QEMU with `code` via `isa-debug-exit` on `port`. The value is written as
+/// double word, which works with every `iosize` of the device. If the device isn't
+/// present, the CPU spins forever.
+///
+/// # Safety
+/// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+/// sufficient I/O privilege level. Otherwise, the port access raises a general protection
+/// fault. The port must belong to `isa-debug-exit` or a device that tolerates arbitrary
+/// writes. See [`try_exit_qemu`], which checks this.
+pub unsafe fn exit_qemu_with_port(port: u16, code: QemuExitCode) -> ! {
+    debug!("Exiting QEMU with status {}.", code.exit_status());
+    io::outl(port, code.value());
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Like [`exit_qemu`], but only writes the port if [`crate::runs_inside_qemu`] is at least
+/// [`crate::QemuCertainty::Maybe`] and the machine can have `isa-debug-exit` (see
+/// [`crate::DebugDevices::isa_debug_exit`]). Returns, if this isn't the case, so that
+/// the caller can fall back to e.g. ACPI shutdown or halting.
+///
+/// The device is write-only and can't be detected. If the machine can have it but QEMU
+/// was started without it, the CPU spins forever.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{try_exit_qemu, QemuExitCode};
+///
+/// fn test_runner(tests: &[fn()]) {
+///     for test in tests {
+///         test();
+///     }
+///     // SAFETY: runs in ring 0 before other CPUs are started
+///     unsafe { try_exit_qemu(QemuExitCode::SUCCESS) };
+///     loop {}
+/// }
+/// ```
+///
+/// # Safety
+/// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+/// sufficient I/O privilege level, and nobody else may access the PCI configuration
+/// space or fw_cfg while this runs.
+pub unsafe fn try_exit_qemu(code: QemuExitCode) {
+    if runs_inside_qemu().is_maybe_or_very_likely() && isa_debug_exit_possible() {
+        exit_qemu(code);
+    }
+    debug!("Not exiting QEMU. isa-debug-exit is not available.");
+}
@@ -0,0 +1,198 @@
+//! Module for [`DebugconWriter`], which writes text to QEMU's debugcon (`-debugcon` or
+//! `-device isa-debugcon`) via [`core::fmt::Write`]. QEMU forwards everything written to
+//! the I/O port of the debugcon to stdout or a file, which makes it the simplest way to
+//! get output from a kernel or bootloader. With the `log` feature, `DebugconLogger`
+//! forwards the `log` facade to it.
+
+use crate::debug_devices::{DEBUGCON_PORT, DEBUGCON_READBACK};
+use crate::{io, runs_inside_qemu};
+use core::fmt;
+#[cfg(feature = "log")]
+use core::fmt::Write;
+#[cfg(feature = "log")]
+use core::sync::atomic::{AtomicU16, Ordering};
+
+/// The logger that [`DebugconLogger::init`] installs.
+#[cfg(feature = "log")]
+static LOGGER: DebugconLogger = DebugconLogger {
+    port: AtomicU16::new(DEBUGCON_PORT),
+};
+
+/// Writer for QEMU's debugcon that implements [`core::fmt::Write`].
+///
+/// The writer only writes to its I/O port, if the code runs inside QEMU and the
+/// debugcon answers on the port. Otherwise, writes are silently dropped, so the writer
+/// can be used unconditionally and doesn't disturb whatever device owns the port on
+/// other machines.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use core::fmt::Write;
+/// use runs_inside_qemu::DebugconWriter;
+///
+/// // SAFETY: runs in ring 0
+/// let mut debugcon = unsafe { DebugconWriter::new() };
+/// writeln!(debugcon, "Hello from {}", "QEMU").unwrap();
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DebugconWriter {
+    /// The I/O port, or `None` if writes are dropped.
+    port: Option<u16>,
+}
+
+impl DebugconWriter {
+    /// The default I/O port of the debugcon, `0xe9`.
+    pub const DEFAULT_PORT: u16 = DEBUGCON_PORT;
+
+    /// Creates a writer for the debugcon on its default port [`Self::DEFAULT_PORT`].
+    /// See [`Self::with_port`].
+    ///
+    /// # Safety
+    /// See [`Self::with_port`].
+    pub unsafe fn new() -> Self {
+        Self::with_port(Self::DEFAULT_PORT)
+    }
+
+    /// Creates a writer for the debugcon on `port`, e.g. `0x402` for
+    /// `-device isa-debugcon,iobase=0x402`. The writer is only enabled, if
+    /// [`crate::runs_inside_qemu`] is at least [`crate::QemuCertainty::Maybe`] and the
+    /// port returns the default read-back value `0xe9` of the debugcon. The port is only
+    /// read inside QEMU.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level. Otherwise, the port access raises a general
+    /// protection fault.
+    pub unsafe fn with_port(port: u16) -> Self {
+        let present =
+            runs_inside_qemu().is_maybe_or_very_likely() && io::inb(port) == DEBUGCON_READBACK;
+        debug!("Debugcon on port {} present: {}", port, present);
+        Self {
+            port: present.then_some(port),
+        }
+    }
+
+    /// Creates a writer for the debugcon on `port` without any detection, e.g. for a
+    /// debugcon with a custom `readback` property or if detection ran before.
+    ///
+    /// # Safety
+    /// See [`Self::with_port`]. Additionally, the port must belong to a debugcon or a
+    /// device that tolerates arbitrary writes.
+    pub const unsafe fn new_unchecked(port: u16) -> Self {
+        Self { port: Some(port) }
+    }
+
+    /// Creates a writer that drops all writes.
+    pub const fn disabled() -> Self {
+        Self { port: None }
+    }
+
+    /// Returns the I/O port, if the writer is enabled.
+    pub const fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Returns if writes reach the debugcon.
+    pub const fn is_enabled(&self) -> bool {
+        self.port.is_some()
+    }
+
+    /// Writes the raw bytes to the debugcon. Does nothing, if the writer is disabled.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        if let Some(port) = self.port {
+            for &byte in bytes {
+                // SAFETY: guaranteed by the caller of the constructor
+                unsafe { io::outb(port, byte) };
+            }
+        }
+    }
+}
+
+impl fmt::Write for DebugconWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Implementation of [`log::Log`] that writes all records to QEMU's debugcon, one line
+/// per record in the format `[LEVEL target] message`. Install it with
+/// [`DebugconLogger::init`].
+///
+/// Records aren't serialized, so the lines of concurrent records on several CPUs may be
+/// interleaved.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::DebugconLogger;
+///
+/// // SAFETY: runs in ring 0
+/// if let Ok(true) = unsafe { DebugconLogger::init(log::LevelFilter::Debug) } {
+///     log::info!("Hello from QEMU");
+/// }
+/// ```
+#[cfg(feature = "log")]
+#[derive(Debug)]
+pub struct DebugconLogger {
+    port: AtomicU16,
+}
+
+#[cfg(feature = "log")]
+impl DebugconLogger {
+    /// Installs the logger as global logger of the `log` facade for the debugcon on its
+    /// default port [`DebugconWriter::DEFAULT_PORT`]. See [`Self::init_with_port`].
+    ///
+    /// # Safety
+    /// See [`DebugconWriter::with_port`].
+    pub unsafe fn init(level: log::LevelFilter) -> Result<bool, log::SetLoggerError> {
+        Self::init_with_port(DEBUGCON_PORT, level)
+    }
+
+    /// Installs the logger as global logger of the `log` facade for the debugcon on
+    /// `port` and sets the maximum log level to `level`. The logger is only installed,
+    /// if a [`DebugconWriter::with_port`] for the port is enabled, i.e. the code runs
+    /// inside QEMU and the debugcon is present. Returns if the logger was installed, or
+    /// an error, if another logger is already installed.
+    ///
+    /// # Safety
+    /// See [`DebugconWriter::with_port`]. The logger accesses the port on every record,
+    /// from any context that logs.
+    pub unsafe fn init_with_port(
+        port: u16,
+        level: log::LevelFilter,
+    ) -> Result<bool, log::SetLoggerError> {
+        if !DebugconWriter::with_port(port).is_enabled() {
+            return Ok(false);
+        }
+        // before registering, so that no record goes to the default port
+        LOGGER.port.store(port, Ordering::Relaxed);
+        log::set_logger(&LOGGER)?;
+        log::set_max_level(level);
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "log")]
+impl log::Log for DebugconLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        // filtered by the maximum log level
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        // SAFETY: the debugcon was detected in `DebugconLogger::init_with_port`
+        let mut writer =
+            unsafe { DebugconWriter::new_unchecked(self.port.load(Ordering::Relaxed)) };
+        let _ = writeln!(
+            writer,
+            "[{} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,265 @@
+//! Module for [`Detector`], which combines the built-in CPUID checks with
+//! user-provided [`Probe`]s, and its builder [`DetectorBuilder`].
+
+use crate::aggregation::Aggregation;
+use crate::cpuid::{self, CpuidChecks};
+use crate::hypervisor::SIGNATURE_KVM;
+use crate::management::{self, management_stack};
+use crate::{
+    Check, CheckOutcome, DetectError, DetectionPolicy, DetectionReport, ManagementStack, Probe,
+    ProbeResult, QemuCertainty, Weights,
+};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use raw_cpuid::CpuIdReader;
+
+/// Runs the built-in CPUID checks followed by a list of user-provided [`Probe`]s
+/// and aggregates all results into a single [`DetectionReport`].
+///
+/// The results are aggregated as follows:
+/// 1. If any source found evidence for QEMU, the verdict is [`QemuCertainty::VeryLikely`].
+/// 2. Otherwise, if any source rules out QEMU, the verdict is [`QemuCertainty::DefinitelyNot`].
+/// 3. Otherwise, if any source found a hint for a virtual machine, the verdict is
+///    [`QemuCertainty::Maybe`].
+/// 4. Otherwise, no source gathered a signal that points towards or against QEMU and the
+///    verdict is [`QemuCertainty::Unknown`]. This happens if CPUID is not available or
+///    disabled and all probes are [`ProbeResult::Inconclusive`] or [`ProbeResult::Unavailable`].
+///
+/// Additionally, each piece of evidence contributes a weight to the score (see
+/// [`DetectionReport::score`] and [`DetectionReport::confidence`]). The weights of the
+/// built-in checks are defined by [`Weights`], which can be overridden via
+/// [`DetectorBuilder::weights`], and those of the probes by [`Probe::weight`].
+///
+/// Finally, the [`DetectionPolicy`] is applied to the verdict.
+///
+/// [`crate::detect`] is equivalent to a detector without any probes. Use
+/// [`Detector::builder`] to select which checks run.
+#[derive(Copy, Clone)]
+pub struct Detector<'a> {
+    cpuid: Option<CpuidChecks>,
+    probes: &'a [&'a dyn Probe],
+    policy: DetectionPolicy,
+    weights: Weights,
+    /// DMI system vendor and product name.
+    dmi: Option<(&'a str, &'a str)>,
+}
+
+impl<'a> Detector<'a> {
+    /// Creates a new detector that runs the given probes after all built-in CPUID checks.
+    pub const fn new(probes: &'a [&'a dyn Probe]) -> Self {
+        Self {
+            cpuid: Some(CpuidChecks::ALL),
+            probes,
+            policy: DetectionPolicy::Balanced,
+            weights: Weights::DEFAULT,
+            dmi: None,
+        }
+    }
+
+    /// Returns a [`DetectorBuilder`] with all built-in checks enabled and no probes.
+    pub const fn builder() -> DetectorBuilder<'a> {
+        DetectorBuilder {
+            detector: Self::new(&[]),
+        }
+    }
+
+    /// Executes all checks and probes and returns the report.
+    pub fn detect(&self) -> DetectionReport {
+        self.run(cpuid::run_checks).0
+    }
+
+    /// Like [`Self::detect`] but returns a [`DetectError`] if a detection source failed,
+    /// instead of silently treating the failure like a negative result. This is the case
+    /// if the CPUID checks are enabled but CPUID is not available, if a required CPUID
+    /// leaf is missing, or if a probe returned [`ProbeResult::Faulted`]. The first error
+    /// is returned.
+    pub fn try_detect(&self) -> Result<DetectionReport, DetectError> {
+        match self.run(cpuid::run_checks) {
+            (report, None) => Ok(report),
+            (_, Some(error)) => Err(error),
+        }
+    }
+
+    /// Like [`Self::detect`] but reads CPUID through the given [`CpuIdReader`] instead of
+    /// executing the `cpuid` instruction. This way, canned or captured CPUID leaves can
+    /// be replayed. Probes still run natively.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub fn detect_with<R: CpuIdReader>(&self, reader: R) -> DetectionReport {
+        self.run(|report, checks| cpuid::run_checks_with(reader, report, checks))
+            .0
+    }
+
+    /// Executes the CPUID checks via `run_checks` followed by all probes and aggregates
+    /// the results into the report. Additionally returns the first error, if any.
+    fn run(
+        &self,
+        run_checks: impl FnOnce(&mut DetectionReport, CpuidChecks) -> bool,
+    ) -> (DetectionReport, Option<DetectError>) {
+        let mut report = DetectionReport::new(QemuCertainty::DefinitelyNot);
+        let mut error = None;
+        let mut aggregation = Aggregation::new(self.weights);
+
+        let cpuid_available = self
+            .cpuid
+            .map(|checks| run_checks(&mut report, checks))
+            .unwrap_or(false);
+        if cpuid_available {
+            aggregation.add_cpuid(report.certainty, report.checks());
+            error = report
+                .checks()
+                .find(|&(_, outcome)| outcome == CheckOutcome::Unavailable)
+                .map(|(check, _)| DetectError::LeafMissing {
+                    leaf: required_leaf(check),
+                });
+        } else if self.cpuid.is_some() {
+            error = Some(DetectError::CpuidUnavailable);
+        }
+
+        for probe in self.probes {
+            let result = probe.run();
+            debug!("Probe '{}' returned {:?}.", probe.name(), result);
+            report.record_probe(probe.name(), result);
+            if let Some(identity) = probe.identity() {
+                report.record_identity(identity);
+            }
+            aggregation.add_probe(
+                result,
+                probe.weight(result, aggregation.weights()),
+                probe.unambiguous(),
+            );
+            if result == ProbeResult::Faulted {
+                error = error.or(Some(DetectError::ProbeFaulted {
+                    probe: probe.name(),
+                }));
+            }
+        }
+
+        report.certainty = aggregation.certainty();
+        report.management_stack = self
+            .dmi
+            .and_then(|(sys_vendor, product_name)| management_stack(sys_vendor, product_name))
+            .filter(|&stack| {
+                stack != ManagementStack::Utm || management::utm_plausible(report.accelerator)
+            });
+
+        match self.policy {
+            DetectionPolicy::Strict
+                if report.certainty == QemuCertainty::VeryLikely
+                    && !aggregation.unambiguous_qemu() =>
+            {
+                debug!("Strict policy: no unambiguous QEMU signature, downgrading to Maybe.");
+                report.certainty = QemuCertainty::Maybe;
+            }
+            DetectionPolicy::Permissive
+                if report.certainty == QemuCertainty::Maybe
+                    && report.hypervisor_id.as_ref() == Some(SIGNATURE_KVM) =>
+            {
+                debug!("Permissive policy: KVM is present, upgrading to VeryLikely.");
+                report.certainty = QemuCertainty::VeryLikely;
+            }
+            _ => {}
+        }
+
+        report.score = aggregation.score();
+        report.confidence = aggregation.score().clamp(0, 100) as u8;
+        (report, error)
+    }
+}
+
+/// Returns the CPUID leaf whose absence makes a check [`CheckOutcome::Unavailable`].
+const fn required_leaf(check: Check) -> u32 {
+    match check {
+        Check::HypervisorFlag | Check::HypervisorId | Check::VmmSignature => 0x4000_0000,
+        Check::BrandString => 0x8000_0002,
+    }
+}
+
+/// Builder for a [`Detector`] that selects which checks run. Useful to disable checks
+/// that are expensive or unreliable in your environment.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Check, Detector};
+///
+/// // I always run QEMU with "-cpu host", so the brand string never mentions QEMU.
+/// let detector = Detector::builder().cpuid(true).brand_string(false).build();
+/// let report = detector.detect();
+/// assert_eq!(report.check(Check::BrandString), None);
+/// ```
+#[derive(Copy, Clone)]
+pub struct DetectorBuilder<'a> {
+    detector: Detector<'a>,
+}
+
+impl<'a> DetectorBuilder<'a> {
+    /// Enables or disables all CPUID-based checks. If disabled, only the probes
+    /// decide about the verdict. Default: enabled.
+    pub const fn cpuid(mut self, enabled: bool) -> Self {
+        self.detector.cpuid = if enabled {
+            Some(CpuidChecks::ALL)
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Enables or disables [`crate::Check::HypervisorId`] and [`crate::Check::VmmSignature`].
+    /// Has no effect if the CPUID checks are disabled. Default: enabled.
+    pub const fn hypervisor_id(mut self, enabled: bool) -> Self {
+        if let Some(checks) = &mut self.detector.cpuid {
+            checks.hypervisor_id = enabled;
+        }
+        self
+    }
+
+    /// Enables or disables [`crate::Check::BrandString`]. Has no effect if the CPUID
+    /// checks are disabled. Default: enabled.
+    pub const fn brand_string(mut self, enabled: bool) -> Self {
+        if let Some(checks) = &mut self.detector.cpuid {
+            checks.brand_string = enabled;
+        }
+        self
+    }
+
+    /// Sets the probes that run after the CPUID checks. Default: none.
+    pub const fn probes(mut self, probes: &'a [&'a dyn Probe]) -> Self {
+        self.detector.probes = probes;
+        self
+    }
+
+    /// Sets the [`DetectionPolicy`]. Default: [`DetectionPolicy::Balanced`].
+    pub const fn policy(mut self, policy: DetectionPolicy) -> Self {
+        self.detector.policy = policy;
+        self
+    }
+
+    /// Sets the [`Weights`] of the evidence. Default: [`Weights::DEFAULT`].
+    pub const fn weights(mut self, weights: Weights) -> Self {
+        self.detector.weights = weights;
+        self
+    }
+
+    /// Sets the DMI system vendor and product name, as found in
+    /// `/sys/class/dmi/id/sys_vendor` and `/sys/class/dmi/id/product_name` on Linux. They
+    /// are used to identify the software that manages the virtual machine, see
+    /// [`DetectionReport::management_stack`]. To use them as evidence, add a
+    /// [`crate::DmiProbe`]. Default: none.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::{Detector, ManagementStack};
+    ///
+    /// let report = Detector::builder().dmi("Proxmox", "pve").build().detect();
+    /// assert_eq!(report.management_stack(), Some(ManagementStack::Proxmox));
+    /// ```
+    pub const fn dmi(mut self, sys_vendor: &'a str, product_name: &'a str) -> Self {
+        self.detector.dmi = Some((sys_vendor, product_name));
+        self
+    }
+
+    /// Builds the [`Detector`].
+    pub const fn build(self) -> Detector<'a> {
+        self.detector
+    }
+}
@@ -0,0 +1,174 @@
+//! Module for [`DmiProbe`], which identifies the VMM by the DMI (SMBIOS) system vendor
+//! and product name. Hypervisors put their own name there, unless the user overrides it,
+//! e.g. QEMU via `-smbios type=1,...`.
+
+use crate::{Probe, ProbeResult, VmmKind};
+
+/// Prefix of the product names of QEMU's PC machines, e.g.
+/// `Standard PC (Q35 + ICH9, 2009)`.
+const QEMU_PRODUCT_PREFIX: &str = "Standard PC (";
+/// Chassis vendor that QEMU reports (SMBIOS type 3).
+const QEMU_CHASSIS_VENDOR: &str = "QEMU";
+/// BIOS vendor of SeaBIOS, the default firmware of QEMU's PC machines and of Bochs.
+const SEABIOS_VENDOR: &str = "SeaBIOS";
+/// BIOS and system vendor of the SMBIOS tables that SeaBIOS generates itself, e.g. for
+/// old QEMU versions or Bochs.
+const BOCHS_VENDOR: &str = "Bochs";
+
+/// Identifies the VMM by the DMI system vendor and product name (SMBIOS type 1), as
+/// found in `/sys/class/dmi/id/sys_vendor` and `/sys/class/dmi/id/product_name` on
+/// Linux. Returns `None`, if the strings don't belong to a known VMM. Leading and
+/// trailing whitespace is ignored.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{vmm_from_dmi, VmmKind};
+///
+/// assert_eq!(vmm_from_dmi("innotek GmbH", "VirtualBox"), Some(VmmKind::VirtualBox));
+/// assert_eq!(
+///     vmm_from_dmi("QEMU", "Standard PC (Q35 + ICH9, 2009)"),
+///     Some(VmmKind::Qemu)
+/// );
+/// assert_eq!(
+///     vmm_from_dmi("Parallels International GmbH.", "Parallels ARM Virtual Machine"),
+///     Some(VmmKind::Parallels)
+/// );
+/// assert_eq!(
+///     vmm_from_dmi("Cloud Hypervisor", "cloud-hypervisor"),
+///     Some(VmmKind::CloudHypervisor)
+/// );
+/// assert_eq!(vmm_from_dmi("LENOVO", "20XW0026GE"), None);
+/// ```
+pub fn vmm_from_dmi(sys_vendor: &str, product_name: &str) -> Option<VmmKind> {
+    match (sys_vendor.trim(), product_name.trim()) {
+        ("QEMU", _) => Some(VmmKind::Qemu),
+        // older versions use the vendor "innotek GmbH", newer "Oracle Corporation"
+        (_, "VirtualBox") => Some(VmmKind::VirtualBox),
+        ("VMware, Inc.", _) => Some(VmmKind::Vmware),
+        ("Microsoft Corporation", "Virtual Machine") => Some(VmmKind::HyperV),
+        ("Xen", _) => Some(VmmKind::Xen),
+        // e.g. "Parallels Virtual Platform" or "Parallels ARM Virtual Machine"
+        (vendor, product)
+            if vendor.starts_with("Parallels") || product.starts_with("Parallels") =>
+        {
+            Some(VmmKind::Parallels)
+        }
+        // the vendor is "FreeBSD"
+        (_, "BHYVE") => Some(VmmKind::Bhyve),
+        ("Cloud Hypervisor", _) => Some(VmmKind::CloudHypervisor),
+        // the vendor is "ChromiumOS"
+        (_, "crosvm") => Some(VmmKind::Crosvm),
+        // e.g. "Apple Virtualization Generic Platform"
+        ("Apple Inc.", product) if product.starts_with("Apple Virtualization") => {
+            Some(VmmKind::AppleVirtualization)
+        }
+        // also bare-metal instances, which have no hypervisor
+        ("Amazon EC2", _) => Some(VmmKind::AwsNitro),
+        // the BIOS vendor is also "Google", see `gce_vmm`
+        ("Google", "Google Compute Engine") => Some(VmmKind::GoogleComputeEngine),
+        _ => None,
+    }
+}
+
+/// Probe that identifies the VMM by the DMI system vendor and product name. See
+/// [`vmm_from_dmi`].
+///
+/// Returns [`ProbeResult::Qemu`] for QEMU's default strings, [`ProbeResult::NotQemu`]
+/// for the strings of a VMM that can't be QEMU, such as VirtualBox, and
+/// [`ProbeResult::Inconclusive`] otherwise.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, DmiProbe, QemuCertainty};
+///
+/// // e.g. read from /sys/class/dmi/id/ on Linux
+/// let probe = DmiProbe::new("Oracle Corporation", "VirtualBox");
+/// let report = Detector::builder().cpuid(false).probes(&[&probe]).build().detect();
+/// assert_eq!(report.certainty(), QemuCertainty::DefinitelyNot);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct DmiProbe<'a> {
+    sys_vendor: &'a str,
+    product_name: &'a str,
+}
+
+impl<'a> DmiProbe<'a> {
+    /// Creates a new probe that inspects the given DMI system vendor and product name.
+    pub const fn new(sys_vendor: &'a str, product_name: &'a str) -> Self {
+        Self {
+            sys_vendor,
+            product_name,
+        }
+    }
+}
+
+impl Probe for DmiProbe<'_> {
+    fn name(&self) -> &'static str {
+        "dmi"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        match vmm_from_dmi(self.sys_vendor, self.product_name) {
+            Some(VmmKind::Qemu) => {
+                debug!("DMI system vendor is QEMU.");
+                ProbeResult::Qemu
+            }
+            Some(vmm) if vmm.rules_out_qemu() => {
+                debug!("DMI strings belong to {:?}.", vmm);
+                ProbeResult::NotQemu
+            }
+            _ => ProbeResult::Inconclusive,
+        }
+    }
+}
+
+/// Firmware strings from the DMI (SMBIOS) tables, as used by [`firmware_strings_result`].
+/// Unknown strings are empty.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct FirmwareStrings<'a> {
+    pub(crate) sys_vendor: &'a str,
+    pub(crate) product_name: &'a str,
+    pub(crate) bios_vendor: &'a str,
+    pub(crate) chassis_vendor: &'a str,
+}
+
+/// Evaluates the DMI strings of the SMBIOS-based probes. Returns [`ProbeResult::Qemu`]
+/// for QEMU's system manufacturer, product names, or chassis vendor,
+/// [`ProbeResult::NotQemu`] for the strings of a VMM that can't be QEMU, and
+/// [`ProbeResult::Hint`] for SeaBIOS or Bochs, which QEMU uses but not exclusively.
+pub(crate) fn firmware_strings_result(strings: &FirmwareStrings) -> ProbeResult {
+    let product_name = strings.product_name.trim();
+    match vmm_from_dmi(strings.sys_vendor, product_name) {
+        Some(VmmKind::Qemu) => {
+            debug!("DMI system vendor is QEMU.");
+            return ProbeResult::Qemu;
+        }
+        Some(vmm) if vmm.rules_out_qemu() => {
+            debug!("DMI strings belong to {:?}.", vmm);
+            return ProbeResult::NotQemu;
+        }
+        _ => {}
+    }
+    let bios_vendor = strings.bios_vendor.trim();
+    if product_name.starts_with(QEMU_PRODUCT_PREFIX) {
+        debug!("DMI product name '{}' is from QEMU.", product_name);
+        ProbeResult::Qemu
+    } else if strings.chassis_vendor.trim() == QEMU_CHASSIS_VENDOR {
+        debug!("DMI chassis vendor is QEMU.");
+        ProbeResult::Qemu
+    } else if bios_vendor == SEABIOS_VENDOR
+        || bios_vendor == BOCHS_VENDOR
+        || strings.sys_vendor.trim() == BOCHS_VENDOR
+    {
+        debug!("DMI BIOS vendor is '{}'.", bios_vendor);
+        ProbeResult::Hint
+    } else {
+        ProbeResult::Inconclusive
+    }
+}
@@ -0,0 +1,389 @@
+//! Module for [`DeviceTree`], a minimal reader for flattened device trees (DTB), and
+//! [`DeviceTreeProbe`]. On ARM and RISC-V, the device tree that QEMU generates for its
+//! `virt` machines is the primary detection vector, as there is no CPUID.
+
+use crate::{Probe, ProbeResult};
+
+/// Magic value at the beginning of each flattened device tree.
+const FDT_MAGIC: u32 = 0xd00d_feed;
+/// Size of the header of a flattened device tree.
+const FDT_HEADER_LEN: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// Root `compatible` strings of the device trees that QEMU generates for its machines.
+/// Firecracker, crosvm, cloud-hypervisor, and kvmtool use `linux,dummy-virt` as well.
+pub(crate) const QEMU_ROOT_COMPATIBLES: &[&str] = &[
+    // hw/arm/virt.c
+    "linux,dummy-virt",
+    // hw/riscv/virt.c
+    "riscv-virtio",
+];
+/// `compatible` string of the fw_cfg node, which only QEMU provides.
+const QEMU_FW_CFG_COMPATIBLE: &str = "qemu,fw-cfg-mmio";
+/// Substring of the `model` property of the root node of QEMU's machines, e.g.
+/// `riscv-virtio,qemu`.
+const QEMU_MODEL: &str = "qemu";
+
+/// Read-only view of a flattened device tree (DTB), as it is passed by the firmware or
+/// bootloader to the kernel.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::DeviceTree;
+///
+/// # // root node with `compatible = "linux,dummy-virt"`
+/// # let dtb: &[u8] = &[
+/// #     0xd0, 0x0d, 0xfe, 0xed, 0x00, 0x00, 0x00, 0x73, 0x00, 0x00, 0x00, 0x38,
+/// #     0x00, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x11,
+/// #     0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b,
+/// #     0x00, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+/// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+/// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x11,
+/// #     0x00, 0x00, 0x00, 0x00, 0x6c, 0x69, 0x6e, 0x75, 0x78, 0x2c, 0x64, 0x75,
+/// #     0x6d, 0x6d, 0x79, 0x2d, 0x76, 0x69, 0x72, 0x74, 0x00, 0x00, 0x00, 0x00,
+/// #     0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x09, 0x63, 0x6f, 0x6d, 0x70,
+/// #     0x61, 0x74, 0x69, 0x62, 0x6c, 0x65, 0x00,
+/// # ];
+/// let dt = DeviceTree::new(dtb).unwrap();
+/// assert!(dt.root_is_compatible("linux,dummy-virt"));
+/// assert_eq!(dt.root_compatible().next(), Some("linux,dummy-virt"));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct DeviceTree<'a> {
+    structs: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> DeviceTree<'a> {
+    /// Creates a view of the flattened device tree in `blob`. Returns `None`, if the
+    /// header is invalid or the blob is truncated.
+    pub fn new(blob: &'a [u8]) -> Option<Self> {
+        if read_be_u32(blob, 0)? != FDT_MAGIC {
+            return None;
+        }
+        let total_size = read_be_u32(blob, 4)? as usize;
+        let blob = blob.get(..total_size)?;
+        let off_structs = read_be_u32(blob, 8)? as usize;
+        let off_strings = read_be_u32(blob, 12)? as usize;
+        let size_strings = read_be_u32(blob, 32)? as usize;
+        let size_structs = read_be_u32(blob, 36)? as usize;
+        Some(Self {
+            structs: blob.get(off_structs..off_structs.checked_add(size_structs)?)?,
+            strings: blob.get(off_strings..off_strings.checked_add(size_strings)?)?,
+        })
+    }
+
+    /// Creates a view of the flattened device tree at `ptr`. The size is taken from the
+    /// header. Returns `None`, if the header is invalid.
+    ///
+    /// # Safety
+    /// `ptr` must point to readable memory that contains at least a device tree header.
+    /// If the magic value matches, the whole `totalsize` bytes of the tree must be readable
+    /// and must stay unchanged for `'a`.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Option<Self> {
+        let header = core::slice::from_raw_parts(ptr, FDT_HEADER_LEN);
+        if read_be_u32(header, 0)? != FDT_MAGIC {
+            return None;
+        }
+        let total_size = read_be_u32(header, 4)? as usize;
+        Self::new(core::slice::from_raw_parts(ptr, total_size))
+    }
+
+    /// Returns the value of the property `name` of the node at `path`, such as `/psci`.
+    /// Path segments without unit address (`@...`) match nodes with any unit address.
+    pub fn property(&self, path: &str, name: &str) -> Option<&'a [u8]> {
+        let segments = || path.split('/').filter(|segment| !segment.is_empty());
+        let segment_count = segments().count();
+        // depth of the current node; the root node has depth 1
+        let mut depth = 0;
+        // number of path segments matched by the current node and its ancestors
+        let mut matched = 0;
+        for token in self.tokens() {
+            match token {
+                Token::BeginNode(node_name) => {
+                    depth += 1;
+                    if depth >= 2
+                        && matched == depth - 2
+                        && segments()
+                            .nth(matched)
+                            .map(|segment| node_name_matches(node_name, segment))
+                            .unwrap_or(false)
+                    {
+                        matched += 1;
+                    }
+                }
+                Token::EndNode => {
+                    if depth >= 2 && matched == depth - 1 {
+                        matched -= 1;
+                    }
+                    depth = depth.saturating_sub(1);
+                }
+                Token::Prop(prop_name, value) => {
+                    if matched == segment_count && depth == segment_count + 1 && prop_name == name {
+                        return Some(value);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the address of the first entry of the `reg` property of the node at
+    /// `path`, e.g. `0x0902_0000` for `/fw-cfg` on QEMU's `virt` machine for aarch64.
+    /// The size of the address is taken from `#address-cells` of the root node, which is
+    /// `2` if absent. Nodes whose parent isn't the root node aren't supported.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::DeviceTree;
+    ///
+    /// # // `#address-cells = <2>` and node `fw-cfg@9020000` with `reg = <0 0x9020000 0 0x18>`
+    /// # let dtb: &[u8] = &[
+    /// #     0xd0, 0x0d, 0xfe, 0xed, 0x00, 0x00, 0x00, 0x9f, 0x00, 0x00, 0x00, 0x38,
+    /// #     0x00, 0x00, 0x00, 0x8c, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x11,
+    /// #     0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x13,
+    /// #     0x00, 0x00, 0x00, 0x54, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    /// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    /// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04,
+    /// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01,
+    /// #     0x66, 0x77, 0x2d, 0x63, 0x66, 0x67, 0x40, 0x39, 0x30, 0x32, 0x30, 0x30,
+    /// #     0x30, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x10,
+    /// #     0x00, 0x00, 0x00, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x09, 0x02, 0x00, 0x00,
+    /// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x02,
+    /// #     0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x09, 0x23, 0x61, 0x64, 0x64,
+    /// #     0x72, 0x65, 0x73, 0x73, 0x2d, 0x63, 0x65, 0x6c, 0x6c, 0x73, 0x00, 0x72,
+    /// #     0x65, 0x67, 0x00,
+    /// # ];
+    /// let device_tree = DeviceTree::new(dtb).unwrap();
+    /// assert_eq!(device_tree.reg_address("/fw-cfg"), Some(0x0902_0000));
+    /// ```
+    pub fn reg_address(&self, path: &str) -> Option<u64> {
+        let address_cells = self
+            .property("/", "#address-cells")
+            .and_then(|value| read_be_u32(value, 0))
+            .unwrap_or(2);
+        let reg = self.property(path, "reg")?;
+        match address_cells {
+            1 => read_be_u32(reg, 0).map(u64::from),
+            2 => Some(u64::from(read_be_u32(reg, 0)?) << 32 | u64::from(read_be_u32(reg, 4)?)),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries of the `compatible` string list of the root node.
+    pub fn root_compatible(&self) -> impl Iterator<Item = &'a str> {
+        string_list(self.property("/", "compatible").unwrap_or(&[]))
+    }
+
+    /// Returns if the `compatible` string list of any node contains `compatible`.
+    pub fn any_node_is_compatible(&self, compatible: &str) -> bool {
+        self.tokens().any(|token| match token {
+            Token::Prop("compatible", value) => string_list(value).any(|entry| entry == compatible),
+            _ => false,
+        })
+    }
+
+    /// Returns if the device tree has a fw_cfg node or a root `model` that mentions
+    /// QEMU, which only QEMU provides.
+    pub(crate) fn has_qemu_device(&self) -> bool {
+        let model = self
+            .property("/", "model")
+            .and_then(|value| read_c_str(value).or(core::str::from_utf8(value).ok()))
+            .unwrap_or("");
+        if model
+            .as_bytes()
+            .windows(QEMU_MODEL.len())
+            .any(|window| window.eq_ignore_ascii_case(QEMU_MODEL.as_bytes()))
+        {
+            debug!("Device tree model '{}' mentions QEMU.", model);
+            true
+        } else if self.any_node_is_compatible(QEMU_FW_CFG_COMPATIBLE) {
+            debug!("Device tree has a fw_cfg node.");
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns if the root node is compatible with one of the machines that QEMU
+    /// emulates. Other VMMs use the same strings, so this alone doesn't identify QEMU.
+    pub(crate) fn root_is_virt(&self) -> bool {
+        self.root_compatible()
+            .any(|compatible| QEMU_ROOT_COMPATIBLES.contains(&compatible))
+    }
+
+    /// Returns if the `compatible` string list of the root node contains `compatible`.
+    pub fn root_is_compatible(&self, compatible: &str) -> bool {
+        self.root_compatible().any(|entry| entry == compatible)
+    }
+
+    /// Returns an iterator over the tokens of the structure block.
+    fn tokens(&self) -> Tokens<'a> {
+        Tokens {
+            structs: self.structs,
+            strings: self.strings,
+            offset: 0,
+        }
+    }
+}
+
+/// Token of the structure block of a flattened device tree.
+#[derive(Copy, Clone, Debug)]
+enum Token<'a> {
+    BeginNode(&'a str),
+    EndNode,
+    Prop(&'a str, &'a [u8]),
+}
+
+/// Iterator over the tokens of the structure block. Stops at `FDT_END` or on
+/// malformed data.
+struct Tokens<'a> {
+    structs: &'a [u8],
+    strings: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = read_be_u32(self.structs, self.offset)?;
+            self.offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = read_c_str(self.structs.get(self.offset..)?)?;
+                    self.offset = align4(self.offset + name.len() + 1);
+                    return Some(Token::BeginNode(name));
+                }
+                FDT_END_NODE => return Some(Token::EndNode),
+                FDT_PROP => {
+                    let len = read_be_u32(self.structs, self.offset)? as usize;
+                    let name_offset = read_be_u32(self.structs, self.offset + 4)? as usize;
+                    let value_start = self.offset + 8;
+                    let value = self
+                        .structs
+                        .get(value_start..value_start.checked_add(len)?)?;
+                    let name = read_c_str(self.strings.get(name_offset..)?)?;
+                    self.offset = align4(value_start + len);
+                    return Some(Token::Prop(name, value));
+                }
+                FDT_NOP => continue,
+                FDT_END => return None,
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Probe that checks a device tree for the machines and devices of QEMU: a root `model`
+/// that mentions QEMU, a fw_cfg node (`qemu,fw-cfg-mmio`), or a root node that is
+/// compatible with one of the machines that QEMU emulates, such as `linux,dummy-virt`
+/// for the ARM `virt` machine or `riscv-virtio` for the RISC-V `virt` machine.
+///
+/// Returns [`ProbeResult::Qemu`] for the model or fw_cfg. The root `compatible` alone is
+/// only a [`ProbeResult::Hint`], as Firecracker, crosvm, cloud-hypervisor, and kvmtool
+/// use `linux,dummy-virt` as well. Returns [`ProbeResult::Inconclusive`] otherwise, as
+/// QEMU also emulates real boards with their original device trees.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{DeviceTree, DeviceTreeProbe, Probe, ProbeResult};
+///
+/// # // root node with `compatible = "acme,board"` and a node `fw-cfg@9020000`
+/// # // with `compatible = "qemu,fw-cfg-mmio"`
+/// # let dtb: &[u8] = &[
+/// #     0xd0, 0x0d, 0xfe, 0xed, 0x00, 0x00, 0x00, 0xa3, 0x00, 0x00, 0x00, 0x38,
+/// #     0x00, 0x00, 0x00, 0x98, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x11,
+/// #     0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b,
+/// #     0x00, 0x00, 0x00, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+/// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+/// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0b,
+/// #     0x00, 0x00, 0x00, 0x00, 0x61, 0x63, 0x6d, 0x65, 0x2c, 0x62, 0x6f, 0x61,
+/// #     0x72, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x66, 0x77, 0x2d, 0x63,
+/// #     0x66, 0x67, 0x40, 0x39, 0x30, 0x32, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00,
+/// #     0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00,
+/// #     0x71, 0x65, 0x6d, 0x75, 0x2c, 0x66, 0x77, 0x2d, 0x63, 0x66, 0x67, 0x2d,
+/// #     0x6d, 0x6d, 0x69, 0x6f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+/// #     0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x09, 0x63, 0x6f, 0x6d, 0x70,
+/// #     0x61, 0x74, 0x69, 0x62, 0x6c, 0x65, 0x00,
+/// # ];
+/// // a board that QEMU emulates with an additional fw_cfg device
+/// let device_tree = DeviceTree::new(dtb).unwrap();
+/// let probe = DeviceTreeProbe::new(device_tree);
+/// assert_eq!(probe.run(), ProbeResult::Qemu);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct DeviceTreeProbe<'a> {
+    device_tree: DeviceTree<'a>,
+}
+
+impl<'a> DeviceTreeProbe<'a> {
+    /// Creates a new probe that inspects the given device tree.
+    pub const fn new(device_tree: DeviceTree<'a>) -> Self {
+        Self { device_tree }
+    }
+}
+
+impl Probe for DeviceTreeProbe<'_> {
+    fn name(&self) -> &'static str {
+        "device-tree"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        if self.device_tree.has_qemu_device() {
+            ProbeResult::Qemu
+        } else if self.device_tree.root_is_virt() {
+            debug!("Device tree root is compatible with a QEMU machine.");
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+}
+
+/// Splits a device tree string list (`"a\0b\0"`) into its entries.
+pub(crate) fn string_list(value: &[u8]) -> impl Iterator<Item = &str> {
+    value
+        .split(|&b| b == 0)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| core::str::from_utf8(entry).ok())
+}
+
+/// Returns if a node name matches a path segment. A segment without unit address
+/// matches nodes with any unit address.
+fn node_name_matches(node_name: &str, segment: &str) -> bool {
+    node_name == segment
+        || (!segment.contains('@')
+            && node_name.split('@').next() == Some(segment)
+            && node_name.contains('@'))
+}
+
+/// Reads the null-terminated string at the beginning of `bytes`.
+fn read_c_str(bytes: &[u8]) -> Option<&str> {
+    let end = bytes.iter().position(|&b| b == 0)?;
+    core::str::from_utf8(&bytes[..end]).ok()
+}
+
+/// Reads a big-endian `u32` at `offset`.
+fn read_be_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Aligns `offset` up to the next multiple of four.
+const fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
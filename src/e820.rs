@@ -0,0 +1,107 @@
+//! Module for [`E820Entries`], which parses the guest RAM map that QEMU provides in the
+//! fw_cfg file `etc/e820`, see [`crate::FwCfg::e820`]. Boot loaders can use it before
+//! the firmware's memory map is available, e.g. with `-kernel` on `microvm`.
+
+/// Size of an entry: address, length, and type.
+const ENTRY_LEN: usize = 20;
+
+/// The type of an [`E820Entry`], as in the E820 BIOS interface.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum E820Kind {
+    /// Usable RAM (type 1).
+    Ram,
+    /// Reserved memory (type 2).
+    Reserved,
+    /// ACPI reclaimable memory (type 3).
+    Acpi,
+    /// ACPI NVS memory (type 4).
+    Nvs,
+    /// Unusable memory (type 5).
+    Unusable,
+    /// Any other type.
+    Other(u32),
+}
+
+impl E820Kind {
+    /// Returns the kind of the given E820 type.
+    pub const fn from_raw(raw: u32) -> Self {
+        match raw {
+            1 => Self::Ram,
+            2 => Self::Reserved,
+            3 => Self::Acpi,
+            4 => Self::Nvs,
+            5 => Self::Unusable,
+            _ => Self::Other(raw),
+        }
+    }
+}
+
+/// A region of the guest-physical address space.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct E820Entry {
+    /// Guest-physical start address.
+    pub address: u64,
+    /// Size in bytes.
+    pub len: u64,
+    /// Type of the region.
+    pub kind: E820Kind,
+}
+
+/// Iterator over the entries of a raw E820 table: packed little-endian entries of a `u64`
+/// address, a `u64` length, and a `u32` type. A truncated entry at the end is ignored.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{E820Entries, E820Kind};
+///
+/// # let mut table = [0_u8; 40];
+/// # // 0..640 KiB RAM, 1 MiB..128 MiB RAM
+/// # table[8..16].copy_from_slice(&0xa_0000_u64.to_le_bytes());
+/// # table[16..20].copy_from_slice(&1_u32.to_le_bytes());
+/// # table[20..28].copy_from_slice(&0x10_0000_u64.to_le_bytes());
+/// # table[28..36].copy_from_slice(&0x7f0_0000_u64.to_le_bytes());
+/// # table[36..40].copy_from_slice(&1_u32.to_le_bytes());
+/// let entries = E820Entries::new(&table);
+/// assert_eq!(entries.clone().count(), 2);
+/// assert!(entries.clone().all(|entry| entry.kind == E820Kind::Ram));
+/// assert_eq!(entries.ram_size(), 0xa_0000 + 0x7f0_0000);
+/// ```
+#[derive(Clone, Debug)]
+pub struct E820Entries<'a> {
+    chunks: core::slice::ChunksExact<'a, u8>,
+}
+
+impl<'a> E820Entries<'a> {
+    /// Creates an iterator over the entries of the raw table in `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            chunks: bytes.chunks_exact(ENTRY_LEN),
+        }
+    }
+
+    /// Returns the total size of all [`E820Kind::Ram`] regions in bytes.
+    pub fn ram_size(self) -> u64 {
+        self.filter(|entry| entry.kind == E820Kind::Ram)
+            .fold(0, |size, entry| size.saturating_add(entry.len))
+    }
+}
+
+impl Iterator for E820Entries<'_> {
+    type Item = E820Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.chunks.next()?;
+        Some(E820Entry {
+            address: u64::from_le_bytes(entry[0..8].try_into().unwrap()),
+            len: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+            kind: E820Kind::from_raw(u32::from_le_bytes(entry[16..20].try_into().unwrap())),
+        })
+    }
+}
@@ -0,0 +1,110 @@
+//! Module for [`EfiVendorProbe`], which checks the vendor of the UEFI firmware. QEMU
+//! usually boots UEFI guests with OVMF, the EDK II build for virtual machines, which
+//! reports the vendor `EDK II`. As other VMMs and some boards also use EDK II, this is
+//! only medium-weight evidence.
+
+use crate::{Probe, ProbeResult, Weights};
+
+/// Firmware vendors of OVMF builds.
+const OVMF_VENDORS: &[&str] = &["EDK II", "EFI Development Kit II / OVMF"];
+
+/// DMI BIOS vendor of a UEFI firmware in the sysfs of Linux.
+#[cfg(feature = "std")]
+const SYSFS_BIOS_VENDOR: &str = "/sys/class/dmi/id/bios_vendor";
+/// Directory that exists if Linux was booted via UEFI.
+#[cfg(feature = "std")]
+const SYSFS_EFI_DIR: &str = "/sys/firmware/efi";
+
+/// Where the firmware vendor comes from.
+#[derive(Copy, Clone, Debug)]
+enum Source<'a> {
+    /// The UCS-2 string from the `FirmwareVendor` field of the UEFI system table.
+    Ucs2(&'a [u16]),
+    /// The sysfs of Linux.
+    #[cfg(feature = "std")]
+    Sysfs,
+}
+
+/// Probe that checks if the UEFI firmware is OVMF.
+///
+/// Returns [`ProbeResult::Hint`] for the firmware vendor of OVMF and
+/// [`ProbeResult::Inconclusive`] otherwise. A hint of this probe contributes the average
+/// of [`Weights::probe_hint`] and [`Weights::probe_qemu`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, EfiVendorProbe, ProbeResult, Probe};
+///
+/// // the UCS-2 string from `EFI_SYSTEM_TABLE.FirmwareVendor`
+/// let vendor: Vec<u16> = "EDK II\0".encode_utf16().collect();
+/// let probe = EfiVendorProbe::new(&vendor);
+/// assert_eq!(probe.run(), ProbeResult::Hint);
+/// let report = Detector::builder().cpuid(false).probes(&[&probe]).build().detect();
+/// assert_eq!(report.score(), 30);
+/// ```
+///
+/// [`crate::UefiTables::detect`] runs this probe with the vendor of the system table.
+#[derive(Copy, Clone, Debug)]
+pub struct EfiVendorProbe<'a> {
+    source: Source<'a>,
+}
+
+impl<'a> EfiVendorProbe<'a> {
+    /// Creates a new probe that checks the given UCS-2 firmware vendor, as found in
+    /// `EFI_SYSTEM_TABLE.FirmwareVendor`. Everything from the first null character on is
+    /// ignored.
+    pub const fn new(vendor: &'a [u16]) -> Self {
+        Self {
+            source: Source::Ucs2(vendor),
+        }
+    }
+
+    /// Creates a new probe that reads the firmware vendor from the sysfs of Linux. As
+    /// Linux doesn't expose the vendor string of the system table to user space, the
+    /// probe checks the BIOS vendor of the DMI tables, if Linux was booted via UEFI.
+    #[cfg(feature = "std")]
+    pub const fn sysfs() -> Self {
+        Self {
+            source: Source::Sysfs,
+        }
+    }
+}
+
+impl Probe for EfiVendorProbe<'_> {
+    fn name(&self) -> &'static str {
+        "efi-vendor"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let ovmf = match self.source {
+            Source::Ucs2(vendor) => {
+                let end = vendor.iter().position(|&c| c == 0).unwrap_or(vendor.len());
+                let vendor = &vendor[..end];
+                OVMF_VENDORS
+                    .iter()
+                    .any(|ovmf| ovmf.encode_utf16().eq(vendor.iter().copied()))
+            }
+            #[cfg(feature = "std")]
+            Source::Sysfs => {
+                std::path::Path::new(SYSFS_EFI_DIR).is_dir()
+                    && std::fs::read_to_string(SYSFS_BIOS_VENDOR)
+                        .map(|vendor| OVMF_VENDORS.contains(&vendor.trim()))
+                        .unwrap_or(false)
+            }
+        };
+        if ovmf {
+            debug!("UEFI firmware is OVMF.");
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+
+    fn weight(&self, result: ProbeResult, weights: &Weights) -> i32 {
+        match result {
+            ProbeResult::Hint => (weights.probe_hint + weights.probe_qemu) / 2,
+            _ => weights.probe(result),
+        }
+    }
+}
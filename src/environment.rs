@@ -0,0 +1,213 @@
+//! Module for [`execution_environment`], which answers the broader question if the code
+//! runs in an isolated sandbox, i.e. a virtual machine or a container. Needs the `std`
+//! feature, as containers are only visible through the operating system.
+
+use crate::{detect_hypervisor, VmmKind};
+use std::{env, fs, path::Path};
+
+/// Files that container runtimes create in the root of the container.
+const CONTAINER_MARKER_FILES: [&str; 2] = ["/.dockerenv", "/run/.containerenv"];
+
+/// Entries in `/proc/1/cgroup` that show that the init process runs in a container.
+const CONTAINER_CGROUPS: [&str; 5] = ["docker", "kubepods", "containerd", "libpod", "lxc"];
+
+/// Kernel version that gVisor reports in `/proc/version`, independent of its release.
+const GVISOR_KERNEL_VERSION: &str = "#1 SMP Sun Jan 10 15:06:54 PST 2016";
+
+/// Vendor that User-mode Linux reports in `/proc/cpuinfo`.
+const UML_CPU_VENDOR: &str = "User Mode Linux";
+
+/// Environment variables that container runtimes set.
+const CONTAINER_ENV_VARS: [&str; 2] = ["container", "KUBERNETES_SERVICE_HOST"];
+
+/// Environment variables that configure qemu-user. qemu-user passes them on to the
+/// emulated process, but they may also be set on the host, e.g. by cross-build tools.
+const QEMU_USER_ENV_VARS: [&str; 5] = [
+    "QEMU_LD_PREFIX",
+    "QEMU_CPU",
+    "QEMU_GUEST_BASE",
+    "QEMU_STRACE",
+    "QEMU_SET_ENV",
+];
+
+/// Prefix of the file names of the qemu-user binaries, e.g. `qemu-aarch64-static`.
+const QEMU_USER_BINARY_PREFIX: &str = "qemu-";
+
+/// The environment that the code runs in. Result of [`execution_environment`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ExecutionEnvironment {
+    /// Neither a virtual machine nor a container was found.
+    BareMetal,
+    /// A virtual machine of the given VMM, but no container.
+    VirtualMachine(VmmKind),
+    /// A container, either on bare metal (`vmm` is `None`) or inside a virtual machine.
+    Container {
+        /// The VMM of the virtual machine that runs the container, if any.
+        vmm: Option<VmmKind>,
+    },
+    /// A kernel that runs as user-space process and implements the system calls of the
+    /// application, such as gVisor. Such kernels usually pass the CPUID of the host
+    /// through, so the hypervisor isn't reported.
+    UserModeKernel(UserModeKernel),
+    /// The process is emulated by qemu-user, e.g. `qemu-aarch64` on an x86_64 host with
+    /// binfmt_misc, as in cross-build CI. There is no virtual machine: the system calls are
+    /// forwarded to the kernel of the host.
+    UserModeEmulation,
+}
+
+/// A kernel that runs as user-space process. See [`ExecutionEnvironment::UserModeKernel`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum UserModeKernel {
+    /// gVisor, e.g. the `runsc` runtime of Docker or Kubernetes.
+    GVisor,
+    /// User-mode Linux (UML).
+    UserModeLinux,
+}
+
+impl ExecutionEnvironment {
+    /// Returns if the code runs isolated from the host, i.e. in a virtual machine or a
+    /// container.
+    pub const fn is_isolated(&self) -> bool {
+        !matches!(self, Self::BareMetal)
+    }
+}
+
+/// Determines if the code runs on bare metal, in a virtual machine, in a container, or
+/// under a user-mode kernel.
+///
+/// qemu-user takes precedence, as it emulates the CPU of a foreign architecture and the
+/// remaining checks would only describe the host. It is identified by the host
+/// architecture in `/proc/sys/kernel/arch`, its binary in `/proc/self/maps`, and its
+/// environment variables together with a modified `argv[0]`. A user-mode kernel takes
+/// precedence over containers, as it usually runs inside a container itself. gVisor is
+/// identified by its fixed kernel version in `/proc/version`, User-mode Linux by the CPU
+/// vendor in `/proc/cpuinfo`. Containers are identified by the marker files of Docker
+/// and Podman, the cgroup of the init process, and the environment variables of
+/// systemd-nspawn, Podman, and Kubernetes. The virtual machine is identified via
+/// [`detect_hypervisor`], so on architectures without CPUID, no virtual machine is found.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{execution_environment, ExecutionEnvironment};
+///
+/// match execution_environment() {
+///     ExecutionEnvironment::BareMetal => println!("bare metal"),
+///     ExecutionEnvironment::VirtualMachine(vmm) => println!("VM: {:?}", vmm),
+///     ExecutionEnvironment::Container { vmm } => println!("container, VM: {:?}", vmm),
+///     _ => println!("other sandbox"),
+/// }
+/// ```
+pub fn execution_environment() -> ExecutionEnvironment {
+    if runs_under_qemu_user() {
+        return ExecutionEnvironment::UserModeEmulation;
+    }
+    if let Some(kernel) = user_mode_kernel() {
+        return ExecutionEnvironment::UserModeKernel(kernel);
+    }
+    let vmm = detect_hypervisor();
+    match (runs_in_container(), vmm) {
+        (true, vmm) => ExecutionEnvironment::Container { vmm },
+        (false, Some(vmm)) => ExecutionEnvironment::VirtualMachine(vmm),
+        (false, None) => ExecutionEnvironment::BareMetal,
+    }
+}
+
+/// Returns if the process is emulated by qemu-user. qemu-user emulates `uname` and hides
+/// its own mappings in `/proc/self/maps` in most configurations, so the checks are
+/// heuristics:
+/// - the host architecture in `/proc/sys/kernel/arch`, which qemu-user doesn't emulate,
+///   doesn't match the architecture of the binary,
+/// - `/proc/self/maps` contains a qemu-user binary, or
+/// - a qemu-user environment variable is set and `argv[0]` doesn't match the executable.
+fn runs_under_qemu_user() -> bool {
+    let foreign_arch = fs::read_to_string("/proc/sys/kernel/arch")
+        .map(|arch| !host_arch_runs_natively(arch.trim(), env::consts::ARCH))
+        .unwrap_or(false);
+    let qemu_mapping = fs::read_to_string("/proc/self/maps")
+        .map(|maps| {
+            maps.lines()
+                .filter_map(|line| line.rsplit('/').next())
+                .any(|name| name.starts_with(QEMU_USER_BINARY_PREFIX))
+        })
+        .unwrap_or(false);
+    let env_var = QEMU_USER_ENV_VARS
+        .iter()
+        .any(|name| env::var_os(name).is_some());
+    let argv0_mismatch = env_var && argv0_mismatch();
+    debug!(
+        "qemu-user markers: foreign arch {:?}, mapping {:?}, environment {:?}, argv[0] {:?}.",
+        foreign_arch, qemu_mapping, env_var, argv0_mismatch
+    );
+    foreign_arch || qemu_mapping || argv0_mismatch
+}
+
+/// Returns if a kernel of the architecture `host` (as in `uname -m` of the host) runs
+/// binaries of the architecture `target` (as in [`env::consts::ARCH`]) natively. 64-bit
+/// kernels also run the binaries of their 32-bit counterpart.
+fn host_arch_runs_natively(host: &str, target: &str) -> bool {
+    match target {
+        "x86" => matches!(host, "i386" | "i486" | "i586" | "i686" | "x86_64"),
+        "arm" => host.starts_with("arm") || host == "aarch64",
+        "aarch64" => matches!(host, "aarch64" | "arm64"),
+        "powerpc" | "powerpc64" => host.starts_with("ppc"),
+        "mips" | "mips64" => host.starts_with("mips"),
+        "riscv32" | "riscv64" => host.starts_with("riscv"),
+        "s390x" => host.starts_with("s390"),
+        _ => host.starts_with(target),
+    }
+}
+
+/// Returns if the file name of `argv[0]` differs from the one of the executable.
+/// qemu-user without the binfmt_misc flag `P` replaces `argv[0]` by the path of the
+/// binary, with the flag, `argv[0]` is what the caller passed.
+fn argv0_mismatch() -> bool {
+    let argv0 = env::args_os().next();
+    let exe = env::current_exe().ok();
+    match (argv0, exe) {
+        (Some(argv0), Some(exe)) => Path::new(&argv0).file_name() != exe.file_name(),
+        _ => false,
+    }
+}
+
+/// Identifies the user-mode kernel, if any, by its quirks in `/proc`.
+fn user_mode_kernel() -> Option<UserModeKernel> {
+    let file_contains = |path: &str, needle: &str| {
+        fs::read_to_string(path)
+            .map(|content| content.contains(needle))
+            .unwrap_or(false)
+    };
+    if file_contains("/proc/version", GVISOR_KERNEL_VERSION) {
+        debug!("Kernel version is the one from gVisor.");
+        Some(UserModeKernel::GVisor)
+    } else if file_contains("/proc/cpuinfo", UML_CPU_VENDOR) {
+        debug!("CPU vendor is User-mode Linux.");
+        Some(UserModeKernel::UserModeLinux)
+    } else {
+        None
+    }
+}
+
+/// Returns if any container marker is present.
+fn runs_in_container() -> bool {
+    let marker_file = CONTAINER_MARKER_FILES
+        .iter()
+        .any(|path| Path::new(path).exists());
+    let env_var = CONTAINER_ENV_VARS
+        .iter()
+        .any(|name| env::var_os(name).is_some());
+    let cgroup = fs::read_to_string("/proc/1/cgroup")
+        .map(|cgroups| CONTAINER_CGROUPS.iter().any(|name| cgroups.contains(name)))
+        .unwrap_or(false);
+    debug!(
+        "Container markers: file {:?}, environment {:?}, cgroup {:?}.",
+        marker_file, env_var, cgroup
+    );
+    marker_file || env_var || cgroup
+}
@@ -0,0 +1,37 @@
+//! Module for [`DetectError`], the error of the fallible API such as
+//! [`crate::try_runs_inside_qemu`].
+
+use core::fmt;
+
+/// Error of [`crate::try_runs_inside_qemu`] and [`crate::Detector::try_detect`]. Tells why
+/// a detection source failed, instead of treating the failure like a negative result.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum DetectError {
+    /// The CPUID checks are enabled, but CPUID is not available on this architecture.
+    CpuidUnavailable,
+    /// The CPUID leaf that a check requires is missing, for example the hypervisor info
+    /// leaf `0x4000_0000` although the Hypervisor-flag is set.
+    LeafMissing {
+        /// The missing CPUID leaf.
+        leaf: u32,
+    },
+    /// A [`crate::Probe`] returned [`crate::ProbeResult::Faulted`].
+    ProbeFaulted {
+        /// Name of the probe, see [`crate::Probe::name`]. With the `serde` feature, it is
+        /// borrowed when deserializing, so the input must be `'static`.
+        probe: &'static str,
+    },
+}
+
+impl fmt::Display for DetectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CpuidUnavailable => f.write_str("CPUID is not available"),
+            Self::LeafMissing { leaf } => write!(f, "CPUID leaf {:#x} is missing", leaf),
+            Self::ProbeFaulted { probe } => write!(f, "probe '{}' faulted", probe),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Module for [`FixedStr`], an owned string with fixed capacity that doesn't need
+//! heap allocations.
+
+use core::fmt;
+
+/// Owned UTF-8 string with a fixed capacity of `N` bytes. Unused bytes are zero.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct FixedStr<const N: usize> {
+    bytes: [u8; N],
+}
+
+impl<const N: usize> FixedStr<N> {
+    /// Creates a new string. If `s` is longer than the capacity, it is truncated at
+    /// the last character boundary that fits.
+    #[cfg_attr(
+        all(
+            feature = "alloc",
+            not(any(target_arch = "x86", target_arch = "x86_64"))
+        ),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(s: &str) -> Self {
+        let mut len = s.len().min(N);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; N];
+        bytes[..len].copy_from_slice(&s.as_bytes()[..len]);
+        Self { bytes }
+    }
+
+    /// Returns the string without the zero padding.
+    pub(crate) fn as_str(&self) -> &str {
+        crate::report::str_from_padded(&self.bytes)
+    }
+}
+
+impl<const N: usize> fmt::Debug for FixedStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const N: usize> defmt::Format for FixedStr<N> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for FixedStr<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for FixedStr<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<const N: usize>;
+
+        impl<const N: usize> serde::de::Visitor<'_> for Visitor<N> {
+            type Value = FixedStr<N>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a string with at most {} bytes", N)
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(FixedStr::new(v))
+            }
+        }
+
+        deserializer.deserialize_str(Visitor::<N>)
+    }
+}
@@ -0,0 +1,704 @@
+//! Access to QEMU's firmware configuration device (fw_cfg), either via the I/O ports
+//! `0x510` (selector) and `0x511` (data) on x86, or via MMIO on the `virt` machines for
+//! ARM and RISC-V. Only QEMU implements this device, so its presence is strong evidence
+//! for QEMU. [`FwCfg`] reads the items and files that the host passes to the guest, such
+//! as `-fw_cfg name=opt/...,file=...`, `-kernel`, and `-initrd`.
+
+use crate::fixed_str::FixedStr;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::io;
+use crate::{DeviceTree, E820Entries, Probe, ProbeResult};
+use core::sync::atomic::{fence, Ordering};
+use core::{fmt, ptr};
+
+/// I/O port of the selector register.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const SELECTOR_PORT: u16 = 0x510;
+/// I/O port of the data register.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const DATA_PORT: u16 = 0x511;
+/// I/O port of the DMA address register, whose high half is written first.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const DMA_PORT: u16 = 0x514;
+/// Offset of the data register in the MMIO region.
+const MMIO_DATA_OFFSET: usize = 0x0;
+/// Offset of the selector register in the MMIO region. The selector is big-endian.
+const MMIO_SELECTOR_OFFSET: usize = 0x8;
+/// Offset of the DMA address register in the MMIO region. The address is big-endian.
+const MMIO_DMA_OFFSET: usize = 0x10;
+/// Path of the fw_cfg node in the device trees of QEMU's `virt` machines, e.g.
+/// `/fw-cfg@9020000`.
+const DEVICE_TREE_NODE: &str = "/fw-cfg";
+/// Item with the signature [`SIGNATURE`].
+const ITEM_SIGNATURE: u16 = 0x0000;
+/// Content of the signature item.
+const SIGNATURE: &[u8; 4] = b"QEMU";
+/// Item with the interface revision and features as little-endian `u32`.
+const ITEM_ID: u16 = 0x0001;
+/// Feature bit in [`ITEM_ID`]: the DMA interface is available.
+const ID_DMA: u32 = 1 << 1;
+/// Item with the size of the kernel as little-endian `u32`. On x86, without the setup
+/// code of a bzImage.
+const ITEM_KERNEL_SIZE: u16 = 0x0008;
+/// Item with the size of the initrd as little-endian `u32`.
+const ITEM_INITRD_SIZE: u16 = 0x000b;
+/// Item with the kernel of `-kernel`.
+const ITEM_KERNEL_DATA: u16 = 0x0011;
+/// Item with the initrd of `-initrd`.
+const ITEM_INITRD_DATA: u16 = 0x0012;
+/// Item with the size of the kernel command line as little-endian `u32`, including the
+/// terminating null byte.
+const ITEM_CMDLINE_SIZE: u16 = 0x0014;
+/// Item with the kernel command line of `-append`.
+const ITEM_CMDLINE_DATA: u16 = 0x0015;
+/// File with the guest RAM map.
+const E820_FILE: &str = "etc/e820";
+/// Item with the file directory: a big-endian count followed by the entries.
+const ITEM_FILE_DIR: u16 = 0x0019;
+/// Size of an entry of the file directory: size, selector, reserved, and the name.
+const FILE_DIR_ENTRY_LEN: usize = 64;
+/// Maximum length of a file name, including the terminating null byte.
+pub const FW_CFG_MAX_FILE_NAME_LEN: usize = 56;
+/// Control bit of a DMA transfer: the transfer failed.
+const DMA_CTL_ERROR: u32 = 1 << 0;
+/// Control bit of a DMA transfer: read from the selected item.
+const DMA_CTL_READ: u32 = 1 << 1;
+/// Control bit of a DMA transfer: select the item in the upper 16 bits first.
+const DMA_CTL_SELECT: u32 = 1 << 3;
+/// DMA transfers are split at this boundary, as the buffer is only virtually contiguous.
+const DMA_PAGE_SIZE: usize = 4096;
+/// Upper bound of the file directory entries that are read, in case the device isn't
+/// fw_cfg and returns garbage.
+const MAX_FILES: u32 = 4096;
+
+/// Translates a virtual address of the guest into the physical address that the fw_cfg
+/// device accesses via DMA. See [`FwCfg::with_dma`]. As a closure, it can capture state
+/// such as the offset of a higher-half direct map.
+pub type VirtToPhysFn<'a> = dyn Fn(*const u8) -> u64 + 'a;
+
+/// The control structure of a DMA transfer (`FWCfgDmaAccess`). All fields are
+/// big-endian.
+#[repr(C, align(16))]
+struct DmaAccess {
+    control: u32,
+    length: u32,
+    address: u64,
+}
+
+/// How the fw_cfg device is accessed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Transport {
+    /// I/O ports `0x510` and `0x511`.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Ports,
+    /// MMIO region at the given base address.
+    Mmio(*mut u8),
+}
+
+impl Transport {
+    /// Selects `item` and reads the first `buf.len()` bytes of it into `buf`.
+    ///
+    /// # Safety
+    /// For [`Self::Ports`], see [`io`]. For [`Self::Mmio`], the region must be mapped as
+    /// device memory. On machines without fw_cfg, the ports or the region might belong to
+    /// another device.
+    unsafe fn read(self, item: u16, buf: &mut [u8]) {
+        self.select(item);
+        self.read_data(buf);
+    }
+
+    /// Selects `item` and resets the data offset to zero.
+    ///
+    /// # Safety
+    /// See [`Self::read`].
+    unsafe fn select(self, item: u16) {
+        match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Ports => io::outw(SELECTOR_PORT, item),
+            Self::Mmio(base) => base
+                .add(MMIO_SELECTOR_OFFSET)
+                .cast::<u16>()
+                .write_volatile(item.to_be()),
+        }
+    }
+
+    /// Reads the next `buf.len()` bytes of the selected item into `buf`.
+    ///
+    /// # Safety
+    /// See [`Self::read`].
+    unsafe fn read_data(self, buf: &mut [u8]) {
+        match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Ports => {
+                for byte in buf {
+                    *byte = io::inb(DATA_PORT);
+                }
+            }
+            Self::Mmio(base) => {
+                for byte in buf {
+                    *byte = base.add(MMIO_DATA_OFFSET).read_volatile();
+                }
+            }
+        }
+    }
+
+    /// Starts the DMA transfer that `access` describes and waits for its completion.
+    /// Returns `false`, if the device reports an error.
+    ///
+    /// # Safety
+    /// See [`Self::read`]. `address` must be the physical address of `access`, and the
+    /// physical address in `access` must be the one of memory that may be written.
+    unsafe fn dma(self, access: &mut DmaAccess, address: u64) -> bool {
+        let (high, low) = (((address >> 32) as u32).to_be(), (address as u32).to_be());
+        fence(Ordering::SeqCst);
+        // writing the low half starts the transfer
+        match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Ports => {
+                io::outl(DMA_PORT, high);
+                io::outl(DMA_PORT + 4, low);
+            }
+            Self::Mmio(base) => {
+                let register = base.add(MMIO_DMA_OFFSET).cast::<u32>();
+                register.write_volatile(high);
+                register.add(1).write_volatile(low);
+            }
+        }
+        // QEMU completes the transfer synchronously, but the interface allows otherwise
+        let control = loop {
+            let control = u32::from_be(ptr::addr_of!(access.control).read_volatile());
+            if control & !DMA_CTL_ERROR == 0 {
+                break control;
+            }
+            core::hint::spin_loop();
+        };
+        fence(Ordering::SeqCst);
+        control & DMA_CTL_ERROR == 0
+    }
+
+    /// Selects the file directory and returns an iterator over its entries.
+    ///
+    /// # Safety
+    /// See [`Self::read`].
+    unsafe fn files(self) -> FwCfgFiles {
+        let mut count = [0; 4];
+        self.read(ITEM_FILE_DIR, &mut count);
+        FwCfgFiles {
+            transport: self,
+            remaining: u32::from_be_bytes(count).min(MAX_FILES),
+        }
+    }
+
+    /// Returns if the fw_cfg device answers with the `QEMU` signature.
+    ///
+    /// # Safety
+    /// See [`Self::read`].
+    unsafe fn signature_present(self) -> bool {
+        let mut signature = [0; 4];
+        self.read(ITEM_SIGNATURE, &mut signature);
+        &signature == SIGNATURE
+    }
+}
+
+/// Returns if the fw_cfg device answers with the `QEMU` signature on the I/O ports.
+///
+/// # Safety
+/// See [`io`]. On machines without fw_cfg, the ports might belong to another device.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) unsafe fn signature_present() -> bool {
+    Transport::Ports.signature_present()
+}
+
+/// Reads the file `name` from fw_cfg on the I/O ports into `buf`. Returns the number of
+/// bytes of the file, which may be larger than `buf`, or `None`, if fw_cfg or the file
+/// isn't present.
+///
+/// # Safety
+/// See [`io`]. Nobody else may access fw_cfg meanwhile.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) unsafe fn read_file(name: &str, buf: &mut [u8]) -> Option<usize> {
+    FwCfg::new().read_file(name, buf)
+}
+
+/// An entry of the fw_cfg file directory. Result of [`FwCfg::files`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct FwCfgFile {
+    /// Size of the file in bytes.
+    pub size: u32,
+    /// The item that selects the file, see [`FwCfg::read_item`].
+    pub select: u16,
+    name: FixedStr<FW_CFG_MAX_FILE_NAME_LEN>,
+}
+
+impl FwCfgFile {
+    /// Returns the name of the file, such as `etc/e820` or `opt/org.example/config`.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+/// Iterator over the fw_cfg file directory. Result of [`FwCfg::files`].
+///
+/// The entries are read one after another from the data register, so fw_cfg must not be
+/// accessed otherwise until the iterator is dropped.
+#[derive(Clone, Debug)]
+pub struct FwCfgFiles {
+    transport: Transport,
+    remaining: u32,
+}
+
+impl Iterator for FwCfgFiles {
+    type Item = FwCfgFile;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let mut entry = [0; FILE_DIR_ENTRY_LEN];
+        // SAFETY: guaranteed by the caller of `FwCfg::files`
+        unsafe { self.transport.read_data(&mut entry) };
+        let name = crate::report::str_from_padded(&entry[8..]);
+        Some(FwCfgFile {
+            size: u32::from_be_bytes(entry[0..4].try_into().unwrap()),
+            select: u16::from_be_bytes([entry[4], entry[5]]),
+            name: FixedStr::new(name),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, usize::try_from(self.remaining).ok())
+    }
+}
+
+/// Client for QEMU's fw_cfg device, which passes data from the host to the guest: the
+/// file directory with files such as `etc/e820` and the ones of
+/// `-fw_cfg name=opt/...,file=...`, and the items of `-kernel`, `-initrd`, and `-append`.
+///
+/// By default, all data is read byte by byte via the data register. Reading large blobs
+/// such as an initrd is slow, as each byte causes a VM exit. [`FwCfg::with_dma`] enables
+/// the DMA interface, which transfers a whole page per VM exit.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::FwCfg;
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// let fw_cfg = unsafe { FwCfg::new() };
+/// if fw_cfg.is_present() {
+///     for file in fw_cfg.files() {
+///         println!("{}: {} bytes", file.name(), file.size);
+///     }
+///     let mut buf = [0; 256];
+///     if let Some(size) = fw_cfg.read_file("opt/org.example/config", &mut buf) {
+///         println!("config: {:?}", &buf[..size.min(buf.len())]);
+///     }
+/// }
+/// ```
+#[derive(Copy, Clone)]
+pub struct FwCfg<'a> {
+    transport: Transport,
+    /// The address translation, if DMA is enabled and supported.
+    dma: Option<&'a VirtToPhysFn<'a>>,
+}
+
+impl fmt::Debug for FwCfg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FwCfg")
+            .field("transport", &self.transport)
+            .field("dma", &self.dma.is_some())
+            .finish()
+    }
+}
+
+impl<'a> FwCfg<'a> {
+    /// Creates a client that accesses fw_cfg via the I/O ports.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access fw_cfg while the
+    /// client is used. On machines without fw_cfg, the ports `0x510` and `0x511` might
+    /// belong to another device; the client writes the selector port.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub const unsafe fn new() -> Self {
+        Self {
+            transport: Transport::Ports,
+            dma: None,
+        }
+    }
+
+    /// Creates a client that accesses fw_cfg via the MMIO region at `base`, e.g.
+    /// `0x0902_0000` on QEMU's `virt` machine for aarch64.
+    ///
+    /// # Safety
+    /// The first 16 bytes at `base` must be mapped as device memory, and nobody else may
+    /// access fw_cfg while the client is used. The client writes to `base + 8`, so
+    /// `base` must belong to fw_cfg or to no device at all.
+    pub const unsafe fn with_mmio(base: *mut u8) -> Self {
+        Self {
+            transport: Transport::Mmio(base),
+            dma: None,
+        }
+    }
+
+    /// Creates a client for the fw_cfg node of the device tree, such as
+    /// `/fw-cfg@9020000`. Returns `None`, if the device tree has no such node, e.g.
+    /// because the machine isn't QEMU.
+    ///
+    /// # Safety
+    /// The region from the device tree must be identity-mapped as device memory. Apart
+    /// from that, see [`Self::with_mmio`].
+    pub unsafe fn from_device_tree(device_tree: &DeviceTree) -> Option<Self> {
+        let base = device_tree.reg_address(DEVICE_TREE_NODE)?;
+        let base = usize::try_from(base).ok()?;
+        Some(Self::with_mmio(base as *mut u8))
+    }
+
+    /// Returns a client that reads items and files via the DMA interface, if the device
+    /// supports it (see [`Self::dma_supported`]), and falls back to the data register
+    /// otherwise. The support is probed once, here. `translate` translates the addresses
+    /// of the buffers, and of the control structure on the stack, into physical
+    /// addresses. Transfers are split at 4 KiB boundaries, so buffers only need to be
+    /// physically contiguous within a page. The file directory is always read via the
+    /// data register.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust,no_run
+    /// use runs_inside_qemu::FwCfg;
+    ///
+    /// // e.g. the higher-half direct map offset from the boot loader
+    /// let hhdm_offset = 0xffff_8000_0000_0000_u64;
+    /// let virt_to_phys = |virt: *const u8| virt as u64 - hhdm_offset;
+    ///
+    /// static mut INITRD: [u8; 0x10_0000] = [0; 0x10_0000];
+    ///
+    /// // SAFETY: runs in ring 0 before other CPUs are started, and the buffers and the
+    /// // stack are in the direct map
+    /// let fw_cfg = unsafe { FwCfg::new().with_dma(&virt_to_phys) };
+    /// // SAFETY: nothing else accesses the buffer
+    /// let initrd = unsafe { &mut *core::ptr::addr_of_mut!(INITRD) };
+    /// if let Some(size) = fw_cfg.initrd(initrd) {
+    ///     println!("initrd: {} bytes", size);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    /// `translate` must return the correct physical address for every address of the
+    /// buffers and the stack, as the device writes to the returned addresses. The
+    /// memory must be accessible to the device, e.g. not be encrypted with SEV.
+    pub unsafe fn with_dma(self, translate: &'a VirtToPhysFn<'a>) -> Self {
+        let dma = self.dma_supported().then_some(translate);
+        debug!("fw_cfg DMA enabled: {}", dma.is_some());
+        Self {
+            transport: self.transport,
+            dma,
+        }
+    }
+
+    /// Returns if reads use the DMA interface, i.e. [`Self::with_dma`] was used and the
+    /// device supports it.
+    pub fn dma_enabled(&self) -> bool {
+        self.dma.is_some()
+    }
+
+    /// Returns if the device supports the DMA interface. This accesses the device on
+    /// every call. See [`Self::with_dma`].
+    pub fn dma_supported(&self) -> bool {
+        let mut id = [0; 4];
+        self.is_present()
+            // SAFETY: guaranteed by the caller of the constructor
+            && unsafe {
+                self.transport.read(ITEM_ID, &mut id);
+                u32::from_le_bytes(id) & ID_DMA != 0
+            }
+    }
+
+    /// Selects `item` and reads the first `buf.len()` bytes of it into `buf`, via DMA if
+    /// it is enabled, see [`Self::dma_enabled`].
+    ///
+    /// # Safety
+    /// The device must be present, see [`Self::is_present`].
+    unsafe fn read(&self, item: u16, buf: &mut [u8]) {
+        if let Some(translate) = self.dma {
+            if self.read_dma(translate, item, buf) {
+                return;
+            }
+            debug!("fw_cfg DMA transfer of item {} failed.", item);
+        }
+        self.transport.read(item, buf);
+    }
+
+    /// Selects `item` and reads the first `buf.len()` bytes of it into `buf` via DMA, one
+    /// transfer per page of `buf`. Returns `false`, if a transfer fails.
+    ///
+    /// # Safety
+    /// See [`Self::read`] and [`Self::with_dma`].
+    unsafe fn read_dma(&self, translate: &VirtToPhysFn, item: u16, buf: &mut [u8]) -> bool {
+        let mut control = u32::from(item) << 16 | DMA_CTL_SELECT | DMA_CTL_READ;
+        let mut offset = 0;
+        while offset < buf.len() {
+            let chunk = buf.as_mut_ptr().add(offset);
+            let len = (DMA_PAGE_SIZE - chunk as usize % DMA_PAGE_SIZE).min(buf.len() - offset);
+            let mut access = DmaAccess {
+                control: control.to_be(),
+                length: (len as u32).to_be(),
+                address: translate(chunk).to_be(),
+            };
+            let address = translate(ptr::addr_of!(access).cast());
+            if !self.transport.dma(&mut access, address) {
+                return false;
+            }
+            // the following transfers continue where the previous one stopped
+            control = DMA_CTL_READ;
+            offset += len;
+        }
+        true
+    }
+
+    /// Reads an item whose size is in the little-endian `u32` item `size_item`, such as
+    /// the kernel. See [`Self::read_file`] for the result.
+    fn read_blob(&self, size_item: u16, item: u16, buf: &mut [u8]) -> Option<usize> {
+        if !self.is_present() {
+            return None;
+        }
+        let mut size = [0; 4];
+        // SAFETY: guaranteed by the caller of the constructor
+        unsafe { self.transport.read(size_item, &mut size) };
+        let size = usize::try_from(u32::from_le_bytes(size)).ok()?;
+        if size == 0 {
+            return None;
+        }
+        let len = size.min(buf.len());
+        // SAFETY: guaranteed by the caller of the constructor
+        unsafe { self.read(item, &mut buf[..len]) };
+        Some(size)
+    }
+
+    /// Returns if the fw_cfg device answers with its `QEMU` signature. The other
+    /// functions check this as well.
+    pub fn is_present(&self) -> bool {
+        // SAFETY: guaranteed by the caller of the constructor
+        unsafe { self.transport.signature_present() }
+    }
+
+    /// Returns an iterator over the file directory. It is empty, if fw_cfg isn't
+    /// present. See [`FwCfgFiles`] for the restrictions while it is used.
+    pub fn files(&self) -> FwCfgFiles {
+        if !self.is_present() {
+            return FwCfgFiles {
+                transport: self.transport,
+                remaining: 0,
+            };
+        }
+        // SAFETY: guaranteed by the caller of the constructor
+        unsafe { self.transport.files() }
+    }
+
+    /// Looks up the file `name`, such as `etc/e820`, in the file directory.
+    pub fn find_file(&self, name: &str) -> Option<FwCfgFile> {
+        self.files().find(|file| file.name() == name)
+    }
+
+    /// Reads the file `name` into `buf`. Returns the size of the file, which may be
+    /// larger than `buf`, so that only the first `buf.len()` bytes are read, or `None`,
+    /// if fw_cfg or the file isn't present.
+    pub fn read_file(&self, name: &str, buf: &mut [u8]) -> Option<usize> {
+        let file = self.find_file(name)?;
+        let size = usize::try_from(file.size).ok()?;
+        let len = size.min(buf.len());
+        // SAFETY: guaranteed by the caller of the constructor, `find_file` checked the
+        // presence
+        unsafe { self.read(file.select, &mut buf[..len]) };
+        Some(size)
+    }
+
+    /// Selects `item` and reads its first `buf.len()` bytes into `buf`, e.g. with the
+    /// selector of a [`FwCfgFile`]. Returns `false`, if fw_cfg isn't present. Items that
+    /// are shorter than `buf` pad it with zeros.
+    pub fn read_item(&self, item: u16, buf: &mut [u8]) -> bool {
+        if !self.is_present() {
+            return false;
+        }
+        // SAFETY: guaranteed by the caller of the constructor
+        unsafe { self.read(item, buf) };
+        true
+    }
+
+    /// Reads the kernel of `-kernel` into `buf`. On x86, QEMU splits a bzImage and this
+    /// is the protected-mode part without the setup code. See [`Self::read_file`] for the
+    /// result; `None` also means that there is no kernel.
+    pub fn kernel(&self, buf: &mut [u8]) -> Option<usize> {
+        self.read_blob(ITEM_KERNEL_SIZE, ITEM_KERNEL_DATA, buf)
+    }
+
+    /// Reads the initrd of `-initrd` into `buf`. See [`Self::read_file`] for the result;
+    /// `None` also means that there is no initrd.
+    pub fn initrd(&self, buf: &mut [u8]) -> Option<usize> {
+        self.read_blob(ITEM_INITRD_SIZE, ITEM_INITRD_DATA, buf)
+    }
+
+    /// Reads the kernel command line that QEMU passes via `-append` into `buf`. Returns
+    /// `None`, if fw_cfg isn't present, if there is no command line, if it doesn't fit
+    /// into `buf`, or if it isn't valid UTF-8.
+    pub fn cmdline<'b>(&self, buf: &'b mut [u8]) -> Option<&'b str> {
+        if !self.is_present() {
+            return None;
+        }
+        let mut size = [0; 4];
+        // SAFETY: guaranteed by the caller of the constructor
+        unsafe { self.transport.read(ITEM_CMDLINE_SIZE, &mut size) };
+        let size = usize::try_from(u32::from_le_bytes(size)).ok()?;
+        let cmdline = buf.get_mut(..size)?;
+        // SAFETY: guaranteed by the caller of the constructor
+        unsafe { self.read(ITEM_CMDLINE_DATA, cmdline) };
+        let len = cmdline.iter().position(|&b| b == 0).unwrap_or(size);
+        core::str::from_utf8(&cmdline[..len])
+            .ok()
+            .map(str::trim)
+            .filter(|cmdline| !cmdline.is_empty())
+    }
+
+    /// Reads the guest RAM map from the fw_cfg file `etc/e820` into `buf` and returns its
+    /// entries. Returns `None`, if fw_cfg or the file isn't present, e.g. on ARM, or if
+    /// the map doesn't fit into `buf`. QEMU reports at most a few entries of 20 bytes.
+    pub fn e820<'b>(&self, buf: &'b mut [u8]) -> Option<E820Entries<'b>> {
+        let size = self.read_file(E820_FILE, buf)?;
+        let table = buf.get(..size)?;
+        Some(E820Entries::new(table))
+    }
+}
+
+/// Probe that checks if QEMU's fw_cfg device answers with its `QEMU` signature.
+///
+/// Returns [`ProbeResult::Qemu`], if it does, as only QEMU implements fw_cfg, and
+/// [`ProbeResult::Inconclusive`] otherwise.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{Detector, FwCfgProbe};
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// let probe = unsafe { FwCfgProbe::new() };
+/// let report = Detector::new(&[&probe]).detect();
+/// if report.certainty().is_very_likely() {
+///     println!("QEMU, use debugcon");
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct FwCfgProbe {
+    fw_cfg: FwCfg<'static>,
+}
+
+impl FwCfgProbe {
+    /// Creates a new probe that accesses fw_cfg via the I/O ports.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access fw_cfg while the probe
+    /// runs. On machines without fw_cfg, the ports `0x510` and `0x511` might belong to
+    /// another device; the probe writes the selector port.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub const unsafe fn new() -> Self {
+        Self {
+            fw_cfg: FwCfg::new(),
+        }
+    }
+
+    /// Creates a new probe that accesses fw_cfg via the MMIO region at `base`, e.g.
+    /// `0x0902_0000` on QEMU's `virt` machine for aarch64.
+    ///
+    /// # Safety
+    /// The first 16 bytes at `base` must be mapped as device memory, and nobody else may
+    /// access fw_cfg while the probe runs. The probe writes to `base + 8`, so `base`
+    /// must belong to fw_cfg or to no device at all.
+    pub const unsafe fn with_mmio(base: *mut u8) -> Self {
+        Self {
+            fw_cfg: FwCfg::with_mmio(base),
+        }
+    }
+
+    /// Creates a new probe for the fw_cfg node of the device tree, such as
+    /// `/fw-cfg@9020000`. Returns `None`, if the device tree has no such node, e.g.
+    /// because the machine isn't QEMU.
+    ///
+    /// # Safety
+    /// The region from the device tree must be identity-mapped as device memory. Apart
+    /// from that, see [`Self::with_mmio`].
+    pub unsafe fn from_device_tree(device_tree: &DeviceTree) -> Option<Self> {
+        let fw_cfg = FwCfg::from_device_tree(device_tree)?;
+        Some(Self { fw_cfg })
+    }
+
+    /// Returns the fw_cfg client that the probe uses, to read further data from fw_cfg.
+    pub fn fw_cfg(&self) -> FwCfg<'static> {
+        self.fw_cfg
+    }
+
+    /// Reads the kernel command line that QEMU passes via `-append` into `buf`. See
+    /// [`FwCfg::cmdline`].
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust,no_run
+    /// use runs_inside_qemu::{CmdlineProbe, Detector, FwCfgProbe};
+    ///
+    /// // SAFETY: runs in ring 0 before other CPUs are started
+    /// let fw_cfg = unsafe { FwCfgProbe::new() };
+    /// let mut buf = [0; 4096];
+    /// if let Some(cmdline) = fw_cfg.cmdline(&mut buf) {
+    ///     let probe = CmdlineProbe::new(cmdline);
+    ///     let report = Detector::new(&[&fw_cfg, &probe]).detect();
+    ///     println!("{}: {:?}", cmdline, report.certainty());
+    /// }
+    /// ```
+    pub fn cmdline<'b>(&self, buf: &'b mut [u8]) -> Option<&'b str> {
+        self.fw_cfg.cmdline(buf)
+    }
+
+    /// Reads the guest RAM map from the fw_cfg file `etc/e820` into `buf` and returns its
+    /// entries. See [`FwCfg::e820`].
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust,no_run
+    /// use runs_inside_qemu::FwCfgProbe;
+    ///
+    /// // SAFETY: runs in ring 0 before other CPUs are started
+    /// let fw_cfg = unsafe { FwCfgProbe::new() };
+    /// let mut buf = [0; 512];
+    /// if let Some(entries) = fw_cfg.e820(&mut buf) {
+    ///     println!("{} bytes RAM", entries.clone().ram_size());
+    ///     for entry in entries {
+    ///         println!("{:#x}+{:#x}: {:?}", entry.address, entry.len, entry.kind);
+    ///     }
+    /// }
+    /// ```
+    pub fn e820<'b>(&self, buf: &'b mut [u8]) -> Option<E820Entries<'b>> {
+        self.fw_cfg.e820(buf)
+    }
+}
+
+impl Probe for FwCfgProbe {
+    fn name(&self) -> &'static str {
+        "fw-cfg"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        if self.fw_cfg.is_present() {
+            debug!("fw_cfg answers with the QEMU signature.");
+            ProbeResult::Qemu
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+}
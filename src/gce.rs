@@ -0,0 +1,72 @@
+//! Google Compute Engine backend. GCE runs on a KVM-based hypervisor that reports the
+//! KVM signature, and sets the DMI system vendor and BIOS vendor `Google` and the product
+//! name `Google Compute Engine`.
+
+use crate::{detect_hypervisor, vmm_from_dmi, Probe, ProbeResult, VmmKind};
+
+/// DMI BIOS vendor of GCE virtual machines.
+const GCE_BIOS_VENDOR: &str = "Google";
+
+/// Identifies GCE by the DMI system vendor, product name, and BIOS vendor, as found in
+/// `/sys/class/dmi/id/` on Linux, and by the KVM signature. Returns
+/// [`VmmKind::GoogleComputeEngine`] inside GCE and `None` otherwise. On architectures
+/// without CPUID, only the DMI strings are checked.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{gce_vmm, VmmKind};
+///
+/// // e.g. read from /sys/class/dmi/id/ on Linux
+/// match gce_vmm("Google", "Google Compute Engine", "Google") {
+///     Some(VmmKind::GoogleComputeEngine) => println!("GCE"),
+///     _ => println!("not GCE"),
+/// }
+/// ```
+pub fn gce_vmm(sys_vendor: &str, product_name: &str, bios_vendor: &str) -> Option<VmmKind> {
+    let kvm_present = detect_hypervisor() == Some(VmmKind::KvmOther)
+        || cfg!(not(any(target_arch = "x86", target_arch = "x86_64")));
+    (kvm_present
+        && bios_vendor.trim() == GCE_BIOS_VENDOR
+        && vmm_from_dmi(sys_vendor, product_name) == Some(VmmKind::GoogleComputeEngine))
+    .then_some(VmmKind::GoogleComputeEngine)
+}
+
+/// Probe that checks if the code runs in a GCE virtual machine (see [`gce_vmm`]).
+///
+/// Returns [`ProbeResult::NotQemu`] inside GCE and [`ProbeResult::Inconclusive`]
+/// otherwise.
+#[derive(Copy, Clone, Debug)]
+pub struct GceProbe<'a> {
+    sys_vendor: &'a str,
+    product_name: &'a str,
+    bios_vendor: &'a str,
+}
+
+impl<'a> GceProbe<'a> {
+    /// Creates a new probe that inspects the given DMI system vendor, product name, and
+    /// BIOS vendor.
+    pub const fn new(sys_vendor: &'a str, product_name: &'a str, bios_vendor: &'a str) -> Self {
+        Self {
+            sys_vendor,
+            product_name,
+            bios_vendor,
+        }
+    }
+}
+
+impl Probe for GceProbe<'_> {
+    fn name(&self) -> &'static str {
+        "gce"
+    }
+
+    fn run(&self) -> ProbeResult {
+        match gce_vmm(self.sys_vendor, self.product_name, self.bios_vendor) {
+            Some(VmmKind::GoogleComputeEngine) => {
+                debug!("Runs in a GCE virtual machine.");
+                ProbeResult::NotQemu
+            }
+            _ => ProbeResult::Inconclusive,
+        }
+    }
+}
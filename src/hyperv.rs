@@ -0,0 +1,240 @@
+//! Hyper-V backend. Reads the Hyper-V CPUID leaves `0x4000_0001..=0x4000_0005`, which
+//! are provided by Hyper-V itself but also by KVM (and thus QEMU) with Hyper-V
+//! enlightenments (`-cpu ...,hv-relaxed,hv-vapic,...`).
+
+use crate::cpuid::{self, HYPERVISOR_INFO_LEAF};
+use crate::{msr, Probe, ProbeResult};
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Interface signature `Hv#1` in `eax` of leaf `0x4000_0001`.
+pub(crate) const HYPERV_INTERFACE_SIGNATURE: u32 = 0x3123_7648;
+/// Partition privilege `AccessHypercallMsrs`: the guest OS ID and hypercall MSRs exist.
+const PRIVILEGE_HYPERCALL_MSRS: u64 = 1 << 5;
+/// `HV_X64_MSR_GUEST_OS_ID`.
+const MSR_GUEST_OS_ID: u32 = 0x4000_0000;
+/// `HV_X64_MSR_HYPERCALL`.
+const MSR_HYPERCALL: u32 = 0x4000_0001;
+/// Enable bit of the hypercall MSR. Bits 12 to 63 hold the GPA of the hypercall page.
+const HYPERCALL_ENABLE: u64 = 1 << 0;
+/// Guest OS ID that the probe writes temporarily: an open source OS (bit 63) with an
+/// otherwise arbitrary ID.
+const PROBE_GUEST_OS_ID: u64 = 1 << 63 | 0x5145_4d55;
+/// Hypercall page GPA that the probe writes temporarily, without the enable bit.
+const PROBE_HYPERCALL_GPA: u64 = 0x1000;
+
+/// Information from the Hyper-V CPUID leaves. Result of [`hyperv_info`].
+///
+/// ## Example Usage
+///
+/// QEMU/KVM with Hyper-V enlightenments reports the Hyper-V signature in leaf
+/// `0x4000_0000` but is not mistaken for Hyper-V:
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{detect_with, QemuCertainty, VmmKind};
+///
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x1, 0, 0, 0),
+///         0x1 => (0, 0, 1 << 31, 0),
+///         // "Microsoft Hv"
+///         0x4000_0000 => (0x4000_0005, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+///         // "Hv#1"
+///         0x4000_0001 => (0x3123_7648, 0, 0, 0),
+///         // "KVMKVMKVM"
+///         0x4000_0100 => (0x4000_0101, 0x4b4d_564b, 0x564b_4d56, 0x0000_004d),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let report = detect_with(reader);
+/// assert_eq!(report.vmm(), Some(VmmKind::KvmOther));
+/// assert_eq!(report.certainty(), QemuCertainty::Maybe);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct HyperVInfo {
+    /// Build number of the hypervisor (leaf `0x4000_0002`, `eax`).
+    pub build_number: u32,
+    /// Major version of the hypervisor (leaf `0x4000_0002`, `ebx[31:16]`).
+    pub major_version: u16,
+    /// Minor version of the hypervisor (leaf `0x4000_0002`, `ebx[15:0]`).
+    pub minor_version: u16,
+    /// Partition privilege mask (leaf `0x4000_0003`, `ebx:eax`).
+    pub partition_privileges: u64,
+    /// Implementation recommendations (leaf `0x4000_0004`, `eax`).
+    pub recommendations: u32,
+    /// Maximum number of virtual processors (leaf `0x4000_0005`, `eax`).
+    pub max_virtual_processors: u32,
+    /// Maximum number of logical processors (leaf `0x4000_0005`, `ebx`).
+    pub max_logical_processors: u32,
+    /// Whether KVM emulates the Hyper-V interface, i.e. KVM announces itself in leaf
+    /// `0x4000_0100`. If `true`, this is not Hyper-V but most likely QEMU/KVM with
+    /// Hyper-V enlightenments.
+    pub emulated_by_kvm: bool,
+}
+
+/// Reads the Hyper-V CPUID leaves. Returns `None`, if the hypervisor doesn't implement
+/// the Hyper-V interface (`Hv#1`) or CPUID is not available on this architecture.
+/// Leaves beyond the highest leaf that the hypervisor reports read as zero.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::hyperv_info;
+///
+/// if let Some(info) = hyperv_info() {
+///     if info.emulated_by_kvm {
+///         println!("KVM with Hyper-V enlightenments");
+///     } else {
+///         println!("Hyper-V {}.{}", info.major_version, info.minor_version);
+///     }
+/// }
+/// ```
+pub fn hyperv_info() -> Option<HyperVInfo> {
+    hyperv_info_with(&CpuIdReaderNative)
+}
+
+/// Like [`hyperv_info`] but reads CPUID through the given reader.
+pub(crate) fn hyperv_info_with<R: CpuIdReader>(reader: &R) -> Option<HyperVInfo> {
+    cpuid::hypervisor_signature_with(reader)?;
+    let max_leaf = reader.cpuid1(HYPERVISOR_INFO_LEAF).eax;
+    let leaf = |leaf| {
+        if leaf <= max_leaf {
+            reader.cpuid1(leaf)
+        } else {
+            raw_cpuid::CpuIdResult {
+                eax: 0,
+                ebx: 0,
+                ecx: 0,
+                edx: 0,
+            }
+        }
+    };
+    if leaf(0x4000_0001).eax != HYPERV_INTERFACE_SIGNATURE {
+        return None;
+    }
+    let version = leaf(0x4000_0002);
+    let features = leaf(0x4000_0003);
+    let limits = leaf(0x4000_0005);
+    Some(HyperVInfo {
+        build_number: version.eax,
+        major_version: (version.ebx >> 16) as u16,
+        minor_version: version.ebx as u16,
+        partition_privileges: u64::from(features.ebx) << 32 | u64::from(features.eax),
+        recommendations: leaf(0x4000_0004).eax,
+        max_virtual_processors: limits.eax,
+        max_logical_processors: limits.ebx,
+        emulated_by_kvm: cpuid::kvm_emulates_hyperv_with(reader),
+    })
+}
+
+/// Probe that exercises the synthetic Hyper-V MSRs, the guest OS ID and the hypercall
+/// MSR, to tell a functional Hyper-V interface apart from a mere vendor signature.
+///
+/// The MSRs are only accessed, if the Hyper-V interface (`Hv#1`) announces them via
+/// the `AccessHypercallMsrs` partition privilege. Returns [`ProbeResult::Inconclusive`]
+/// otherwise, e.g. if only the vendor signature is reported (`hv-vendor-id` without
+/// enlightenments). If the OS didn't set a guest OS ID yet, the probe writes one, then
+/// writes a hypercall page GPA without the enable bit, and checks that both values
+/// read back. Afterwards, both MSRs are restored. The enable bit is never set, because
+/// the hypervisor would then overwrite the guest page at the GPA. If the OS already set
+/// a guest OS ID, the MSRs are left untouched and only read.
+///
+/// If the values don't stick, the interface is not functional and the result is
+/// [`ProbeResult::Inconclusive`]. Otherwise, the result depends on whether KVM
+/// announces itself behind the interface (see [`HyperVInfo::emulated_by_kvm`]):
+/// [`ProbeResult::Hint`] for KVM, and [`ProbeResult::NotQemu`] for genuine Hyper-V.
+/// KVM and Hyper-V implement the MSRs alike, so this part of the verdict is a CPUID
+/// check; QEMU started with `kvm=off` hides the KVM leaves and is reported as Hyper-V.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{Detector, HyperVMsrProbe};
+///
+/// // SAFETY: runs in ring 0
+/// let probe = unsafe { HyperVMsrProbe::new() };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct HyperVMsrProbe {
+    _private: (),
+}
+
+impl HyperVMsrProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// The caller must run in ring 0. Otherwise, `rdmsr` and `wrmsr` raise a general
+    /// protection fault. The MSRs are only accessed, if the hypervisor announces them.
+    /// The probe must not run concurrently with code that sets up the Hyper-V interface,
+    /// e.g. a Hyper-V aware kernel, because it writes the MSRs temporarily.
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for HyperVMsrProbe {
+    fn name(&self) -> &'static str {
+        "hyperv-msr"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let Some(info) = hyperv_info() else {
+            return ProbeResult::Inconclusive;
+        };
+        if info.partition_privileges & PRIVILEGE_HYPERCALL_MSRS == 0 {
+            debug!("Hyper-V interface without hypercall MSRs.");
+            return ProbeResult::Inconclusive;
+        }
+        // SAFETY: guaranteed by the caller of `HyperVMsrProbe::new`; the MSRs exist
+        if !unsafe { hyperv_msrs_functional() } {
+            debug!("Hyper-V MSRs don't keep written values.");
+            return ProbeResult::Inconclusive;
+        }
+        if info.emulated_by_kvm {
+            debug!("Functional Hyper-V interface of KVM.");
+            ProbeResult::Hint
+        } else {
+            debug!("Functional Hyper-V interface without KVM.");
+            ProbeResult::NotQemu
+        }
+    }
+}
+
+/// Checks that the guest OS ID and the hypercall MSR keep written values, and restores
+/// them afterwards. See [`HyperVMsrProbe`].
+///
+/// # Safety
+/// See [`HyperVMsrProbe::new`].
+unsafe fn hyperv_msrs_functional() -> bool {
+    let guest_os_id = msr::rdmsr(MSR_GUEST_OS_ID);
+    let hypercall = msr::rdmsr(MSR_HYPERCALL);
+    debug!(
+        "Hyper-V guest OS ID: {:?}, hypercall MSR: {:?}.",
+        guest_os_id, hypercall
+    );
+    if guest_os_id != 0 {
+        // in use by the OS: a kept guest OS ID is all the evidence there is
+        return true;
+    }
+    msr::wrmsr(MSR_GUEST_OS_ID, PROBE_GUEST_OS_ID);
+    let os_id_sticks = msr::rdmsr(MSR_GUEST_OS_ID) == PROBE_GUEST_OS_ID;
+    let mut gpa_sticks = false;
+    if os_id_sticks {
+        msr::wrmsr(MSR_HYPERCALL, PROBE_HYPERCALL_GPA);
+        let read = msr::rdmsr(MSR_HYPERCALL);
+        gpa_sticks = read & !HYPERCALL_ENABLE == PROBE_HYPERCALL_GPA;
+        msr::wrmsr(MSR_HYPERCALL, hypercall);
+    }
+    msr::wrmsr(MSR_GUEST_OS_ID, guest_os_id);
+    debug!(
+        "Hyper-V guest OS ID sticks: {:?}, hypercall GPA sticks: {:?}.",
+        os_id_sticks, gpa_sticks
+    );
+    os_id_sticks && gpa_sticks
+}
@@ -0,0 +1,359 @@
+//! Module for [`detect_hypervisor`], which identifies the virtual machine monitor (VMM)
+//! instead of only answering the QEMU yes/no question.
+
+// Without CPUID, there are no signatures to identify.
+#![cfg_attr(
+    not(any(target_arch = "x86", target_arch = "x86_64")),
+    allow(dead_code)
+)]
+
+use crate::cpuid;
+use crate::report::HYPERVISOR_SIGNATURE_LEN;
+
+/// Empty hypervisor signature, e.g. of QEMU with the HVF accelerator on macOS.
+pub(crate) const SIGNATURE_NONE: &[u8; HYPERVISOR_SIGNATURE_LEN] = &[0; HYPERVISOR_SIGNATURE_LEN];
+/// Hypervisor signature of QEMU without an accelerator (TCG).
+pub(crate) const SIGNATURE_TCG: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"TCGTCGTCGTCG";
+/// Hypervisor signature of KVM.
+pub(crate) const SIGNATURE_KVM: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"KVMKVMKVM\0\0\0";
+/// Hypervisor signature of Microsoft Hyper-V.
+pub(crate) const SIGNATURE_HYPERV: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"Microsoft Hv";
+/// Hypervisor signature of VMware products.
+pub(crate) const SIGNATURE_VMWARE: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"VMwareVMware";
+/// Hypervisor signature of Xen.
+pub(crate) const SIGNATURE_XEN: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"XenVMMXenVMM";
+/// Hypervisor signature of Oracle VirtualBox.
+const SIGNATURE_VIRTUALBOX: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"VBoxVBoxVBox";
+/// Hypervisor signature of Parallels Desktop.
+const SIGNATURE_PARALLELS: &[u8; HYPERVISOR_SIGNATURE_LEN] = b" prl hyperv ";
+/// Byte-swapped hypervisor signature of older Parallels Desktop versions.
+const SIGNATURE_PARALLELS_ALT: &[u8; HYPERVISOR_SIGNATURE_LEN] = b" lrpepyh  vr";
+/// Hypervisor signature of FreeBSD's bhyve.
+const SIGNATURE_BHYVE: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"bhyve bhyve ";
+/// Hypervisor signature of the ACRN hypervisor.
+const SIGNATURE_ACRN: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"ACRNACRNACRN";
+/// Hypervisor signature of NetBSD's NVMM.
+const SIGNATURE_NVMM: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"___ NVMM ___";
+/// Hypervisor signature of the Intel Hardware Accelerated Execution Manager (HAXM).
+const SIGNATURE_HAXM: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"HAXMHAXMHAXM";
+/// Hypervisor signature of OpenBSD's vmm.
+const SIGNATURE_OPENBSD_VMM: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"OpenBSDVMM58";
+/// Hypervisor signature of the QNX Hypervisor.
+const SIGNATURE_QNX: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"QNXQVMBSQG\0\0";
+/// Hypervisor signature of the Jailhouse partitioning hypervisor.
+const SIGNATURE_JAILHOUSE: &[u8; HYPERVISOR_SIGNATURE_LEN] = b"Jailhouse\0\0\0";
+
+/// The virtual machine monitor (VMM) that the code runs in. Result of [`detect_hypervisor`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum VmmKind {
+    /// QEMU, either without accelerator (TCG) or with an accelerator and a QEMU CPU model.
+    /// See [`crate::DetectionReport::accelerator`].
+    Qemu,
+    /// KVM with a VMM that can't be identified as QEMU. This is the case for QEMU with
+    /// "-cpu host" but also for other KVM-based VMMs. Use [`crate::KvmEvidence`] to
+    /// identify the VMM.
+    KvmOther,
+    /// Microsoft Hyper-V. KVM (and thus QEMU) can emulate the Hyper-V interface
+    /// ("enlightenments"), in that case the VMM is reported as [`Self::Qemu`] or
+    /// [`Self::KvmOther`]. QEMU with WHPX also reports the Hyper-V signature, but a QEMU
+    /// CPU model, and is reported as [`Self::Qemu`]. See [`crate::hyperv_info`].
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+    /// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+    /// use runs_inside_qemu::{detect_with, QemuCertainty, VmmKind};
+    ///
+    /// // QEMU with WHPX: the Hyper-V interface and QEMU's CPU model
+    /// let reader = |eax, _ecx| {
+    ///     let (eax, ebx, ecx, edx) = match eax {
+    ///         0x0 => (0x1, 0, 0, 0),
+    ///         0x1 => (0, 0, 1 << 31, 0),
+    ///         // "Microsoft Hv"
+    ///         0x4000_0000 => (0x4000_0006, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+    ///         0x8000_0000 => (0x8000_0004, 0, 0, 0),
+    ///         // "QEMU Virtual CPU version 2.5+"
+    ///         0x8000_0002 => (0x554d_4551, 0x7269_5620, 0x6c61_7574, 0x5550_4320),
+    ///         0x8000_0003 => (0x7265_7620, 0x6e6f_6973, 0x352e_3220, 0x0000_002b),
+    ///         _ => (0, 0, 0, 0),
+    ///     };
+    ///     CpuIdResult { eax, ebx, ecx, edx }
+    /// };
+    /// let report = detect_with(reader);
+    /// assert_eq!(report.vmm(), Some(VmmKind::Qemu));
+    /// assert_eq!(report.certainty(), QemuCertainty::VeryLikely);
+    /// # }
+    /// ```
+    HyperV,
+    /// VMware Workstation or ESXi.
+    Vmware,
+    /// Xen, in any guest mode. See [`crate::xen_info`] for details.
+    Xen,
+    /// Oracle VirtualBox. Also see [`crate::VirtualBoxProbe`] and [`crate::vmm_from_dmi`].
+    VirtualBox,
+    /// Parallels Desktop.
+    Parallels,
+    /// FreeBSD's bhyve, also its illumos port, e.g. in SmartOS or OmniOS.
+    Bhyve,
+    /// The ACRN hypervisor for embedded and automotive systems.
+    Acrn,
+    /// cloud-hypervisor. It reports the KVM signature, so [`detect_hypervisor`] reports
+    /// it as [`Self::KvmOther`]. Identified by [`crate::vmm_from_dmi`] and
+    /// [`crate::KvmEvidence::classify`], see also [`crate::KvmVmmProbe::for_vmm`].
+    CloudHypervisor,
+    /// Firecracker. It reports the KVM signature, so [`detect_hypervisor`] reports it as
+    /// [`Self::KvmOther`]. Identified by [`crate::KvmEvidence::classify`], see also
+    /// [`crate::KvmVmmProbe::for_vmm`].
+    Firecracker,
+    /// kvmtool (lkvm). It reports the KVM signature, so [`detect_hypervisor`] reports it
+    /// as [`Self::KvmOther`]. Identified by [`crate::KvmEvidence::classify`].
+    Kvmtool,
+    /// Google's crosvm, e.g. for ChromeOS VMs. It reports the KVM signature, so
+    /// [`detect_hypervisor`] reports it as [`Self::KvmOther`]. Identified by
+    /// [`crate::vmm_from_dmi`] and, via its PCI layout, [`crate::KvmEvidence`].
+    Crosvm,
+    /// Apple's Virtualization.framework on macOS. Identified by [`crate::vmm_from_dmi`].
+    /// QEMU on macOS uses the Hypervisor.framework (HVF) instead and is reported as
+    /// [`Self::Qemu`].
+    AppleVirtualization,
+    /// AWS Nitro, the hypervisor of current EC2 instance types. It reports the KVM
+    /// signature. Identified by [`crate::vmm_from_dmi`] and [`crate::aws_vmm`].
+    AwsNitro,
+    /// The Xen-based hypervisor of older EC2 instance types, which uses QEMU as device
+    /// model. [`detect_hypervisor`] reports it as [`Self::Xen`]. Identified by
+    /// [`crate::aws_vmm`].
+    AwsXen,
+    /// The hypervisor of Google Compute Engine. It reports the KVM signature. Identified
+    /// by [`crate::vmm_from_dmi`] and [`crate::gce_vmm`].
+    GoogleComputeEngine,
+    /// Microsoft Azure. It reports the Hyper-V signature, so [`detect_hypervisor`]
+    /// reports it as [`Self::HyperV`]. Identified by [`crate::azure_vmm`].
+    Azure,
+    /// NetBSD's NVMM with a VMM that can't be identified as QEMU. NVMM is mostly used as
+    /// accelerator of QEMU; with a QEMU CPU model, the VMM is reported as [`Self::Qemu`].
+    Nvmm,
+    /// Intel HAXM with a VMM that can't be identified as QEMU. HAXM is mostly used as
+    /// accelerator of QEMU; with a QEMU CPU model, the VMM is reported as [`Self::Qemu`].
+    Haxm,
+    /// OpenBSD's vmm.
+    OpenBsdVmm,
+    /// The QNX Hypervisor. Its signature `QNXQVMBSQG` is padded with null bytes.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+    /// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+    /// use runs_inside_qemu::{detect_with, QemuCertainty, VmmKind};
+    ///
+    /// let reader = |eax, _ecx| {
+    ///     let (eax, ebx, ecx, edx) = match eax {
+    ///         0x0 => (0x1, 0, 0, 0),
+    ///         0x1 => (0, 0, 1 << 31, 0),
+    ///         // "QNXQVMBSQG\0\0"
+    ///         0x4000_0000 => (0x4000_0001, 0x5158_4e51, 0x5342_4d56, 0x0000_4751),
+    ///         _ => (0, 0, 0, 0),
+    ///     };
+    ///     CpuIdResult { eax, ebx, ecx, edx }
+    /// };
+    /// let report = detect_with(reader);
+    /// assert_eq!(report.vmm(), Some(VmmKind::Qnx));
+    /// assert_eq!(report.certainty(), QemuCertainty::DefinitelyNot);
+    /// # }
+    /// ```
+    Qnx,
+    /// The Jailhouse partitioning hypervisor, as seen from a non-root cell.
+    Jailhouse,
+    /// A hypervisor that this crate doesn't know. Contains the raw vendor signature
+    /// from CPUID leaf `0x4000_0000`.
+    Unknown([u8; HYPERVISOR_SIGNATURE_LEN]),
+}
+
+impl VmmKind {
+    /// Identifies the VMM by its hypervisor vendor signature. KVM, Hyper-V (WHPX), NVMM,
+    /// and HAXM are only reported as QEMU, if the CPU brand string mentions QEMU.
+    pub(crate) fn identify(
+        signature: &[u8; HYPERVISOR_SIGNATURE_LEN],
+        brand_string_mentions_qemu: bool,
+    ) -> Self {
+        match signature {
+            SIGNATURE_TCG => Self::Qemu,
+            SIGNATURE_KVM if brand_string_mentions_qemu => Self::Qemu,
+            SIGNATURE_KVM => Self::KvmOther,
+            SIGNATURE_NONE if brand_string_mentions_qemu => Self::Qemu,
+            SIGNATURE_HYPERV if brand_string_mentions_qemu => Self::Qemu,
+            SIGNATURE_HYPERV => Self::HyperV,
+            SIGNATURE_VMWARE => Self::Vmware,
+            SIGNATURE_XEN => Self::Xen,
+            SIGNATURE_VIRTUALBOX => Self::VirtualBox,
+            SIGNATURE_PARALLELS | SIGNATURE_PARALLELS_ALT => Self::Parallels,
+            SIGNATURE_BHYVE => Self::Bhyve,
+            SIGNATURE_ACRN => Self::Acrn,
+            SIGNATURE_NVMM | SIGNATURE_HAXM if brand_string_mentions_qemu => Self::Qemu,
+            SIGNATURE_NVMM => Self::Nvmm,
+            SIGNATURE_HAXM => Self::Haxm,
+            SIGNATURE_OPENBSD_VMM => Self::OpenBsdVmm,
+            SIGNATURE_QNX => Self::Qnx,
+            SIGNATURE_JAILHOUSE => Self::Jailhouse,
+            _ => Self::Unknown(*signature),
+        }
+    }
+
+    /// Returns if the VMM is known to use KVM, i.e. reports the KVM signature in CPUID.
+    pub(crate) const fn kvm_based(&self) -> bool {
+        matches!(
+            self,
+            Self::Qemu
+                | Self::KvmOther
+                | Self::CloudHypervisor
+                | Self::Firecracker
+                | Self::Kvmtool
+                | Self::Crosvm
+                | Self::AwsNitro
+                | Self::GoogleComputeEngine
+        )
+    }
+
+    /// Returns if the VMM can't be QEMU, no matter what other checks say.
+    pub(crate) const fn rules_out_qemu(&self) -> bool {
+        matches!(
+            self,
+            Self::Vmware
+                | Self::HyperV
+                | Self::VirtualBox
+                | Self::Parallels
+                | Self::Bhyve
+                | Self::Acrn
+                | Self::OpenBsdVmm
+                | Self::Qnx
+                | Self::Jailhouse
+                | Self::CloudHypervisor
+                | Self::Firecracker
+                | Self::Kvmtool
+                | Self::Crosvm
+                | Self::AppleVirtualization
+                | Self::AwsNitro
+                | Self::GoogleComputeEngine
+                | Self::Azure
+        )
+    }
+}
+
+/// The accelerator that QEMU uses. See [`fn@crate::accelerator`] and
+/// [`crate::DetectionReport::accelerator`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Accelerator {
+    /// No accelerator, the Tiny Code Generator emulates the CPU.
+    Tcg,
+    /// KVM on Linux hosts, also with Hyper-V enlightenments.
+    Kvm,
+    /// The Hypervisor.framework on macOS hosts, which exposes no hypervisor signature.
+    Hvf,
+    /// NetBSD's NVMM.
+    Nvmm,
+    /// Intel HAXM.
+    Haxm,
+    /// The Windows Hypervisor Platform (WHPX) on Windows hosts, which exposes the
+    /// Hyper-V interface.
+    Whpx,
+    /// Xen, with QEMU as device model of an HVM guest.
+    Xen,
+}
+
+impl Accelerator {
+    /// Identifies the accelerator by the raw hypervisor signature, if the VMM is QEMU.
+    pub(crate) fn identify(
+        signature: &[u8; HYPERVISOR_SIGNATURE_LEN],
+        vmm: VmmKind,
+    ) -> Option<Self> {
+        if vmm != VmmKind::Qemu {
+            return None;
+        }
+        match signature {
+            SIGNATURE_TCG => Some(Self::Tcg),
+            SIGNATURE_KVM => Some(Self::Kvm),
+            SIGNATURE_NONE => Some(Self::Hvf),
+            SIGNATURE_NVMM => Some(Self::Nvmm),
+            SIGNATURE_HAXM => Some(Self::Haxm),
+            _ => None,
+        }
+    }
+}
+
+/// Version information of a hypervisor interface, from the leaves that follow its base
+/// leaf. See [`crate::DetectionReport::interface_version`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum InterfaceVersion {
+    /// The Hyper-V interface (`Hv#1` in leaf base + 1), which KVM with Hyper-V
+    /// enlightenments exposes as well.
+    HyperV {
+        /// Major version (leaf base + 2, `ebx[31:16]`).
+        major: u16,
+        /// Minor version (leaf base + 2, `ebx[15:0]`).
+        minor: u16,
+        /// Build number (leaf base + 2, `eax`).
+        build: u32,
+    },
+    /// The KVM interface. KVM has no version number, new capabilities are announced as
+    /// feature bits instead. See [`crate::KvmFeatures`].
+    Kvm {
+        /// Raw feature bits (leaf base + 1, `eax`).
+        features: u32,
+        /// Raw hint bits (leaf base + 1, `edx`).
+        hints: u32,
+    },
+    /// The Xen interface.
+    Xen {
+        /// Major version (leaf base + 1, `eax[31:16]`).
+        major: u16,
+        /// Minor version (leaf base + 1, `eax[15:0]`).
+        minor: u16,
+    },
+}
+
+/// Identifies the virtual machine monitor (VMM) that the code runs in, using the
+/// hypervisor info leaves and the CPU brand string. Returns `None`, if no hypervisor
+/// is present or CPUID is not available on this architecture.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{detect_hypervisor, VmmKind};
+///
+/// match detect_hypervisor() {
+///     None => println!("bare metal"),
+///     Some(VmmKind::Qemu) => println!("QEMU"),
+///     Some(other) => println!("other hypervisor: {:?}", other),
+/// }
+/// ```
+pub fn detect_hypervisor() -> Option<VmmKind> {
+    cpuid::vmm()
+}
+
+/// Returns the raw 12 byte vendor signature from the hypervisor info leaf `0x4000_0000`,
+/// such as `b"KVMKVMKVM\0\0\0"`. Returns `None`, if no hypervisor is present or CPUID
+/// is not available on this architecture. Useful to
+/// match against signatures of hypervisors that [`VmmKind`] doesn't know.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::hypervisor_signature;
+///
+/// if hypervisor_signature() == Some(*b"MyHypervisor") {
+///     println!("runs inside my proprietary hypervisor");
+/// }
+/// ```
+pub fn hypervisor_signature() -> Option<[u8; HYPERVISOR_SIGNATURE_LEN]> {
+    cpuid::hypervisor_signature()
+}
@@ -0,0 +1,81 @@
+//! Module for [`VmIdentity`], which tells a guest which virtual machine it is, and
+//! [`Uuid`]. QEMU sets both from the command line: `-uuid` or
+//! `-smbios type=1,uuid=...` for the UUID and `-smbios type=1,serial=...` for the serial
+//! number. Orchestration software uses them to recognize its VMs.
+
+use core::fmt;
+
+/// A UUID in the byte order of RFC 4122, e.g. the system UUID of SMBIOS type 1.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::Uuid;
+///
+/// let uuid = Uuid::from_bytes([
+///     0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc,
+///     0xde, 0xf0,
+/// ]);
+/// assert_eq!(uuid.to_string(), "12345678-9abc-def0-1234-56789abcdef0");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Uuid([u8; 16]);
+
+impl Uuid {
+    /// Creates a UUID from its bytes in the byte order of RFC 4122.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// Creates a UUID from the GUID encoding of Microsoft, where the first three fields
+    /// are little-endian. SMBIOS 2.6 and newer and the vmgenid device use it.
+    pub(crate) const fn from_guid_bytes(bytes: [u8; 16]) -> Self {
+        let [a0, a1, a2, a3, b0, b1, c0, c1, rest @ ..] = bytes;
+        let [d0, d1, d2, d3, d4, d5, d6, d7] = rest;
+        Self([
+            a3, a2, a1, a0, b1, b0, c1, c0, d0, d1, d2, d3, d4, d5, d6, d7,
+        ])
+    }
+
+    /// Returns the bytes in the byte order of RFC 4122.
+    pub const fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if matches!(index, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// The identity of the virtual machine, as set by the user or the management software.
+/// See [`crate::Probe::identity`] and [`crate::DetectionReport::identity`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct VmIdentity<'a> {
+    /// The system UUID, e.g. from `-uuid`.
+    pub uuid: Option<Uuid>,
+    /// The system serial number, e.g. from `-smbios type=1,serial=...`.
+    pub serial_number: Option<&'a str>,
+}
+
+impl<'a> VmIdentity<'a> {
+    /// Creates a new identity.
+    pub const fn new(uuid: Option<Uuid>, serial_number: Option<&'a str>) -> Self {
+        Self {
+            uuid,
+            serial_number,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Module for [`hypervisor_interfaces`], which walks the whole range of hypervisor info
+//! leaves. Hypervisors may expose several interfaces at once, e.g. KVM and Xen emulate
+//! the Hyper-V interface at `0x4000_0000` and announce themselves at `0x4000_0100`, and
+//! nested setups stack further interfaces on top. Looking at `0x4000_0000` alone only
+//! finds the outermost one.
+
+use crate::cpuid::{self, HYPERVISOR_INFO_LEAF};
+use crate::hypervisor::SIGNATURE_NONE;
+use crate::report::HYPERVISOR_SIGNATURE_LEN;
+use crate::{InterfaceVersion, VmmKind};
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Distance between the base leaves of two interfaces.
+const INTERFACE_STRIDE: u32 = 0x100;
+/// Number of base leaves in `0x4000_0000..=0x4000_ff00`.
+const INTERFACE_COUNT: u32 = 0x100;
+
+/// A hypervisor interface, as announced by a base leaf `0x4000_xx00`. Result of
+/// [`hypervisor_interfaces`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct HypervisorInterface {
+    /// The base leaf, e.g. `0x4000_0100`.
+    pub base_leaf: u32,
+    /// The highest leaf of the interface (`eax` of the base leaf).
+    pub max_leaf: u32,
+    /// The raw 12 byte vendor signature, such as `b"KVMKVMKVM\0\0\0"`.
+    pub signature: [u8; HYPERVISOR_SIGNATURE_LEN],
+    /// The VMM that the signature belongs to.
+    pub vmm: VmmKind,
+    /// The version information of the interface, if it is the one of KVM, Xen, or
+    /// Hyper-V.
+    pub version: Option<InterfaceVersion>,
+}
+
+/// Walks the hypervisor info leaves `0x4000_0000` to `0x4000_ff00` in steps of `0x100`
+/// and returns all interfaces that announce themselves, starting with the outermost one.
+/// A base leaf counts as interface, if its highest leaf is within its range and its
+/// signature isn't empty. The leaves are also walked without the hypervisor flag, to
+/// find hypervisors that hide it.
+///
+/// All 256 base leaves are read, which costs a VM exit each in a virtual machine.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::hypervisor_interfaces;
+///
+/// for interface in hypervisor_interfaces() {
+///     println!("{:#x}: {:?}", interface.base_leaf, interface.vmm);
+/// }
+/// ```
+pub fn hypervisor_interfaces() -> impl Iterator<Item = HypervisorInterface> {
+    hypervisor_interfaces_with(CpuIdReaderNative)
+}
+
+/// Like [`hypervisor_interfaces`] but reads CPUID through the given [`CpuIdReader`].
+///
+/// ## Example Usage
+///
+/// QEMU with KVM and Hyper-V enlightenments, e.g. `-cpu host,hv-relaxed`:
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{hypervisor_interfaces_with, VmmKind};
+///
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         // "Microsoft Hv"
+///         0x4000_0000 => (0x4000_000b, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+///         // "KVMKVMKVM"
+///         0x4000_0100 => (0x4000_0101, 0x4b4d_564b, 0x564b_4d56, 0x0000_004d),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let vmms = hypervisor_interfaces_with(reader)
+///     .map(|interface| interface.vmm)
+///     .collect::<Vec<_>>();
+/// assert_eq!(vmms, [VmmKind::HyperV, VmmKind::KvmOther]);
+/// ```
+pub fn hypervisor_interfaces_with<R: CpuIdReader>(
+    reader: R,
+) -> impl Iterator<Item = HypervisorInterface> {
+    let brand_string_mentions_qemu = cpuid::brand_string_mentions_qemu_with(&reader);
+    (0..INTERFACE_COUNT)
+        .map(|i| HYPERVISOR_INFO_LEAF + i * INTERFACE_STRIDE)
+        .filter_map(move |base_leaf| {
+            let max_leaf = reader.cpuid1(base_leaf).eax;
+            if !(base_leaf..base_leaf + INTERFACE_STRIDE).contains(&max_leaf) {
+                return None;
+            }
+            let signature = cpuid::read_signature(&reader, base_leaf);
+            if &signature == SIGNATURE_NONE {
+                return None;
+            }
+            let vmm = VmmKind::identify(&signature, brand_string_mentions_qemu);
+            debug!("Hypervisor interface at {}: {:?}", base_leaf, vmm);
+            Some(HypervisorInterface {
+                base_leaf,
+                max_leaf,
+                signature,
+                vmm,
+                version: cpuid::interface_version_with(&reader, base_leaf),
+            })
+        })
+}
@@ -0,0 +1,45 @@
+//! Port I/O primitives for x86/x86_64. Used by the probes that talk to emulated devices.
+//!
+//! All functions require the privilege to access I/O ports, i.e. ring 0 or a sufficient
+//! I/O privilege level. Otherwise, they raise a general protection fault.
+
+// Not every function is used by every combination of probes.
+#![allow(dead_code)]
+
+use core::arch::asm;
+
+/// Reads a byte from `port`.
+pub(crate) unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+/// Reads a word from `port`.
+pub(crate) unsafe fn inw(port: u16) -> u16 {
+    let value: u16;
+    asm!("in ax, dx", out("ax") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+/// Reads a double word from `port`.
+pub(crate) unsafe fn inl(port: u16) -> u32 {
+    let value: u32;
+    asm!("in eax, dx", out("eax") value, in("dx") port, options(nomem, nostack, preserves_flags));
+    value
+}
+
+/// Writes a byte to `port`.
+pub(crate) unsafe fn outb(port: u16, value: u8) {
+    asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+/// Writes a word to `port`.
+pub(crate) unsafe fn outw(port: u16, value: u16) {
+    asm!("out dx, ax", in("dx") port, in("ax") value, options(nomem, nostack, preserves_flags));
+}
+
+/// Writes a double word to `port`.
+pub(crate) unsafe fn outl(port: u16, value: u32) {
+    asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags));
+}
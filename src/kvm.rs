@@ -0,0 +1,400 @@
+//! Classification of KVM-based VMMs. All of them report the KVM signature in CPUID, so
+//! [`crate::detect_hypervisor`] only answers [`VmmKind::KvmOther`] for them, unless the
+//! CPU brand string mentions QEMU. [`KvmEvidence`] collects secondary evidence, namely
+//! DMI strings, fw_cfg, and PCI, to identify the VMM. [`KvmFeatures`] describes the
+//! paravirtual features that KVM announces in CPUID.
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::cpuid::{HYPERVISOR_INFO_LEAF, HYPERV_EMULATOR_INFO_LEAF};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::hypervisor::SIGNATURE_KVM;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::{cpuid, Probe, ProbeResult};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::{fw_cfg, pci};
+use crate::{vmm_from_dmi, VmmKind};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// PCI vendor ID and device ID of the host bridge of cloud-hypervisor.
+const CLOUD_HYPERVISOR_HOST_BRIDGE: (u16, u16) = (0x8086, 0x0d57);
+/// PCI vendor ID and device ID of the i440FX host bridge, which QEMU's `pc` machine and
+/// crosvm emulate.
+const I440FX_HOST_BRIDGE: (u16, u16) = (0x8086, 0x1237);
+/// PCI vendor ID of virtio devices. kvmtool has no host bridge, so its first virtio
+/// device sits at `00:00.0`.
+const VIRTIO_VENDOR_ID: u16 = 0x1af4;
+/// First PCI device ID of modern (non-transitional) virtio devices, which crosvm uses.
+const VIRTIO_MODERN_DEVICE_ID_BASE: u16 = 0x1040;
+
+/// `KVM_FEATURE_CLOCKSOURCE`: kvmclock via the legacy MSRs.
+const KVM_FEATURE_CLOCKSOURCE: u32 = 1 << 0;
+/// `KVM_FEATURE_CLOCKSOURCE2`: kvmclock via the new MSRs.
+pub(crate) const KVM_FEATURE_CLOCKSOURCE2: u32 = 1 << 3;
+/// `KVM_FEATURE_ASYNC_PF`: asynchronous page faults.
+const KVM_FEATURE_ASYNC_PF: u32 = 1 << 4;
+/// `KVM_FEATURE_STEAL_TIME`: steal time accounting.
+const KVM_FEATURE_STEAL_TIME: u32 = 1 << 5;
+/// `KVM_FEATURE_PV_EOI`: paravirtual end of interrupt.
+const KVM_FEATURE_PV_EOI: u32 = 1 << 6;
+/// `KVM_FEATURE_PV_UNHALT`: paravirtual spinlocks.
+const KVM_FEATURE_PV_UNHALT: u32 = 1 << 7;
+/// `KVM_FEATURE_PV_TLB_FLUSH`: paravirtual TLB flushes.
+const KVM_FEATURE_PV_TLB_FLUSH: u32 = 1 << 9;
+/// `KVM_FEATURE_PV_SEND_IPI`: paravirtual IPIs.
+const KVM_FEATURE_PV_SEND_IPI: u32 = 1 << 11;
+/// `KVM_FEATURE_PV_SCHED_YIELD`: paravirtual yield to a preempted vCPU.
+const KVM_FEATURE_PV_SCHED_YIELD: u32 = 1 << 13;
+/// `KVM_FEATURE_ASYNC_PF_INT`: asynchronous page faults delivered as interrupt.
+const KVM_FEATURE_ASYNC_PF_INT: u32 = 1 << 14;
+/// `KVM_FEATURE_CLOCKSOURCE_STABLE_BIT`: the stable bit of kvmclock is reliable.
+const KVM_FEATURE_CLOCKSOURCE_STABLE_BIT: u32 = 1 << 24;
+/// `KVM_HINTS_REALTIME` in `edx`: vCPUs are never preempted and never migrated.
+const KVM_HINTS_REALTIME: u32 = 1 << 0;
+
+/// The paravirtual features and hints that KVM announces in CPUID leaf `0x4000_0001`
+/// (`0x4000_0101` if KVM also emulates the Hyper-V interface). Result of
+/// [`kvm_features`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::KvmFeatures;
+///
+/// // e.g. eax and edx of leaf 0x4000_0001 of QEMU/KVM
+/// let features = KvmFeatures::from_leaf(0x0100_00fb, 0);
+/// assert!(features.kvmclock);
+/// assert!(features.pv_eoi);
+/// assert!(!features.pv_send_ipi);
+/// assert!(!features.realtime_hint);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct KvmFeatures {
+    /// Raw feature bits (`eax`).
+    pub features: u32,
+    /// Raw hint bits (`edx`).
+    pub hints: u32,
+    /// kvmclock is available (`KVM_FEATURE_CLOCKSOURCE` or `KVM_FEATURE_CLOCKSOURCE2`).
+    pub kvmclock: bool,
+    /// The stable bit of kvmclock is reliable (`KVM_FEATURE_CLOCKSOURCE_STABLE_BIT`).
+    pub kvmclock_stable: bool,
+    /// Asynchronous page faults (`KVM_FEATURE_ASYNC_PF` or `KVM_FEATURE_ASYNC_PF_INT`).
+    pub async_pf: bool,
+    /// Steal time accounting (`KVM_FEATURE_STEAL_TIME`).
+    pub steal_time: bool,
+    /// Paravirtual end of interrupt (`KVM_FEATURE_PV_EOI`).
+    pub pv_eoi: bool,
+    /// Paravirtual spinlocks (`KVM_FEATURE_PV_UNHALT`).
+    pub pv_unhalt: bool,
+    /// Paravirtual TLB flushes (`KVM_FEATURE_PV_TLB_FLUSH`).
+    pub pv_tlb_flush: bool,
+    /// Paravirtual IPIs (`KVM_FEATURE_PV_SEND_IPI`).
+    pub pv_send_ipi: bool,
+    /// Paravirtual yield to a preempted vCPU (`KVM_FEATURE_PV_SCHED_YIELD`).
+    pub pv_sched_yield: bool,
+    /// vCPUs are pinned and never preempted (`KVM_HINTS_REALTIME`).
+    pub realtime_hint: bool,
+}
+
+impl KvmFeatures {
+    /// Decodes `eax` and `edx` of the KVM feature leaf.
+    pub const fn from_leaf(eax: u32, edx: u32) -> Self {
+        Self {
+            features: eax,
+            hints: edx,
+            kvmclock: eax & (KVM_FEATURE_CLOCKSOURCE | KVM_FEATURE_CLOCKSOURCE2) != 0,
+            kvmclock_stable: eax & KVM_FEATURE_CLOCKSOURCE_STABLE_BIT != 0,
+            async_pf: eax & (KVM_FEATURE_ASYNC_PF | KVM_FEATURE_ASYNC_PF_INT) != 0,
+            steal_time: eax & KVM_FEATURE_STEAL_TIME != 0,
+            pv_eoi: eax & KVM_FEATURE_PV_EOI != 0,
+            pv_unhalt: eax & KVM_FEATURE_PV_UNHALT != 0,
+            pv_tlb_flush: eax & KVM_FEATURE_PV_TLB_FLUSH != 0,
+            pv_send_ipi: eax & KVM_FEATURE_PV_SEND_IPI != 0,
+            pv_sched_yield: eax & KVM_FEATURE_PV_SCHED_YIELD != 0,
+            realtime_hint: edx & KVM_HINTS_REALTIME != 0,
+        }
+    }
+}
+
+/// Reads the KVM feature leaf. Returns `None`, if KVM doesn't announce itself in leaf
+/// `0x4000_0000` or `0x4000_0100`.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::kvm_features;
+///
+/// if let Some(features) = kvm_features() {
+///     if features.pv_send_ipi {
+///         println!("use paravirtual IPIs");
+///     }
+/// }
+/// ```
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn kvm_features() -> Option<KvmFeatures> {
+    kvm_features_with(&CpuIdReaderNative)
+}
+
+/// Like [`kvm_features`] but reads CPUID through the given reader.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn kvm_features_with<R: CpuIdReader>(reader: &R) -> Option<KvmFeatures> {
+    cpuid::hypervisor_signature_with(reader)?;
+    let base_leaf = [HYPERVISOR_INFO_LEAF, HYPERV_EMULATOR_INFO_LEAF]
+        .into_iter()
+        .find(|&leaf| &cpuid::read_signature(reader, leaf) == SIGNATURE_KVM)?;
+    let leaf = reader.cpuid1(base_leaf + 1);
+    Some(KvmFeatures::from_leaf(leaf.eax, leaf.edx))
+}
+
+/// Secondary evidence to tell KVM-based VMMs apart. Unknown evidence is `None`. See
+/// [`KvmEvidence::classify`].
+///
+/// New kinds of evidence may be added in the future, hence the struct is
+/// `#[non_exhaustive]`. Start from [`KvmEvidence::EMPTY`] and set single fields.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{KvmEvidence, VmmKind};
+///
+/// let mut evidence = KvmEvidence::EMPTY;
+/// // e.g. read from /sys/class/dmi/id/ on Linux
+/// evidence.sys_vendor = Some("Cloud Hypervisor");
+/// evidence.product_name = Some("cloud-hypervisor");
+/// assert_eq!(evidence.classify(), VmmKind::CloudHypervisor);
+///
+/// // no DMI tables, no fw_cfg, no PCI
+/// let mut evidence = KvmEvidence::EMPTY;
+/// evidence.fw_cfg_present = Some(false);
+/// evidence.pci_present = Some(false);
+/// assert_eq!(evidence.classify(), VmmKind::Firecracker);
+///
+/// // no fw_cfg, virtio-blk instead of a host bridge at 00:00.0
+/// evidence.pci_present = Some(true);
+/// evidence.pci_host_bridge = Some((0x1af4, 0x1001));
+/// assert_eq!(evidence.classify(), VmmKind::Kvmtool);
+///
+/// // no fw_cfg, i440FX host bridge, modern virtio-blk at 00:01.0
+/// evidence.pci_host_bridge = Some((0x8086, 0x1237));
+/// evidence.pci_device_1 = Some((0x1af4, 0x1042));
+/// assert_eq!(evidence.classify(), VmmKind::Crosvm);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KvmEvidence<'a> {
+    /// DMI system vendor, e.g. from `/sys/class/dmi/id/sys_vendor`.
+    pub sys_vendor: Option<&'a str>,
+    /// DMI product name, e.g. from `/sys/class/dmi/id/product_name`.
+    pub product_name: Option<&'a str>,
+    /// Whether QEMU's fw_cfg device answers with its signature.
+    pub fw_cfg_present: Option<bool>,
+    /// Whether a PCI configuration mechanism is present.
+    pub pci_present: Option<bool>,
+    /// Vendor ID and device ID of the PCI function `00:00.0`, usually the host bridge.
+    pub pci_host_bridge: Option<(u16, u16)>,
+    /// Vendor ID and device ID of the PCI function `00:01.0`, e.g. the ISA bridge of
+    /// QEMU's `pc` machine or the first virtio device of crosvm.
+    pub pci_device_1: Option<(u16, u16)>,
+}
+
+impl<'a> KvmEvidence<'a> {
+    /// No evidence at all.
+    pub const EMPTY: Self = Self {
+        sys_vendor: None,
+        product_name: None,
+        fw_cfg_present: None,
+        pci_present: None,
+        pci_host_bridge: None,
+        pci_device_1: None,
+    };
+
+    /// Fills [`Self::fw_cfg_present`], [`Self::pci_present`], [`Self::pci_host_bridge`], and
+    /// [`Self::pci_device_1`] by accessing the hardware via I/O ports.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access fw_cfg or the PCI
+    /// configuration space concurrently. Only call this under a KVM-based VMM, on other
+    /// machines the ports might belong to other devices.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub unsafe fn gather_devices(&mut self) {
+        self.fw_cfg_present = Some(fw_cfg::signature_present());
+        let pci_present = pci::mechanism_present();
+        self.pci_present = Some(pci_present);
+        let read_ids = |device| {
+            pci_present
+                .then(|| pci::read_config(0, device, 0, 0))
+                .filter(|&id| id as u16 != 0xffff)
+                .map(|id| (id as u16, (id >> 16) as u16))
+        };
+        self.pci_host_bridge = read_ids(0);
+        self.pci_device_1 = read_ids(1);
+    }
+
+    /// Identifies the KVM-based VMM. Only meaningful, if the VMM is KVM-based. Returns
+    /// [`VmmKind::KvmOther`], if the evidence isn't sufficient.
+    ///
+    /// DMI strings take precedence, then fw_cfg (only QEMU has it), then the PCI host
+    /// bridge. Without fw_cfg and PCI, the VMM is Firecracker. Without fw_cfg and with a
+    /// virtio device instead of a host bridge at `00:00.0`, the VMM is kvmtool, which
+    /// also provides no DMI tables. Without fw_cfg, with the i440FX host bridge and a
+    /// modern virtio device instead of the ISA bridge of QEMU at `00:01.0`, the VMM is
+    /// crosvm.
+    pub fn classify(&self) -> VmmKind {
+        let dmi = vmm_from_dmi(
+            self.sys_vendor.unwrap_or(""),
+            self.product_name.unwrap_or(""),
+        );
+        if let Some(vmm) = dmi.filter(VmmKind::kvm_based) {
+            return vmm;
+        }
+        if self.fw_cfg_present == Some(true) {
+            VmmKind::Qemu
+        } else if self.pci_host_bridge == Some(CLOUD_HYPERVISOR_HOST_BRIDGE) {
+            VmmKind::CloudHypervisor
+        } else if self.fw_cfg_present == Some(false)
+            && self.pci_host_bridge.map(|(vendor_id, _)| vendor_id) == Some(VIRTIO_VENDOR_ID)
+        {
+            VmmKind::Kvmtool
+        } else if self.fw_cfg_present == Some(false)
+            && self.pci_host_bridge == Some(I440FX_HOST_BRIDGE)
+            && self.pci_device_1.is_some_and(|(vendor_id, device_id)| {
+                vendor_id == VIRTIO_VENDOR_ID && device_id >= VIRTIO_MODERN_DEVICE_ID_BASE
+            })
+        {
+            VmmKind::Crosvm
+        } else if self.fw_cfg_present == Some(false) && self.pci_present == Some(false) {
+            VmmKind::Firecracker
+        } else {
+            VmmKind::KvmOther
+        }
+    }
+}
+
+impl Default for KvmEvidence<'_> {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+/// Probe that identifies the KVM-based VMM via [`KvmEvidence::gather_devices`] and
+/// [`KvmEvidence::classify`].
+///
+/// Returns [`ProbeResult::Qemu`], if the VMM is QEMU, and [`ProbeResult::NotQemu`], if it
+/// is another known VMM, such as kvmtool or Firecracker. Returns
+/// [`ProbeResult::Inconclusive`], if the VMM isn't KVM-based or can't be identified. A
+/// probe created by [`Self::for_vmm`] only looks for one VMM.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{Detector, KvmVmmProbe};
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// let probe = unsafe { KvmVmmProbe::new() };
+/// let report = Detector::new(&[&probe]).detect();
+/// if report.certainty().is_very_likely() {
+///     println!("QEMU, use debugcon");
+/// }
+/// ```
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[derive(Copy, Clone, Debug)]
+pub struct KvmVmmProbe {
+    /// The only VMM to look for, or `None` for all.
+    vmm: Option<VmmKind>,
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl KvmVmmProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// See [`KvmEvidence::gather_devices`]. The probe only accesses the ports under
+    /// KVM-based VMMs.
+    pub const unsafe fn new() -> Self {
+        Self { vmm: None }
+    }
+
+    /// Creates a new probe that only looks for `vmm`, e.g. [`VmmKind::Firecracker`],
+    /// which [`KvmEvidence::classify`] recognizes without fw_cfg and PCI.
+    ///
+    /// Returns [`ProbeResult::NotQemu`], if the VMM is `vmm`, and
+    /// [`ProbeResult::Inconclusive`] otherwise, also for QEMU.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust,no_run
+    /// use runs_inside_qemu::{Detector, KvmVmmProbe, VmmKind};
+    ///
+    /// // SAFETY: runs in ring 0 before other CPUs are started
+    /// let probe = unsafe { KvmVmmProbe::for_vmm(VmmKind::Firecracker) };
+    /// let report = Detector::new(&[&probe]).detect();
+    /// if report.certainty().is_definitely_not() {
+    ///     println!("don't use debugcon");
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    /// See [`Self::new`].
+    pub const unsafe fn for_vmm(vmm: VmmKind) -> Self {
+        Self { vmm: Some(vmm) }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl Probe for KvmVmmProbe {
+    fn name(&self) -> &'static str {
+        "kvm-vmm"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        if cpuid::vmm() != Some(VmmKind::KvmOther) {
+            return ProbeResult::Inconclusive;
+        }
+        let mut evidence = KvmEvidence::EMPTY;
+        // SAFETY: guaranteed by the caller of `KvmVmmProbe::new`
+        unsafe { evidence.gather_devices() };
+        match evidence.classify() {
+            vmm if self.vmm.is_some_and(|target| target != vmm) => ProbeResult::Inconclusive,
+            VmmKind::Qemu => ProbeResult::Qemu,
+            VmmKind::KvmOther => ProbeResult::Inconclusive,
+            vmm => {
+                debug!("KVM-based VMM is {:?}.", vmm);
+                ProbeResult::NotQemu
+            }
+        }
+    }
+}
+
+/// Probe that checks if KVM announces paravirtual features (see [`kvm_features`]).
+///
+/// Returns [`ProbeResult::Hint`], if kvmclock is available, which corroborates that the
+/// KVM signature isn't spoofed, and [`ProbeResult::Inconclusive`] otherwise. KVM-based
+/// VMMs other than QEMU announce the features as well.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KvmFeaturesProbe;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl Probe for KvmFeaturesProbe {
+    fn name(&self) -> &'static str {
+        "kvm-features"
+    }
+
+    fn run(&self) -> ProbeResult {
+        match kvm_features() {
+            Some(features) if features.kvmclock => {
+                debug!("KVM paravirtual features: {:?}.", features.features);
+                ProbeResult::Hint
+            }
+            _ => ProbeResult::Inconclusive,
+        }
+    }
+}
@@ -23,7 +23,15 @@ SOFTWARE.
 */
 
 //! Small `no_std`-lib that checks if the binary is running inside a QEMU virtual machine.
-//! Only works on x86/x86_64 platform. There are no heap allocation required.
+//! Works on x86/x86_64 via CPUID, and on aarch64 and riscv64 via the device tree, SMCCC,
+//! and SBI. There are no heap allocation required.
+//!
+//! On architectures other than x86/x86_64, the crate still compiles, but the CPUID-based
+//! checks are not available. Without further [`Probe`]s, the verdict there is always
+//! [`QemuCertainty::Unknown`]. On aarch64, pass the device tree to a
+//! [`DeviceTreeProbe`] and optionally use the SMCCC-based `SmcccProbe`. On riscv64, use
+//! the [`DeviceTreeProbe`] and optionally the SBI-based `SbiProbe`. On both, the MMIO
+//! variant of fw_cfg is found via [`FwCfgProbe::from_device_tree`].
 //!
 //! Under the hood, this is a wrapper around the awesome crate <https://crates.io/crates/raw-cpuid>.
 
@@ -32,123 +40,422 @@ SOFTWARE.
 #![deny(rustdoc::all)]
 #![allow(rustdoc::missing_doc_code_examples)]
 
-#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
-compile_error!("This crate only works on the x86/x86_64-platform.");
-
-use raw_cpuid::{CpuId, Hypervisor};
-
-/// Result of [`runs_inside_qemu`] that tells with what certainty the code runs inside QEMU.
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum QemuCertainty {
-    /// The code definitely doesn't run inside QEMU, because the Hypervisor-flag is not set.
-    DefinitelyNot,
-
-    /// The code maybe runs inside QEMU. This is returned
-    /// if the application runs inside KVM as hypervisor, but
-    /// the CPU is not QEMU. If you know, that your development
-    /// setup only uses QEMU/KVM as Hypervisor, this is equal
-    /// to [`Self::VeryLikely`].
-    ///
-    /// This happens, if you use something like "-cpu host" instead of "-cpu qemu64" as QEMU option.
-    Maybe,
-
-    /// The information obtained by CPUID clearly states, that the code runs inside QEMU.
-    /// Be aware, that Hypervisors could implement similar behaviour as QEMU does.
-    ///
-    /// `High` is the case, if the Hypervisor ID is the one from QEMU or if the Hypervisor-ID
-    /// is `KVM` and the ID of the CPU brand string contains `QEMU`.
-    VeryLikely,
-}
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
-impl QemuCertainty {
-    /// Returns if certainty is low/definitely not.
-    pub fn is_definitely_not(self) -> bool {
-        self == Self::DefinitelyNot
-    }
+#[macro_use]
+mod logging;
 
-    /// Returns if certainty is high/very likely.
-    pub fn is_very_likely(self) -> bool {
-        self == Self::VeryLikely
-    }
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod accelerator;
+mod acpi;
+mod aggregation;
+mod aws;
+mod azure;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod bios;
+#[cfg(feature = "std")]
+mod block;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod bochs;
+mod cache;
+mod certainty;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod clock;
+mod cmdline;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod cmos;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod confidential;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod cpu_model;
+mod cpuid;
+#[cfg(feature = "std")]
+mod cpuinfo;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod debug_devices;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod debug_exit;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod debugcon;
+mod detector;
+mod dmi;
+mod dtb;
+mod e820;
+mod efi_vendor;
+#[cfg(feature = "std")]
+mod environment;
+mod error;
+mod fixed_str;
+mod fw_cfg;
+mod gce;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod hyperv;
+mod hypervisor;
+mod identity;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod interfaces;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod io;
+mod kvm;
+#[cfg(feature = "std")]
+mod mac;
+mod machine;
+mod management;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod microvm;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod msr;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod nesting;
+mod pci;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod pit;
+mod policy;
+mod probe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod pvpanic;
+mod qemu_pci;
+mod report;
+#[cfg(all(feature = "ring0", any(target_arch = "x86", target_arch = "x86_64")))]
+mod ring0;
+#[cfg(target_arch = "riscv64")]
+mod riscv64;
+mod smbios;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod smp;
+#[cfg(feature = "std")]
+mod sysfs_dmi;
+#[cfg(feature = "testing")]
+mod testing;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod timing;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod topology;
+#[cfg(feature = "uefi")]
+mod uefi;
+mod version;
+mod virtio;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod virtualbox;
+mod vmgenid;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod vmware;
+#[cfg(all(feature = "std", windows))]
+mod windows;
+#[cfg(feature = "std")]
+mod wsl;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod xen;
 
-    /// Returns if certainty is maybe or high.
-    pub fn is_maybe_or_very_likely(self) -> bool {
-        self == Self::Maybe || self == Self::VeryLikely
-    }
-}
+#[cfg(target_arch = "aarch64")]
+pub use aarch64::{hypervisor_uid, SmcccConduit, SmcccProbe, KVM_HYPERVISOR_UID};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use accelerator::{accelerator, accelerator_with};
+pub use acpi::AcpiProbe;
+pub use aggregation::Weights;
+pub use aws::{aws_vmm, AwsProbe};
+pub use azure::azure_vmm;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use bios::BiosAreaProbe;
+#[cfg(feature = "std")]
+pub use block::BlockDeviceProbe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use bochs::BochsProbe;
+pub use cache::{init, runs_inside_qemu_cached, runs_inside_qemu_irqsafe};
+pub use certainty::{ParseQemuCertaintyError, QemuCertainty};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use clock::{clock_capabilities, clock_capabilities_with, ClockCapabilities};
+#[cfg(feature = "std")]
+pub use cmdline::proc_cmdline;
+pub use cmdline::CmdlineProbe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use cmos::CmosProbe;
+#[cfg(all(feature = "ring0", any(target_arch = "x86", target_arch = "x86_64")))]
+pub use confidential::sev_status;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use confidential::{confidential_mode, confidential_mode_with, ConfidentialMode};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use cpu_model::{cpu_model, cpu_model_with, CpuModel};
+pub use cpuid::{brand_string_mentions_qemu, hypervisor_flag_set, hypervisor_is_qemu};
+#[cfg(feature = "std")]
+pub use cpuinfo::CpuinfoProbe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use debug_devices::{debug_devices, DebugDevices};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use debug_exit::{exit_qemu, exit_qemu_with_port, try_exit_qemu};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use debug_exit::{QemuExitCode, ISA_DEBUG_EXIT_PORT};
+#[cfg(all(feature = "log", any(target_arch = "x86", target_arch = "x86_64")))]
+pub use debugcon::DebugconLogger;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use debugcon::DebugconWriter;
+pub use detector::{Detector, DetectorBuilder};
+pub use dmi::{vmm_from_dmi, DmiProbe};
+pub use dtb::{DeviceTree, DeviceTreeProbe};
+pub use e820::{E820Entries, E820Entry, E820Kind};
+pub use efi_vendor::EfiVendorProbe;
+#[cfg(feature = "std")]
+pub use environment::{execution_environment, ExecutionEnvironment, UserModeKernel};
+pub use error::DetectError;
+pub use fw_cfg::{
+    FwCfg, FwCfgFile, FwCfgFiles, FwCfgProbe, VirtToPhysFn, FW_CFG_MAX_FILE_NAME_LEN,
+};
+pub use gce::{gce_vmm, GceProbe};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use hyperv::{hyperv_info, HyperVInfo, HyperVMsrProbe};
+pub use hypervisor::{
+    detect_hypervisor, hypervisor_signature, Accelerator, InterfaceVersion, VmmKind,
+};
+pub use identity::{Uuid, VmIdentity};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use interfaces::{hypervisor_interfaces, hypervisor_interfaces_with, HypervisorInterface};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use kvm::{kvm_features, KvmFeaturesProbe, KvmVmmProbe};
+pub use kvm::{KvmEvidence, KvmFeatures};
+#[cfg(feature = "std")]
+pub use mac::MacAddressProbe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use machine::{firmware_kind, machine_type};
+pub use machine::{FirmwareKind, MachineType};
+pub use management::{management_stack, ManagementStack};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use microvm::{Microvm, VirtioMmioDevice, MICROVM_VIRTIO_MMIO_SLOTS};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use nesting::{nesting, nesting_with, Nesting};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use pit::PitProbe;
+pub use policy::DetectionPolicy;
+pub use probe::{Probe, ProbeResult};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use pvpanic::{notify_panic, PanicEvent, Pvpanic, PVPANIC_ISA_PORT};
+pub use qemu_pci::{Chipset, QemuPciProbe};
+pub use report::{
+    Check, CheckOutcome, DetectionReport, Evidence, EvidenceItem, MAX_PROBE_NAME_LEN,
+    MAX_REPORTED_PROBES, MAX_SERIAL_NUMBER_LEN,
+};
+#[cfg(all(feature = "ring0", any(target_arch = "x86", target_arch = "x86_64")))]
+pub use ring0::{MsrProbe, MsrReadFn};
+#[cfg(target_arch = "riscv64")]
+pub use riscv64::{sbi_info, SbiImplementation, SbiInfo, SbiProbe};
+pub use smbios::{Smbios, SmbiosProbe};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use smp::{bsp_apic_id, init_bsp, is_bsp};
+#[cfg(feature = "std")]
+pub use sysfs_dmi::SysfsDmiProbe;
+#[cfg(feature = "testing")]
+pub use testing::{set_mock_result, MockGuard};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use timing::TimingProbe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use topology::{guest_topology, guest_topology_with, GuestTopology};
+#[cfg(feature = "uefi")]
+pub use uefi::UefiTables;
+pub use version::{qemu_version, QemuVersion};
+pub use virtio::VirtioProbe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use virtualbox::VirtualBoxProbe;
+pub use vmgenid::VmGenId;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use vmware::{vmware_backdoor_version, VmwareBackdoorProbe};
+#[cfg(all(feature = "std", windows))]
+pub use windows::WindowsFirmwareProbe;
+#[cfg(feature = "std")]
+pub use wsl::{runs_inside_wsl2, Wsl2Probe};
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use xen::{xen_guest_mode, xen_hvm_with_qemu, xen_info, XenGuestMode, XenInfo, XenProbe};
+
+/// Re-export of the `raw-cpuid` version that this crate uses, to implement a
+/// [`CpuIdReader`] for [`runs_inside_qemu_with`] and [`detect_with`].
+///
+/// [`CpuIdReader`]: raw_cpuid::CpuIdReader
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use raw_cpuid;
 
 /// Returns if the code is running inside a QEMU virtual machine.
-/// See [`QemuCertainty`], which is the return type.
+/// See [`QemuCertainty`], which is the return type. Use [`detect`] if you
+/// are also interested in the evidence that led to the verdict.
 ///
 /// ## Example Usage
 ///
-/// ```rust
-/// # use runs_inside_qemu::runs_inside_qemu;
+/// ```rust,no_run
+/// # use core::fmt::Write;
+/// # use runs_inside_qemu::{debug_devices, runs_inside_qemu, DebugconWriter};
 ///
 /// fn main() {
 ///     // If we are in QEMU, we use the nice "debugcon"-feature which maps
-///     // the x86 I/O-port `0xe9` to stdout or a file.
-///     if runs_inside_qemu().is_maybe_or_very_likely() {
-///         unsafe {
-///             x86::io::outb(0xe9, b'H');
-///             x86::io::outb(0xe9, b'e');
-///             x86::io::outb(0xe9, b'l');
-///             x86::io::outb(0xe9, b'l');
-///             x86::io::outb(0xe9, b'o');
-///             x86::io::outb(0xe9, b'\n');
-///         }
+///     // the x86 I/O-port `0xe9` to stdout or a file. `debug_devices()` tells
+///     // if the debugcon is actually present.
+///     if runs_inside_qemu().is_maybe_or_very_likely() && unsafe { debug_devices() }.debugcon {
+///         let mut debugcon = unsafe { DebugconWriter::new_unchecked(0xe9) };
+///         writeln!(debugcon, "Hello").unwrap();
 ///     }
 /// }
 /// ```
 pub fn runs_inside_qemu() -> QemuCertainty {
-    let id = CpuId::new();
-
-    // ########## CHECK 1 ##########
-    // The `x86` library first checks if the Hypervisor flag is present in the `cpuid` features.
-    // If yes, it reads the Hypervisor info leaf from `cpuid`.
-    // Also see https://lwn.net/Articles/301888/)
-    let hypervisor_info = id.get_hypervisor_info();
-    if hypervisor_info.is_none() {
-        // QEMU is a Hypervisor and no real machine => exit if this is None
-        log::debug!(
-            "Definitely not QEMU. Hypervisor flag is not set, no hypervisor info available."
-        );
-        return QemuCertainty::DefinitelyNot;
+    #[cfg(feature = "testing")]
+    if let Some(certainty) = testing::mock_result() {
+        return certainty;
     }
-    let hypervisor_info = hypervisor_info.unwrap();
+    detect().certainty()
+}
 
-    // if this returns false, because the hypervisor ID can be "KVM",
-    // we still could be executed by QEMU -> further checks needed
-    if matches!(hypervisor_info.identify(), Hypervisor::QEMU) {
-        log::debug!("Runs very likely in QEMU. QEMU is the direct hypervisor (no KVM etc.).");
-        return QemuCertainty::VeryLikely;
-    }
+/// Like [`runs_inside_qemu`] but returns a [`DetectionReport`] that contains the final
+/// [`QemuCertainty`] plus all evidence that was gathered: the hypervisor ID, the CPU brand
+/// string, and the outcome of each [`Check`] that ran.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::detect;
+///
+/// let report = detect();
+/// println!("verdict: {:?}", report.certainty());
+/// println!("hypervisor id: {:?}", report.hypervisor_id());
+/// println!("brand string: {:?}", report.brand_string());
+/// for (check, outcome) in report.checks() {
+///     println!("{:?}: {:?}", check, outcome);
+/// }
+/// ```
+pub fn detect() -> DetectionReport {
+    Detector::new(&[]).detect()
+}
+
+/// Like [`runs_inside_qemu`] but reads CPUID through the given
+/// [`raw_cpuid::CpuIdReader`] instead of executing the `cpuid` instruction. This way, you
+/// can unit test your code with canned CPUID data or replay captured leaves. Closures
+/// `Fn(u32, u32) -> CpuIdResult` implement [`raw_cpuid::CpuIdReader`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{runs_inside_qemu_with, QemuCertainty};
+///
+/// // QEMU without an accelerator (TCG)
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         // highest basic leaf
+///         0x0 => (0x1, 0, 0, 0),
+///         // hypervisor flag
+///         0x1 => (0, 0, 1 << 31, 0),
+///         // "TCGTCGTCGTCG"
+///         0x4000_0000 => (0x4000_0001, 0x5447_4354, 0x4354_4743, 0x4743_5447),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// assert_eq!(runs_inside_qemu_with(reader), QemuCertainty::VeryLikely);
+/// ```
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn runs_inside_qemu_with<R: raw_cpuid::CpuIdReader>(reader: R) -> QemuCertainty {
+    detect_with(reader).certainty()
+}
 
-    // ########## CHECK 2 ##########
-    // now check the extended CPU brand string (which is specific for QEMU)
-    let brand_string = id.get_processor_brand_string();
-    if brand_string.is_none() {
-        log::debug!(
-            "Maybe QEMU. CPU brand string not available, can't verify if code runs inside QEMU."
-        );
-        return QemuCertainty::Maybe;
+/// Like [`detect`] but reads CPUID through the given [`raw_cpuid::CpuIdReader`]. See
+/// [`runs_inside_qemu_with`].
+///
+/// ## Example Usage
+///
+/// QEMU with TCG and `-cpu qemu64,-hypervisor` hides the hypervisor flag but is still
+/// detected by its signature:
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{detect_with, Check, CheckOutcome, QemuCertainty};
+///
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x1, 0, 0, 0),
+///         // no hypervisor flag
+///         0x1 => (0, 0, 0, 0),
+///         // "TCGTCGTCGTCG"
+///         0x4000_0000 => (0x4000_0001, 0x5447_4354, 0x4354_4743, 0x4743_5447),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let report = detect_with(reader);
+/// assert_eq!(report.check(Check::HypervisorFlag), Some(CheckOutcome::Failed));
+/// assert_eq!(report.check(Check::HypervisorId), Some(CheckOutcome::Passed));
+/// assert_eq!(report.certainty(), QemuCertainty::VeryLikely);
+/// ```
+///
+/// With KVM and `-cpu host,-hypervisor`, KVM still announces itself:
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{detect_with, QemuCertainty, VmmKind};
+///
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x1, 0, 0, 0),
+///         // no hypervisor flag
+///         0x1 => (0, 0, 0, 0),
+///         // "KVMKVMKVM"
+///         0x4000_0000 => (0x4000_0001, 0x4b4d_564b, 0x564b_4d56, 0x0000_004d),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let report = detect_with(reader);
+/// assert_eq!(report.vmm(), Some(VmmKind::KvmOther));
+/// assert_eq!(report.certainty(), QemuCertainty::Maybe);
+/// ```
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn detect_with<R: raw_cpuid::CpuIdReader>(reader: R) -> DetectionReport {
+    Detector::new(&[]).detect_with(reader)
+}
+
+/// Like [`runs_inside_qemu`] but returns a [`DetectError`] if the CPUID checks couldn't run
+/// properly, instead of silently treating this like a negative result. See
+/// [`Detector::try_detect`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{try_runs_inside_qemu, DetectError};
+///
+/// match try_runs_inside_qemu() {
+///     Ok(certainty) => println!("verdict: {}", certainty),
+///     Err(DetectError::CpuidUnavailable) => println!("no CPUID on this architecture"),
+///     Err(error) => println!("detection failed: {}", error),
+/// }
+/// ```
+pub fn try_runs_inside_qemu() -> Result<QemuCertainty, DetectError> {
+    #[cfg(feature = "testing")]
+    if let Some(certainty) = testing::mock_result() {
+        return Ok(certainty);
     }
-    let brand_string = brand_string.unwrap();
-    let brand_string = brand_string.as_str();
-
-    let cpu_brand_string_contains_qemu = brand_string.contains("QEMU");
-    if cpu_brand_string_contains_qemu {
-        // "QEMU Virtual CPU version 2.5+"
-        log::debug!(
-            "Runs very likely in QEMU with {:?} as accelerator.",
-            hypervisor_info.identify()
-        );
-        QemuCertainty::VeryLikely
-    } else {
-        log::debug!(
-            "Maybe QEMU. Hypervisor is {:?} but CPU brand string is not the one from QEMU.",
-            hypervisor_info.identify()
-        );
-        QemuCertainty::Maybe
+    Detector::new(&[])
+        .try_detect()
+        .map(|report| report.certainty())
+}
+
+/// Like [`runs_inside_qemu`] but applies the given [`DetectionPolicy`] to the verdict.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{runs_inside_qemu_with_policy, DetectionPolicy};
+///
+/// // conservative: only run the QEMU-specific integration tests if we are sure
+/// if runs_inside_qemu_with_policy(DetectionPolicy::Strict).is_very_likely() {
+///     println!("running QEMU-specific tests");
+/// }
+/// ```
+pub fn runs_inside_qemu_with_policy(policy: DetectionPolicy) -> QemuCertainty {
+    #[cfg(feature = "testing")]
+    if let Some(certainty) = testing::mock_result() {
+        return certainty;
     }
+    Detector::builder()
+        .policy(policy)
+        .build()
+        .detect()
+        .certainty()
 }
@@ -0,0 +1,18 @@
+//! Internal logging facade. Forwards to the `log` crate and/or `defmt`, depending
+//! on the enabled features, and compiles to nothing otherwise.
+
+/// Emits a debug message. The format string must be compatible with both `log` and
+/// `defmt`, i.e. only use `{}` and `{:?}`. Without any logging feature, the arguments
+/// are still type-checked but no code is generated.
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+        #[cfg(feature = "defmt")]
+        defmt::debug!($($arg)*);
+        #[cfg(not(any(feature = "log", feature = "defmt")))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    }};
+}
@@ -0,0 +1,63 @@
+//! Module for [`MacAddressProbe`], which checks the MAC addresses of the network
+//! interfaces for QEMU's default prefix. Needs the `std` feature. QEMU, libvirt, and most
+//! tools on top of them generate MAC addresses with the prefix `52:54:00`, unless the user
+//! configures another address.
+
+use crate::{Probe, ProbeResult, Weights};
+use std::fs;
+
+/// Directory with one subdirectory per network interface.
+const SYSFS_NET_DIR: &str = "/sys/class/net";
+/// Prefix of the MAC addresses that QEMU generates.
+const QEMU_MAC_PREFIX: &str = "52:54:00:";
+
+/// Probe that reads the MAC addresses of all network interfaces from
+/// `/sys/class/net/*/address`.
+///
+/// Returns [`ProbeResult::Hint`], if any address starts with `52:54:00`, and
+/// [`ProbeResult::Inconclusive`] otherwise. As addresses are configurable, this is weak
+/// evidence: the probe contributes only half the weight of [`Weights::probe_hint`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, MacAddressProbe};
+///
+/// let probe = MacAddressProbe;
+/// let report = Detector::builder().probes(&[&probe]).build().detect();
+/// println!("{:?} (score {})", report.certainty(), report.score());
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MacAddressProbe;
+
+impl Probe for MacAddressProbe {
+    fn name(&self) -> &'static str {
+        "mac-address"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let Ok(interfaces) = fs::read_dir(SYSFS_NET_DIR) else {
+            return ProbeResult::Inconclusive;
+        };
+        let qemu_mac = interfaces.flatten().any(|interface| {
+            fs::read_to_string(interface.path().join("address"))
+                .map(|address| {
+                    address
+                        .trim()
+                        .to_ascii_lowercase()
+                        .starts_with(QEMU_MAC_PREFIX)
+                })
+                .unwrap_or(false)
+        });
+        if qemu_mac {
+            debug!("Found a network interface with QEMU's MAC address prefix.");
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+
+    fn weight(&self, result: ProbeResult, weights: &Weights) -> i32 {
+        weights.probe(result) / 2
+    }
+}
@@ -0,0 +1,270 @@
+//! Module for [`MachineType`] and [`machine_type`], which tell which of QEMU's machine
+//! types emulates the platform. Drivers usually depend on it: `pc` and `q35` differ in
+//! their chipset, `microvm` has neither PCI nor an ACPI-capable chipset by default, and
+//! the `virt` machines for ARM and RISC-V are described by a device tree. On top of that,
+//! [`FirmwareKind`] and [`firmware_kind`] tell which firmware booted the machine.
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::{bios, fw_cfg, pci, QemuPciProbe};
+use crate::{Chipset, DeviceTree};
+
+/// Part of the SMBIOS product name of the `pc` machine, e.g.
+/// `Standard PC (i440FX + PIIX, 1996)`.
+const PRODUCT_NAME_PC: &str = "i440FX";
+/// Part of the SMBIOS product name of the `q35` machine, e.g.
+/// `Standard PC (Q35 + ICH9, 2009)`.
+const PRODUCT_NAME_Q35: &str = "Q35";
+/// SMBIOS product name of the `microvm` machine, if SMBIOS tables are enabled.
+const PRODUCT_NAME_MICROVM: &str = "microvm";
+
+/// Path of the SMBIOS product name in the sysfs of Linux.
+#[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+const SYSFS_PRODUCT_NAME: &str = "/sys/class/dmi/id/product_name";
+/// Path of the SMBIOS BIOS vendor in the sysfs of Linux.
+#[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+const SYSFS_BIOS_VENDOR: &str = "/sys/class/dmi/id/bios_vendor";
+/// Directory in the sysfs of Linux that only exists when booted via UEFI.
+#[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+const SYSFS_EFI: &str = "/sys/firmware/efi";
+
+/// BIOS vendor of SeaBIOS.
+const BIOS_VENDOR_SEABIOS: &str = "SeaBIOS";
+/// BIOS vendors of OVMF: the SMBIOS vendor and the UEFI firmware vendor.
+const BIOS_VENDORS_OVMF: [&str; 2] = ["EDK II", "EFI Development Kit II / OVMF"];
+/// BIOS vendor of qboot, if SMBIOS tables are enabled.
+const BIOS_VENDOR_QBOOT: &str = "qboot";
+
+/// A machine type of QEMU (`-machine ...`). Result of [`machine_type`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum MachineType {
+    /// The `pc` machine with the i440FX chipset.
+    Pc,
+    /// The `q35` machine with the Q35 chipset.
+    Q35,
+    /// The minimal `microvm` machine without PCI.
+    Microvm,
+    /// The `virt` machine for ARM or RISC-V.
+    Virt,
+}
+
+impl MachineType {
+    /// Identifies the machine type by the SMBIOS product name, e.g.
+    /// `Standard PC (Q35 + ICH9, 2009)`.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::MachineType;
+    ///
+    /// let machine = MachineType::from_product_name("Standard PC (Q35 + ICH9, 2009)");
+    /// assert_eq!(machine, Some(MachineType::Q35));
+    /// ```
+    pub fn from_product_name(product_name: &str) -> Option<Self> {
+        let product_name = product_name.trim();
+        if product_name.contains(PRODUCT_NAME_PC) {
+            Some(Self::Pc)
+        } else if product_name.contains(PRODUCT_NAME_Q35) {
+            Some(Self::Q35)
+        } else if product_name == PRODUCT_NAME_MICROVM {
+            Some(Self::Microvm)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the machine type with the given chipset, see [`QemuPciProbe::chipset`].
+    pub const fn from_chipset(chipset: Chipset) -> Self {
+        match chipset {
+            Chipset::I440fx => Self::Pc,
+            Chipset::Q35 => Self::Q35,
+        }
+    }
+
+    /// Identifies the `virt` machines by the `compatible` property of the root node of
+    /// the device tree. Other VMMs use the same `compatible`, so the device tree must also
+    /// have a fw_cfg node or a root `model` that mentions QEMU, see
+    /// [`crate::DeviceTreeProbe`].
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::{DeviceTree, DeviceTreeProbe, MachineType, Probe, ProbeResult};
+    ///
+    /// // the device trees of `qemu-system-aarch64 -M virt` and `qemu-system-riscv64 -M virt`
+    /// let blobs: [(&[u8], u64); 2] = [
+    ///     (include_bytes!("../tests/fixtures/qemu-virt-aarch64.dtb"), 0x0902_0000),
+    ///     (include_bytes!("../tests/fixtures/qemu-virt-riscv64.dtb"), 0x1010_0000),
+    /// ];
+    /// for (blob, fw_cfg_address) in blobs {
+    ///     let device_tree = DeviceTree::new(blob).unwrap();
+    ///     let machine = MachineType::from_device_tree(&device_tree);
+    ///     assert_eq!(machine, Some(MachineType::Virt));
+    ///     assert_eq!(device_tree.reg_address("/fw-cfg"), Some(fw_cfg_address));
+    ///     assert_eq!(DeviceTreeProbe::new(device_tree).run(), ProbeResult::Qemu);
+    /// }
+    ///
+    /// // Firecracker uses the same root `compatible`, but has no fw_cfg
+    /// let blob = include_bytes!("../tests/fixtures/firecracker-aarch64.dtb");
+    /// let device_tree = DeviceTree::new(blob).unwrap();
+    /// assert_eq!(MachineType::from_device_tree(&device_tree), None);
+    /// assert_eq!(DeviceTreeProbe::new(device_tree).run(), ProbeResult::Hint);
+    /// ```
+    pub fn from_device_tree(device_tree: &DeviceTree) -> Option<Self> {
+        (device_tree.root_is_virt() && device_tree.has_qemu_device()).then_some(Self::Virt)
+    }
+}
+
+/// Determines the machine type of QEMU on x86. With the `std` feature, the SMBIOS
+/// product name from the sysfs of Linux is used first. Otherwise, or if it is unknown,
+/// the host bridge at `00:00.0` tells `pc` and `q35` apart. Without PCI, fw_cfg tells
+/// that the machine is `microvm`. Returns `None`, if none of them matches, e.g. because
+/// the VMM isn't QEMU. Use [`MachineType::from_device_tree`] on ARM and RISC-V.
+///
+/// The chipset alone doesn't identify QEMU: combine this with the verdict of
+/// [`crate::detect`].
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{machine_type, MachineType};
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// match unsafe { machine_type() } {
+///     Some(MachineType::Q35) => println!("load the ICH9 drivers"),
+///     Some(MachineType::Microvm) => println!("virtio-mmio only"),
+///     other => println!("{:?}", other),
+/// }
+/// ```
+///
+/// # Safety
+/// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+/// sufficient I/O privilege level, and nobody else may access the PCI configuration
+/// space or fw_cfg while this runs.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub unsafe fn machine_type() -> Option<MachineType> {
+    #[cfg(feature = "std")]
+    if let Some(machine) = std::fs::read_to_string(SYSFS_PRODUCT_NAME)
+        .ok()
+        .and_then(|product_name| MachineType::from_product_name(&product_name))
+    {
+        debug!("Machine type from the DMI product name: {:?}", machine);
+        return Some(machine);
+    }
+    if pci::mechanism_present() {
+        let machine = QemuPciProbe::new().chipset().map(MachineType::from_chipset);
+        debug!("Machine type from the host bridge: {:?}", machine);
+        machine
+    } else if fw_cfg::signature_present() {
+        debug!("fw_cfg without PCI, machine type is microvm.");
+        Some(MachineType::Microvm)
+    } else {
+        None
+    }
+}
+
+/// A firmware of QEMU (`-bios ...` or `-drive if=pflash,...`). Result of
+/// [`firmware_kind`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum FirmwareKind {
+    /// SeaBIOS, the default legacy BIOS of the `pc` and `q35` machines.
+    SeaBios,
+    /// OVMF, the UEFI firmware of EDK II for virtual machines.
+    Ovmf,
+    /// qboot, the minimal firmware and default of the `microvm` machine.
+    Qboot,
+}
+
+impl FirmwareKind {
+    /// Identifies the firmware by the SMBIOS BIOS vendor (see [`crate::Smbios::bios_vendor`])
+    /// or the UEFI firmware vendor, e.g. from `UefiTables::firmware_vendor()` of the
+    /// `uefi` feature.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::FirmwareKind;
+    ///
+    /// assert_eq!(FirmwareKind::from_bios_vendor("EDK II"), Some(FirmwareKind::Ovmf));
+    /// assert_eq!(FirmwareKind::from_bios_vendor("SeaBIOS"), Some(FirmwareKind::SeaBios));
+    /// ```
+    pub fn from_bios_vendor(vendor: &str) -> Option<Self> {
+        let vendor = vendor.trim();
+        if vendor == BIOS_VENDOR_SEABIOS {
+            Some(Self::SeaBios)
+        } else if BIOS_VENDORS_OVMF.contains(&vendor) {
+            Some(Self::Ovmf)
+        } else if vendor == BIOS_VENDOR_QBOOT {
+            Some(Self::Qboot)
+        } else {
+            None
+        }
+    }
+}
+
+/// Determines the firmware of QEMU on x86. With the `std` feature, the SMBIOS BIOS
+/// vendor from the sysfs of Linux is used first. Only if it can't be read, the presence
+/// of `/sys/firmware/efi` means OVMF; a vendor of another firmware yields `None`.
+/// Otherwise, the legacy BIOS area is searched for the signature of SeaBIOS and the
+/// SMBIOS tables for the BIOS vendor. qboot provides neither, so a `microvm` machine
+/// (see [`machine_type`]) without them means qboot. Returns `None`, if none of them
+/// matches.
+///
+/// The firmware alone doesn't identify QEMU, e.g. OVMF and SeaBIOS also run on other
+/// VMMs: combine this with the verdict of [`crate::detect`].
+///
+/// With the `uefi` feature, [`FirmwareKind::from_bios_vendor`] identifies OVMF via
+/// `UefiTables::firmware_vendor()` without touching the legacy BIOS area.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{firmware_kind, FirmwareKind};
+///
+/// // SAFETY: runs in ring 0 with identity-mapped memory before other CPUs are started
+/// match unsafe { firmware_kind() } {
+///     Some(FirmwareKind::Ovmf) => println!("check the OVMF debug log on port 0x402"),
+///     Some(FirmwareKind::SeaBios) => println!("check the SeaBIOS debug log on port 0x402"),
+///     other => println!("{:?}", other),
+/// }
+/// ```
+///
+/// # Safety
+/// See [`machine_type`]. Additionally, physical memory `0xe0000..0x100000` and the SMBIOS
+/// tables that it references must be identity-mapped and readable, see
+/// [`crate::BiosAreaProbe::new`].
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub unsafe fn firmware_kind() -> Option<FirmwareKind> {
+    #[cfg(feature = "std")]
+    {
+        let firmware = match std::fs::read_to_string(SYSFS_BIOS_VENDOR) {
+            Ok(vendor) => Some(FirmwareKind::from_bios_vendor(&vendor)),
+            Err(_) => std::path::Path::new(SYSFS_EFI)
+                .exists()
+                .then_some(Some(FirmwareKind::Ovmf)),
+        };
+        if let Some(firmware) = firmware {
+            debug!("Firmware from the sysfs: {:?}", firmware);
+            return firmware;
+        }
+    }
+    let firmware = if bios::seabios_signature_present() {
+        Some(FirmwareKind::SeaBios)
+    } else if let Some(firmware) = bios::find_smbios()
+        .and_then(|smbios| smbios.bios_vendor())
+        .and_then(FirmwareKind::from_bios_vendor)
+    {
+        Some(firmware)
+    } else if machine_type() == Some(MachineType::Microvm) {
+        Some(FirmwareKind::Qboot)
+    } else {
+        None
+    };
+    debug!("Firmware: {:?}", firmware);
+    firmware
+}
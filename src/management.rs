@@ -0,0 +1,77 @@
+//! Module for [`ManagementStack`], the software that manages a QEMU virtual machine.
+//! QEMU itself doesn't know it, but the management software usually leaves its name in
+//! the DMI strings.
+
+use crate::Accelerator;
+
+/// The software that manages a QEMU virtual machine. Result of [`management_stack`] and
+/// part of the [`crate::DetectionReport`], see [`crate::DetectorBuilder::dmi`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ManagementStack {
+    /// Proxmox Virtual Environment.
+    Proxmox,
+    /// oVirt.
+    Ovirt,
+    /// Red Hat Virtualization (RHV), formerly RHEV, the commercial oVirt.
+    Rhv,
+    /// The `qemu-kvm` of a Red Hat based host (RHEL, CentOS Stream, Fedora), which names
+    /// the product `KVM`. The software on top of it, e.g. libvirt, leaves no trace in the
+    /// DMI strings.
+    RhelQemuKvm,
+    /// UTM on macOS. UTM runs QEMU with the HVF accelerator. The
+    /// [`crate::DetectionReport`] only reports it together with HVF, see
+    /// [`crate::DetectionReport::qemu_via_utm`].
+    Utm,
+}
+
+/// Identifies the management software by the DMI system vendor and product name, as
+/// found in `/sys/class/dmi/id/sys_vendor` and `/sys/class/dmi/id/product_name` on
+/// Linux. Returns `None`, if the strings don't name known management software.
+///
+/// The DMI strings alone are only a candidate for [`ManagementStack::Utm`]; the
+/// [`crate::DetectionReport`] drops it, unless QEMU uses HVF.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{management_stack, ManagementStack};
+///
+/// assert_eq!(management_stack("Proxmox", "pve-vm"), Some(ManagementStack::Proxmox));
+/// assert_eq!(management_stack("oVirt", "oVirt Node"), Some(ManagementStack::Ovirt));
+/// assert_eq!(management_stack("Red Hat", "RHEV Hypervisor"), Some(ManagementStack::Rhv));
+/// assert_eq!(management_stack("Red Hat", "KVM"), Some(ManagementStack::RhelQemuKvm));
+/// assert_eq!(management_stack("QEMU", "Standard PC (i440FX + PIIX, 1996)"), None);
+/// ```
+pub fn management_stack(sys_vendor: &str, product_name: &str) -> Option<ManagementStack> {
+    let mentions =
+        |word: &str| contains_word(sys_vendor, word) || contains_word(product_name, word);
+    if mentions("proxmox") || mentions("pve") {
+        Some(ManagementStack::Proxmox)
+    } else if mentions("ovirt") {
+        Some(ManagementStack::Ovirt)
+    } else if mentions("rhev") || mentions("rhv") {
+        Some(ManagementStack::Rhv)
+    } else if mentions("utm") {
+        Some(ManagementStack::Utm)
+    } else if product_name.trim() == "KVM" {
+        Some(ManagementStack::RhelQemuKvm)
+    } else {
+        None
+    }
+}
+
+/// Returns if UTM fits the accelerator of the report: HVF on x86, or any on aarch64,
+/// where the accelerator can't be told.
+pub(crate) fn utm_plausible(accelerator: Option<Accelerator>) -> bool {
+    accelerator == Some(Accelerator::Hvf) || cfg!(target_arch = "aarch64")
+}
+
+/// Returns if `haystack` contains `word` as a whole word, ignoring ASCII case.
+fn contains_word(haystack: &str, word: &str) -> bool {
+    haystack
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|candidate| candidate.eq_ignore_ascii_case(word))
+}
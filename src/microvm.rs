@@ -0,0 +1,97 @@
+//! Module for [`Microvm`], the dedicated support for QEMU's `microvm` machine on x86. It
+//! has neither PCI nor a legacy chipset by default, but fw_cfg and up to 24 virtio-mmio
+//! transports at fixed addresses, so minimal kernels need the virtio-mmio transport
+//! instead of virtio-pci.
+
+use crate::virtio::{VIRTIO_MMIO_DEVICE_ID, VIRTIO_MMIO_MAGIC};
+use crate::{machine_type, MachineType};
+
+/// Physical address of the first virtio-mmio transport.
+const VIRTIO_MMIO_BASE: u64 = 0xfeb0_0000;
+/// Size of each virtio-mmio transport.
+const VIRTIO_MMIO_SIZE: u64 = 0x200;
+
+/// Number of virtio-mmio transports of the `microvm` machine with the second I/O APIC
+/// (`ioapic2=on`, the default). Without it, only the first 8 exist.
+pub const MICROVM_VIRTIO_MMIO_SLOTS: usize = 24;
+
+/// A virtio device on a virtio-mmio transport of the `microvm` machine. See
+/// [`Microvm::virtio_mmio_devices`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct VirtioMmioDevice {
+    /// Index of the transport.
+    pub slot: u8,
+    /// Physical address of the transport.
+    pub address: u64,
+    /// virtio device ID, e.g. `1` for a network device or `2` for a block device.
+    pub device_id: u32,
+}
+
+/// QEMU's `microvm` machine (`-machine microvm`).
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::Microvm;
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// if let Some(microvm) = unsafe { Microvm::detect() } {
+///     // SAFETY: the virtio-mmio transports are identity-mapped as device memory
+///     for device in unsafe { microvm.virtio_mmio_devices() } {
+///         println!("virtio device {} at {:#x}", device.device_id, device.address);
+///     }
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Microvm {
+    _private: (),
+}
+
+impl Microvm {
+    /// Returns the `microvm` machine, if [`machine_type`] reports it: with the `std`
+    /// feature via the SMBIOS product name, otherwise via fw_cfg without PCI.
+    ///
+    /// # Safety
+    /// See [`machine_type`].
+    pub unsafe fn detect() -> Option<Self> {
+        (machine_type() == Some(MachineType::Microvm)).then_some(Self { _private: () })
+    }
+
+    /// Returns the physical address of the virtio-mmio transport `slot`, whether or not
+    /// a device is attached to it.
+    pub const fn virtio_mmio_address(slot: u8) -> u64 {
+        VIRTIO_MMIO_BASE + slot as u64 * VIRTIO_MMIO_SIZE
+    }
+
+    /// Returns an iterator over the virtio-mmio transports with an attached device.
+    ///
+    /// # Safety
+    /// The [`MICROVM_VIRTIO_MMIO_SLOTS`] transports, i.e. 12 KiB starting at
+    /// `0xfeb0_0000`, must be identity-mapped as device memory.
+    pub unsafe fn virtio_mmio_devices(&self) -> impl Iterator<Item = VirtioMmioDevice> {
+        (0..MICROVM_VIRTIO_MMIO_SLOTS as u8).filter_map(|slot| {
+            let address = Self::virtio_mmio_address(slot);
+            let read = |offset: usize| {
+                // SAFETY: guaranteed by the caller of `virtio_mmio_devices`
+                unsafe {
+                    (address as usize as *const u8)
+                        .add(offset)
+                        .cast::<u32>()
+                        .read_volatile()
+                }
+            };
+            if read(0) != VIRTIO_MMIO_MAGIC {
+                return None;
+            }
+            let device_id = read(VIRTIO_MMIO_DEVICE_ID);
+            (device_id != 0).then_some(VirtioMmioDevice {
+                slot,
+                address,
+                device_id,
+            })
+        })
+    }
+}
@@ -0,0 +1,19 @@
+//! MSR primitives for x86/x86_64. Used by the probes that read model-specific registers.
+//!
+//! All functions require ring 0. Otherwise, and for MSRs that the CPU or the hypervisor
+//! doesn't implement, they raise a general protection fault.
+
+use core::arch::asm;
+
+/// Reads the model-specific register `msr`.
+pub(crate) unsafe fn rdmsr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    u64::from(high) << 32 | u64::from(low)
+}
+
+/// Writes `value` to the model-specific register `msr`.
+pub(crate) unsafe fn wrmsr(msr: u32, value: u64) {
+    let (low, high) = (value as u32, (value >> 32) as u32);
+    asm!("wrmsr", in("ecx") msr, in("eax") low, in("edx") high, options(nostack, preserves_flags));
+}
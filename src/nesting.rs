@@ -0,0 +1,139 @@
+//! Module for [`nesting`], a best-effort detection of nested virtualization, e.g.
+//! QEMU/KVM inside a Hyper-V or KVM guest. A nested hypervisor usually doesn't reveal
+//! the hypervisors below it, so the depth is a lower bound based on indirect evidence.
+
+use crate::hyperv::hyperv_info_with;
+use crate::interfaces::hypervisor_interfaces_with;
+use crate::{cpuid, timing, VmmKind};
+use raw_cpuid::{CpuId, CpuIdReader, CpuIdReaderNative};
+
+/// Hyper-V recommendation (leaf `0x4000_0004`, `eax`) that tells that Hyper-V itself
+/// runs nested within a Hyper-V partition.
+const HV_X64_HYPERV_NESTED: u32 = 1 << 12;
+/// TSC ticks of CPUID above which a nested VM exit is assumed. A VM exit to a nested
+/// hypervisor goes through the hypervisor below it, which costs several times as much as
+/// an ordinary VM exit of roughly 1000 to 2000 ticks.
+const NESTED_CPUID_THRESHOLD: u64 = 10_000;
+
+/// Information about nested virtualization. Result of [`nesting`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Nesting {
+    /// Best-effort lower bound of the number of hypervisors below the code: `0` on bare
+    /// metal, `1` in an ordinary virtual machine, and `2` or more if nesting was found.
+    pub depth: u8,
+    /// The hypervisor whose interface is visible first, i.e. the one in leaf
+    /// `0x4000_0000`, with KVM or Xen preferred over their emulation of Hyper-V.
+    pub outermost: Option<VmmKind>,
+    /// Whether the virtual machine exposes VMX or SVM, so that it can run another level
+    /// of virtual machines.
+    pub nested_capable: bool,
+}
+
+impl Nesting {
+    /// Returns if the code runs in a nested virtual machine.
+    pub const fn is_nested(&self) -> bool {
+        self.depth >= 2
+    }
+}
+
+/// Detects nested virtualization. In addition to [`nesting_with`], measures the latency
+/// of CPUID, which is dramatically higher if the VM exit goes through a nested
+/// hypervisor.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::nesting;
+///
+/// let nesting = nesting();
+/// if nesting.is_nested() {
+///     println!("nested in {:?}, don't trust the benchmark", nesting.outermost);
+/// }
+/// ```
+pub fn nesting() -> Nesting {
+    let mut nesting = nesting_with(&CpuIdReaderNative);
+    if nesting.depth == 1 {
+        let ticks = timing::min_cpuid_latency();
+        if ticks > NESTED_CPUID_THRESHOLD {
+            debug!("CPUID takes {} TSC ticks, assuming a nested VM.", ticks);
+            nesting.depth = 2;
+        }
+    }
+    nesting
+}
+
+/// Like [`nesting`] but only consults CPUID, through the given reader. The depth is
+/// `1` for a hypervisor and grows by one for each further VMM among the interfaces of
+/// [`crate::hypervisor_interfaces`] that isn't an emulation of Hyper-V by KVM or Xen.
+/// Hyper-V reports if it runs nested itself.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{nesting_with, VmmKind};
+///
+/// // KVM with Hyper-V enlightenments isn't nested
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x1, 0, 0, 0),
+///         0x1 => (0, 0, 1 << 31, 0),
+///         // "Microsoft Hv"
+///         0x4000_0000 => (0x4000_0005, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+///         // "KVMKVMKVM"
+///         0x4000_0100 => (0x4000_0101, 0x4b4d_564b, 0x564b_4d56, 0x0000_004d),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let nesting = nesting_with(&reader);
+/// assert_eq!(nesting.depth, 1);
+/// assert_eq!(nesting.outermost, Some(VmmKind::KvmOther));
+/// ```
+pub fn nesting_with<R: CpuIdReader>(reader: &R) -> Nesting {
+    let id = CpuId::with_cpuid_reader(reader.clone());
+    let nested_capable = id.get_feature_info().is_some_and(|info| info.has_vmx())
+        || id
+            .get_extended_processor_and_feature_identifiers()
+            .is_some_and(|info| info.has_svm());
+    let Some(outermost) = cpuid::vmm_with(reader) else {
+        return Nesting {
+            depth: 0,
+            outermost: None,
+            nested_capable,
+        };
+    };
+
+    let emulates_hyperv = matches!(outermost, VmmKind::KvmOther | VmmKind::Qemu | VmmKind::Xen);
+    let mut vmms = [None; 4];
+    let mut depth: u8 = 0;
+    for interface in hypervisor_interfaces_with(reader.clone()) {
+        let vmm = match interface.vmm {
+            VmmKind::HyperV if emulates_hyperv => outermost,
+            VmmKind::KvmOther | VmmKind::Qemu => VmmKind::KvmOther,
+            vmm => vmm,
+        };
+        if !vmms.contains(&Some(vmm)) {
+            if let Some(slot) = vmms.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(vmm);
+            }
+            depth = depth.saturating_add(1);
+        }
+    }
+    let mut depth = depth.max(1);
+    if hyperv_info_with(reader).is_some_and(|info| {
+        !info.emulated_by_kvm && info.recommendations & HV_X64_HYPERV_NESTED != 0
+    }) {
+        debug!("Hyper-V runs nested.");
+        depth = depth.max(2);
+    }
+    debug!("Nesting depth: {}", depth);
+    Nesting {
+        depth,
+        outermost: Some(outermost),
+        nested_capable,
+    }
+}
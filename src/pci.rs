@@ -0,0 +1,160 @@
+//! Access to the PCI configuration space, either via the legacy configuration mechanism
+//! #1 (I/O ports `0xcf8` and `0xcfc`, x86 only) or via memory-mapped ECAM. Used by the
+//! probes that look for emulated PCI devices.
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::io;
+
+/// I/O port of the configuration address register.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const CONFIG_ADDRESS: u16 = 0xcf8;
+/// I/O port of the configuration data register.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const CONFIG_DATA: u16 = 0xcfc;
+/// Vendor ID that reads back for absent devices.
+const VENDOR_ID_NONE: u16 = 0xffff;
+
+/// Location and identifiers of a PCI function.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PciDevice {
+    pub(crate) bus: u8,
+    pub(crate) device: u8,
+    pub(crate) function: u8,
+    pub(crate) vendor_id: u16,
+    pub(crate) device_id: u16,
+}
+
+/// How the configuration space is accessed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ConfigAccess {
+    /// Configuration mechanism #1 via I/O ports.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Ports,
+    /// Memory-mapped ECAM (PCIe enhanced configuration access mechanism) at `base`,
+    /// covering the buses `0..=last_bus`.
+    Ecam { base: *const u8, last_bus: u8 },
+}
+
+impl ConfigAccess {
+    /// Reads the double word at `offset` from the configuration space of a function.
+    ///
+    /// # Safety
+    /// For [`Self::Ports`], see [`io`]. For [`Self::Ecam`], the ECAM region of the buses
+    /// must be mapped and readable.
+    pub(crate) unsafe fn read(&self, bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+        match *self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Ports => read_config(bus, device, function, offset),
+            Self::Ecam { base, .. } => {
+                let offset = usize::from(bus) << 20
+                    | usize::from(device & 0x1f) << 15
+                    | usize::from(function & 0x7) << 12
+                    | usize::from(offset & 0xfc);
+                base.add(offset).cast::<u32>().read_volatile()
+            }
+        }
+    }
+
+    /// Enumerates all PCI functions by brute force. Yields nothing, if configuration
+    /// mechanism #1 isn't present, e.g. on machines without PCI.
+    ///
+    /// # Safety
+    /// See [`Self::read`]. The caller must make sure that nobody else accesses the
+    /// configuration space concurrently.
+    pub(crate) unsafe fn devices(self) -> impl Iterator<Item = PciDevice> {
+        let (present, last_bus) = match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Ports => (mechanism_present(), u8::MAX),
+            Self::Ecam { last_bus, .. } => (true, last_bus),
+        };
+        if !present {
+            debug!("PCI configuration mechanism #1 is not present.");
+        }
+        (0..=last_bus)
+            .filter(move |_| present)
+            .flat_map(|bus| (0..32).map(move |device| (bus, device)))
+            .flat_map(move |(bus, device)| {
+                // SAFETY: guaranteed by the caller of `devices`
+                let function_0 = unsafe { self.function(bus, device, 0) };
+                // the header type is only read for present devices, so that each empty
+                // slot costs a single read
+                let functions = match function_0 {
+                    // SAFETY: guaranteed by the caller of `devices`
+                    Some(_) if unsafe { self.read(bus, device, 0, 0xc) } & (1 << 23) != 0 => 8,
+                    Some(_) => 1,
+                    None => 0,
+                };
+                function_0
+                    .into_iter()
+                    .chain((1..functions).filter_map(move |function| {
+                        // SAFETY: guaranteed by the caller of `devices`
+                        unsafe { self.function(bus, device, function) }
+                    }))
+            })
+    }
+
+    /// Returns the function, if its vendor ID isn't [`VENDOR_ID_NONE`].
+    ///
+    /// # Safety
+    /// See [`Self::read`].
+    unsafe fn function(&self, bus: u8, device: u8, function: u8) -> Option<PciDevice> {
+        let id = self.read(bus, device, function, 0);
+        let vendor_id = id as u16;
+        (vendor_id != VENDOR_ID_NONE).then_some(PciDevice {
+            bus,
+            device,
+            function,
+            vendor_id,
+            device_id: (id >> 16) as u16,
+        })
+    }
+}
+
+/// Reads the double word at `offset` from the configuration space of a function via
+/// configuration mechanism #1.
+///
+/// # Safety
+/// See [`io`].
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) unsafe fn read_config(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    let address = 1 << 31
+        | u32::from(bus) << 16
+        | u32::from(device & 0x1f) << 11
+        | u32::from(function & 0x7) << 8
+        | u32::from(offset & 0xfc);
+    io::outl(CONFIG_ADDRESS, address);
+    io::inl(CONFIG_DATA)
+}
+
+/// Returns if configuration mechanism #1 is present, i.e. the address register keeps
+/// the enable bit.
+///
+/// # Safety
+/// See [`io`].
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) unsafe fn mechanism_present() -> bool {
+    let previous = io::inl(CONFIG_ADDRESS);
+    io::outl(CONFIG_ADDRESS, 1 << 31);
+    let present = io::inl(CONFIG_ADDRESS) == 1 << 31;
+    io::outl(CONFIG_ADDRESS, previous);
+    present
+}
+
+/// Enumerates all PCI functions via configuration mechanism #1. See
+/// [`ConfigAccess::devices`].
+///
+/// # Safety
+/// See [`ConfigAccess::devices`].
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) unsafe fn devices() -> impl Iterator<Item = PciDevice> {
+    ConfigAccess::Ports.devices()
+}
+
+/// Returns the first function with the given vendor and device ID.
+///
+/// # Safety
+/// See [`devices`].
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) unsafe fn find_device(vendor_id: u16, device_id: u16) -> Option<PciDevice> {
+    devices().find(|dev| dev.vendor_id == vendor_id && dev.device_id == device_id)
+}
@@ -0,0 +1,77 @@
+//! Module for [`PitProbe`], a heuristic that checks the refresh bit of the PIT, as seen in
+//! the system control port `0x61`. On real chipsets, the bit toggles every 15 µs. QEMU's
+//! PC speaker emulation toggles it on every read instead, but only handles the port with
+//! TCG or a userspace PIT (`-machine kernel-irqchip=off`). With KVM's in-kernel PIT, KVM
+//! answers the port with a time-based bit like real hardware, so the quirk tells QEMU
+//! apart only in the former cases and is at most a hint.
+
+use crate::{io, Probe, ProbeResult, Weights};
+
+/// I/O port of the system control port B, with the PIT channel 2 and refresh status.
+const SYSTEM_CONTROL_PORT_B: u16 = 0x61;
+/// Refresh request toggle.
+const REFRESH_BIT: u8 = 1 << 4;
+/// Number of consecutive reads.
+const READS: usize = 8;
+
+/// Probe that reads port `0x61` several times in a row.
+///
+/// Returns [`ProbeResult::Hint`], if the refresh bit toggles on every read, and
+/// [`ProbeResult::Inconclusive`] otherwise, which includes QEMU with KVM's in-kernel PIT.
+/// Contributes half the weight of an ordinary hint, as a slow port access on real
+/// hardware can coincide with the refresh period.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{Detector, PitProbe};
+///
+/// // SAFETY: runs in ring 0
+/// let probe = unsafe { PitProbe::new() };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct PitProbe {
+    _private: (),
+}
+
+impl PitProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level. The probe only reads port `0x61`, which has no
+    /// side effects on real chipsets.
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for PitProbe {
+    fn name(&self) -> &'static str {
+        "pit"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `PitProbe::new`
+        let mut previous = unsafe { io::inb(SYSTEM_CONTROL_PORT_B) } & REFRESH_BIT;
+        let toggles_on_every_read = (0..READS).all(|_| {
+            // SAFETY: guaranteed by the caller of `PitProbe::new`
+            let current = unsafe { io::inb(SYSTEM_CONTROL_PORT_B) } & REFRESH_BIT;
+            let toggled = current != previous;
+            previous = current;
+            toggled
+        });
+        if toggles_on_every_read {
+            debug!("PIT refresh bit toggles on every read of port 0x61.");
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+
+    fn weight(&self, result: ProbeResult, weights: &Weights) -> i32 {
+        weights.probe(result) / 2
+    }
+}
@@ -0,0 +1,26 @@
+//! Module for [`DetectionPolicy`].
+
+/// Policy that decides how strict the verdict of a [`crate::Detector`] is.
+/// The confidence score ([`crate::DetectionReport::confidence`]) is not affected
+/// by the policy, as it only reflects the gathered evidence.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum DetectionPolicy {
+    /// Only returns [`crate::QemuCertainty::VeryLikely`] on unambiguous QEMU signatures:
+    /// the QEMU hypervisor ID (TCG) or a [`crate::ProbeResult::Qemu`] of a probe whose
+    /// [`crate::Probe::unambiguous`] returns `true`, such as [`crate::FwCfgProbe`]. A CPU
+    /// brand string that mentions QEMU or a heuristic probe alone only results in
+    /// [`crate::QemuCertainty::Maybe`], as other hypervisors can produce them. Useful for
+    /// conservative gating logic, such as in CI.
+    Strict,
+    /// The default policy. A QEMU hypervisor ID, a CPU brand string that mentions QEMU,
+    /// or a [`crate::ProbeResult::Qemu`] result in [`crate::QemuCertainty::VeryLikely`].
+    #[default]
+    Balanced,
+    /// Like [`Self::Balanced`] but upgrades [`crate::QemuCertainty::Maybe`] under KVM
+    /// to [`crate::QemuCertainty::VeryLikely`], as QEMU is the most common VMM for KVM.
+    /// Useful for best-effort decisions, such as enabling debug logging.
+    Permissive,
+}
@@ -0,0 +1,125 @@
+//! Module for the [`Probe`] trait that lets users plug their own checks into a
+//! [`crate::Detector`].
+
+use crate::{VmIdentity, Weights};
+
+/// Result of a single [`Probe`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ProbeResult {
+    /// The probe found evidence that the code runs inside QEMU, for example a
+    /// QEMU-specific device or firmware signature.
+    Qemu,
+    /// The probe found a hint that the code runs inside a virtual machine, which
+    /// might be QEMU.
+    Hint,
+    /// The probe ran but found nothing that points towards or against QEMU.
+    Inconclusive,
+    /// The probe rules out that the code runs inside QEMU.
+    NotQemu,
+    /// The probe couldn't gather its signal, for example because the inspected
+    /// data source doesn't exist on this system.
+    Unavailable,
+    /// The probe failed while gathering its signal, for example because the hardware
+    /// responded unexpectedly. Like [`Self::Unavailable`], this doesn't influence the
+    /// verdict, but [`crate::Detector::try_detect`] reports it as
+    /// [`crate::DetectError::ProbeFaulted`].
+    Faulted,
+}
+
+/// A custom detection check that runs as part of a [`crate::Detector`], after the
+/// built-in CPUID checks.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, Probe, ProbeResult};
+///
+/// /// Site-specific check. A real probe would look at a magic fw_cfg file or similar.
+/// struct MyProbe;
+///
+/// impl Probe for MyProbe {
+///     fn name(&self) -> &'static str {
+///         "my-probe"
+///     }
+///
+///     fn run(&self) -> ProbeResult {
+///         ProbeResult::Inconclusive
+///     }
+/// }
+///
+/// let report = Detector::new(&[&MyProbe]).detect();
+/// assert_eq!(report.probes().next(), Some(("my-probe", ProbeResult::Inconclusive)));
+/// ```
+pub trait Probe {
+    /// Short, human-readable name of the probe. Used to identify the probe in the
+    /// [`crate::DetectionReport`].
+    fn name(&self) -> &'static str;
+
+    /// Executes the probe.
+    fn run(&self) -> ProbeResult;
+
+    /// Returns the contribution of `result` to the score of the [`crate::DetectionReport`].
+    /// Override this for probes whose signal is weaker or stronger than usual. Default:
+    /// the weight from `weights`, see [`Weights::probe`].
+    fn weight(&self, result: ProbeResult, weights: &Weights) -> i32 {
+        weights.probe(result)
+    }
+
+    /// Returns if a [`ProbeResult::Qemu`] of this probe is a signature that only QEMU
+    /// provides, such as fw_cfg, QEMU's PCI IDs, or QEMU's SMBIOS strings, as opposed to
+    /// a heuristic. Only such results let [`crate::DetectionPolicy::Strict`] return
+    /// [`crate::QemuCertainty::VeryLikely`]. Default: `false`.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::{DetectionPolicy, Detector, Probe, ProbeResult, QemuCertainty};
+    ///
+    /// /// Checks for a device of QEMU reported by the boot loader.
+    /// struct QemuDeviceProbe {
+    ///     unambiguous: bool,
+    /// }
+    ///
+    /// impl Probe for QemuDeviceProbe {
+    ///     fn name(&self) -> &'static str {
+    ///         "qemu-device"
+    ///     }
+    ///
+    ///     fn run(&self) -> ProbeResult {
+    ///         ProbeResult::Qemu
+    ///     }
+    ///
+    ///     fn unambiguous(&self) -> bool {
+    ///         self.unambiguous
+    ///     }
+    /// }
+    ///
+    /// for (unambiguous, certainty) in [
+    ///     (false, QemuCertainty::Maybe),
+    ///     (true, QemuCertainty::VeryLikely),
+    /// ] {
+    ///     let probe = QemuDeviceProbe { unambiguous };
+    ///     let report = Detector::builder()
+    ///         .cpuid(false)
+    ///         .policy(DetectionPolicy::Strict)
+    ///         .probes(&[&probe])
+    ///         .build()
+    ///         .detect();
+    ///     assert_eq!(report.certainty(), certainty);
+    /// }
+    /// ```
+    fn unambiguous(&self) -> bool {
+        false
+    }
+
+    /// Returns the identity of the virtual machine, if the probe's data source provides
+    /// one, e.g. the SMBIOS system UUID. Called after [`Self::run`]. The first identity
+    /// that a probe of a [`crate::Detector`] returns ends up in the
+    /// [`crate::DetectionReport`]. Default: `None`.
+    fn identity(&self) -> Option<VmIdentity<'_>> {
+        None
+    }
+}
@@ -0,0 +1,186 @@
+//! Module for [`Pvpanic`] and [`notify_panic`], which tell the host about a crash of the
+//! guest via QEMU's pvpanic device: the ISA variant (`-device pvpanic`) on I/O port
+//! `0x505` or the PCI variant (`-device pvpanic-pci`, `1b36:0011`). QEMU reports the
+//! event to the management software, e.g. as `GUEST_PANICKED` event of QMP, which can
+//! then collect a dump or restart the guest.
+
+use crate::debug_devices::{PVPANIC_FW_CFG_FILE, PVPANIC_PCI};
+use crate::{fw_cfg, io, pci};
+
+/// Default I/O port of the ISA pvpanic device (`ioport` property).
+pub const PVPANIC_ISA_PORT: u16 = 0x505;
+/// Offset of the first base address register in the configuration space.
+const BAR0_OFFSET: u8 = 0x10;
+/// Type bits of a base address register for a 64-bit memory BAR.
+const BAR_TYPE_64: u32 = 0b10 << 1;
+
+/// Event that the guest reports via pvpanic. The device returns the events it supports
+/// on reads, see [`Pvpanic::supports`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum PanicEvent {
+    /// The guest panicked (`PVPANIC_PANICKED`).
+    Panicked,
+    /// The guest loaded a crash kernel, e.g. via kdump, and the host shouldn't act on the
+    /// panic (`PVPANIC_CRASH_LOADED`).
+    CrashLoaded,
+    /// The guest shuts down regularly (`PVPANIC_SHUTDOWN`, since QEMU 8.2).
+    Shutdown,
+}
+
+impl PanicEvent {
+    /// Returns the bit of the event in the register of the device.
+    pub const fn bit(self) -> u8 {
+        match self {
+            Self::Panicked => 1 << 0,
+            Self::CrashLoaded => 1 << 1,
+            Self::Shutdown => 1 << 2,
+        }
+    }
+}
+
+/// A pvpanic device of QEMU. Result of [`Pvpanic::detect`].
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{PanicEvent, Pvpanic};
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started, and the BAR of the PCI
+/// // variant is identity-mapped
+/// if let Some(pvpanic) = unsafe { Pvpanic::detect() } {
+///     if unsafe { pvpanic.supports(PanicEvent::CrashLoaded) } {
+///         println!("{:?} can report a loaded crash kernel", pvpanic);
+///     }
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Pvpanic {
+    /// The ISA device on the given I/O port, usually [`PVPANIC_ISA_PORT`].
+    Isa {
+        /// The I/O port.
+        port: u16,
+    },
+    /// The PCI device with its register at the given physical address (BAR 0).
+    Pci {
+        /// Physical address of the register.
+        address: u64,
+    },
+}
+
+impl Pvpanic {
+    /// Looks for the ISA device via the fw_cfg file `etc/pvpanic-port`, which QEMU
+    /// provides for it, and then for the PCI device `1b36:0011` with an assigned BAR.
+    /// Returns `None`, if neither is present.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access fw_cfg or the PCI
+    /// configuration space while this runs.
+    pub unsafe fn detect() -> Option<Self> {
+        let mut port = [0; 2];
+        let pvpanic = if fw_cfg::read_file(PVPANIC_FW_CFG_FILE, &mut port).is_some() {
+            Some(Self::Isa {
+                port: u16::from_le_bytes(port),
+            })
+        } else {
+            pci_address().map(|address| Self::Pci { address })
+        };
+        debug!("pvpanic: {:?}", pvpanic);
+        pvpanic
+    }
+
+    /// Reads the events that the device supports, as bits of [`PanicEvent::bit`].
+    ///
+    /// # Safety
+    /// See [`Self::notify`].
+    pub unsafe fn supported_events(&self) -> u8 {
+        match *self {
+            Self::Isa { port } => io::inb(port),
+            Self::Pci { address } => (address as *const u8).read_volatile(),
+        }
+    }
+
+    /// Returns if the device supports `event`. QEMU before 8.2 doesn't support
+    /// [`PanicEvent::Shutdown`].
+    ///
+    /// # Safety
+    /// See [`Self::notify`].
+    pub unsafe fn supports(&self, event: PanicEvent) -> bool {
+        self.supported_events() & event.bit() != 0
+    }
+
+    /// Reports `event` to the host. Depending on its configuration, e.g.
+    /// `-action panic=pause`, QEMU stops or terminates the guest right away.
+    ///
+    /// # Safety
+    /// The device must exist, e.g. be returned by [`Self::detect`]. For [`Self::Isa`],
+    /// the caller must be allowed to access I/O ports. For [`Self::Pci`], the register
+    /// must be identity-mapped as device memory.
+    pub unsafe fn notify(&self, event: PanicEvent) {
+        match *self {
+            Self::Isa { port } => io::outb(port, event.bit()),
+            Self::Pci { address } => (address as *mut u8).write_volatile(event.bit()),
+        }
+    }
+}
+
+/// Reports `event` to the host via the pvpanic device that [`Pvpanic::detect`] finds,
+/// if it supports the event. Returns if the event was reported. Meant to be called from
+/// the panic handler of a kernel.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{notify_panic, PanicEvent};
+///
+/// // called by the `#[panic_handler]`
+/// fn on_panic() -> ! {
+///     // SAFETY: runs in ring 0, other CPUs are stopped, and the BAR of the PCI
+///     // variant is identity-mapped
+///     unsafe { notify_panic(PanicEvent::Panicked) };
+///     loop {
+///         core::hint::spin_loop();
+///     }
+/// }
+/// ```
+///
+/// # Safety
+/// See [`Pvpanic::detect`] and [`Pvpanic::notify`].
+pub unsafe fn notify_panic(event: PanicEvent) -> bool {
+    match Pvpanic::detect() {
+        Some(pvpanic) if pvpanic.supports(event) => {
+            pvpanic.notify(event);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Returns the physical address of BAR 0 of the PCI pvpanic device, if the device is
+/// present and the BAR is an assigned memory BAR.
+///
+/// # Safety
+/// See [`Pvpanic::detect`].
+unsafe fn pci_address() -> Option<u64> {
+    if !pci::mechanism_present() {
+        return None;
+    }
+    let device = pci::find_device(PVPANIC_PCI.0, PVPANIC_PCI.1)?;
+    let read = |offset| pci::read_config(device.bus, device.device, device.function, offset);
+    let bar = read(BAR0_OFFSET);
+    if bar & 1 != 0 {
+        // I/O BAR, which QEMU doesn't use for pvpanic
+        return None;
+    }
+    let mut address = u64::from(bar & !0xf);
+    if bar & 0b110 == BAR_TYPE_64 {
+        address |= u64::from(read(BAR0_OFFSET + 4)) << 32;
+    }
+    (address != 0).then_some(address)
+}
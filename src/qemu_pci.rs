@@ -0,0 +1,167 @@
+//! Module for [`QemuPciProbe`], which scans the PCI configuration space for devices that
+//! QEMU emulates. Unlike CPUID, the devices are also visible with TCG and without the
+//! hypervisor flag.
+
+use crate::pci::{ConfigAccess, PciDevice};
+use crate::{cpuid, Probe, ProbeResult};
+
+/// PCI vendor ID of the QEMU devices of Red Hat, such as the PCI bridges and pvpanic.
+const REDHAT_QEMU_VENDOR_ID: u16 = 0x1b36;
+/// PCI vendor ID and device ID of the standard VGA of QEMU, which Bochs emulates as well.
+const STD_VGA: (u16, u16) = (0x1234, 0x1111);
+/// PCI vendor IDs and device IDs of the host bridges of QEMU's `pc` (i440FX) and `q35`
+/// machines. Real chipsets and other VMMs, e.g. VirtualBox, use them as well.
+const HOST_BRIDGES: [((u16, u16), Chipset); 2] = [
+    ((0x8086, 0x1237), Chipset::I440fx),
+    ((0x8086, 0x29c0), Chipset::Q35),
+];
+/// Subsystem vendor ID and subsystem ID that QEMU assigns to its emulated PCI devices
+/// by default (Red Hat, "QEMU Virtual Machine").
+const QEMU_SUBSYSTEM: (u16, u16) = (0x1af4, 0x1100);
+/// Offset of the subsystem vendor ID and the subsystem ID in the configuration space.
+const SUBSYSTEM_OFFSET: u8 = 0x2c;
+
+/// The emulated chipset of an x86 machine, as identified by its host bridge.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Chipset {
+    /// Intel 440FX and PIIX, as in QEMU's `pc` machine.
+    I440fx,
+    /// Intel Q35 and ICH9, as in QEMU's `q35` machine.
+    Q35,
+}
+
+/// Probe that scans the PCI configuration space for the devices of QEMU.
+///
+/// Returns [`ProbeResult::Qemu`], if a Red Hat QEMU device (vendor `1b36`) is present,
+/// or the standard VGA (`1234:1111`) and the hypervisor flag is set. Bochs emulates the
+/// standard VGA as well but never sets the flag. Returns [`ProbeResult::Hint`] for the
+/// standard VGA without the flag. The host bridges of QEMU's `pc` and `q35` machines
+/// (`8086:1237` and `8086:29c0`) also exist on real hardware: they result in
+/// [`ProbeResult::Qemu`] only with QEMU's subsystem IDs (`1af4:1100`) and in
+/// [`ProbeResult::Hint`] otherwise. [`QemuPciProbe::chipset`] tells which of the
+/// machines it is. If none of the devices is present, returns
+/// [`ProbeResult::Inconclusive`].
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{Detector, QemuPciProbe};
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// let probe = unsafe { QemuPciProbe::new() };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?} on {:?}", report.certainty(), probe.chipset());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct QemuPciProbe {
+    access: ConfigAccess,
+}
+
+impl QemuPciProbe {
+    /// Creates a new probe that accesses the configuration space via the I/O ports
+    /// `0xcf8` and `0xcfc`.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access the PCI configuration
+    /// space while the probe runs. Otherwise, the probe raises a general protection fault
+    /// or corrupts concurrent configuration space accesses.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub const unsafe fn new() -> Self {
+        Self {
+            access: ConfigAccess::Ports,
+        }
+    }
+
+    /// Creates a new probe that accesses the configuration space via the ECAM region at
+    /// `base`, e.g. from the ACPI MCFG table or the device tree, for the buses
+    /// `0..=last_bus`.
+    ///
+    /// # Safety
+    /// The ECAM region of the buses, i.e. `(last_bus + 1) * 1 MiB` starting at `base`,
+    /// must be mapped as device memory and readable. Nobody else may access the PCI
+    /// configuration space while the probe runs.
+    pub const unsafe fn with_ecam(base: *const u8, last_bus: u8) -> Self {
+        Self {
+            access: ConfigAccess::Ecam { base, last_bus },
+        }
+    }
+
+    /// Returns the chipset, if the host bridge at `00:00.0` is the one of QEMU's `pc` or
+    /// `q35` machine. Doesn't check the subsystem IDs, as real chipsets use the same
+    /// host bridges.
+    pub fn chipset(&self) -> Option<Chipset> {
+        // SAFETY: guaranteed by the caller of `QemuPciProbe::new` or `with_ecam`
+        let id = unsafe { self.access.read(0, 0, 0, 0) };
+        chipset((id as u16, (id >> 16) as u16))
+    }
+
+    /// Returns if the function has QEMU's subsystem IDs.
+    fn has_qemu_subsystem(&self, device: &PciDevice) -> bool {
+        // SAFETY: guaranteed by the caller of `QemuPciProbe::new` or `with_ecam`
+        let subsystem = unsafe {
+            self.access
+                .read(device.bus, device.device, device.function, SUBSYSTEM_OFFSET)
+        };
+        (subsystem as u16, (subsystem >> 16) as u16) == QEMU_SUBSYSTEM
+    }
+}
+
+impl Probe for QemuPciProbe {
+    fn name(&self) -> &'static str {
+        "qemu-pci"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        let mut result = ProbeResult::Inconclusive;
+        // SAFETY: guaranteed by the caller of `QemuPciProbe::new` or `with_ecam`
+        for device in unsafe { self.access.devices() } {
+            let PciDevice {
+                bus,
+                device: slot,
+                function,
+                vendor_id,
+                device_id,
+            } = device;
+            let id = (vendor_id, device_id);
+            if vendor_id == REDHAT_QEMU_VENDOR_ID || (id == STD_VGA && cpuid::hypervisor_flag_set())
+            {
+                debug!(
+                    "QEMU PCI device {:?} found at {}:{}.{}.",
+                    id, bus, slot, function
+                );
+                return ProbeResult::Qemu;
+            }
+            if let Some(chipset) = chipset(id) {
+                if self.has_qemu_subsystem(&device) {
+                    debug!(
+                        "Host bridge of {:?} with QEMU's subsystem IDs found.",
+                        chipset
+                    );
+                    return ProbeResult::Qemu;
+                }
+                debug!("Host bridge of {:?} found.", chipset);
+                result = ProbeResult::Hint;
+            } else if id == STD_VGA {
+                debug!("PCI device {:?} of QEMU or another machine found.", id);
+                result = ProbeResult::Hint;
+            }
+        }
+        result
+    }
+}
+
+/// Returns the chipset of a host bridge with the given vendor ID and device ID.
+fn chipset(id: (u16, u16)) -> Option<Chipset> {
+    HOST_BRIDGES
+        .iter()
+        .find(|(host_bridge, _)| *host_bridge == id)
+        .map(|&(_, chipset)| chipset)
+}
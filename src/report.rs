@@ -0,0 +1,520 @@
+//! Module for [`DetectionReport`], the structured result of [`crate::detect`].
+
+use crate::fixed_str::FixedStr;
+use crate::{
+    Accelerator, InterfaceVersion, ManagementStack, ProbeResult, QemuCertainty, Uuid, VmIdentity,
+    VmmKind,
+};
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
+/// Length of the hypervisor vendor signature in CPUID leaf `0x4000_0000`.
+pub(crate) const HYPERVISOR_SIGNATURE_LEN: usize = 12;
+
+/// Length of the processor brand string in CPUID leaves `0x8000_0002..=0x8000_0004`.
+pub(crate) const BRAND_STRING_LEN: usize = 48;
+
+/// Maximum number of [`crate::Probe`] results that a [`DetectionReport`] stores. Results of
+/// further probes still contribute to the verdict but are not part of the report. With the
+/// `alloc` feature, the report stores all results and this limit doesn't apply.
+pub const MAX_REPORTED_PROBES: usize = 8;
+
+/// Maximum length in bytes of a [`crate::Probe::name`] that a [`DetectionReport`] stores.
+/// Longer names are truncated. With the `alloc` feature, names are stored completely and
+/// this limit doesn't apply.
+pub const MAX_PROBE_NAME_LEN: usize = 32;
+
+/// Maximum length in bytes of the serial number that a [`DetectionReport`] stores, see
+/// [`DetectionReport::identity`]. Longer serial numbers are truncated.
+pub const MAX_SERIAL_NUMBER_LEN: usize = 64;
+
+/// A single check that [`crate::detect`] performs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Check {
+    /// Checks if the Hypervisor-flag is set in CPUID leaf `0x1`.
+    HypervisorFlag,
+    /// Checks if the hypervisor ID in CPUID leaf `0x4000_0000` is the one from QEMU
+    /// (`TCGTCGTCGTCG`), i.e. QEMU runs without an accelerator.
+    HypervisorId,
+    /// Checks that the hypervisor signature in CPUID leaf `0x4000_0000` doesn't belong to
+    /// a VMM that can't be QEMU, such as VMware. Fails, if it does.
+    VmmSignature,
+    /// Checks if the processor brand string contains `QEMU`.
+    BrandString,
+}
+
+impl Check {
+    /// All checks in the order [`crate::detect`] executes them.
+    pub const ALL: [Self; 4] = [
+        Self::HypervisorFlag,
+        Self::HypervisorId,
+        Self::VmmSignature,
+        Self::BrandString,
+    ];
+
+    const fn index(self) -> usize {
+        match self {
+            Self::HypervisorFlag => 0,
+            Self::HypervisorId => 1,
+            Self::VmmSignature => 2,
+            Self::BrandString => 3,
+        }
+    }
+}
+
+/// Outcome of a single [`Check`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum CheckOutcome {
+    /// The check ran and its condition is fulfilled, i.e. it points towards QEMU.
+    Passed,
+    /// The check ran but its condition is not fulfilled.
+    Failed,
+    /// The check couldn't run, because the required CPUID leaf is not available.
+    Unavailable,
+}
+
+/// Result of [`crate::detect`]. Contains the final [`QemuCertainty`] and all evidence
+/// that led to it. This way, you don't have to re-run CPUID yourself to understand why
+/// the crate decided what it did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetectionReport {
+    pub(crate) certainty: QemuCertainty,
+    pub(crate) score: i32,
+    pub(crate) confidence: u8,
+    pub(crate) hypervisor_id: Option<[u8; HYPERVISOR_SIGNATURE_LEN]>,
+    pub(crate) hypervisor_max_leaf: Option<u32>,
+    pub(crate) interface_version: Option<InterfaceVersion>,
+    pub(crate) vmm: Option<VmmKind>,
+    pub(crate) accelerator: Option<Accelerator>,
+    pub(crate) brand_string: Option<FixedStr<BRAND_STRING_LEN>>,
+    pub(crate) management_stack: Option<ManagementStack>,
+    pub(crate) uuid: Option<Uuid>,
+    pub(crate) serial_number: Option<FixedStr<MAX_SERIAL_NUMBER_LEN>>,
+    pub(crate) checks: [Option<CheckOutcome>; Check::ALL.len()],
+    #[cfg(not(feature = "alloc"))]
+    pub(crate) probes: [Option<(FixedStr<MAX_PROBE_NAME_LEN>, ProbeResult)>; MAX_REPORTED_PROBES],
+    #[cfg(feature = "alloc")]
+    pub(crate) probes: Vec<(String, ProbeResult)>,
+}
+
+impl DetectionReport {
+    /// Creates an empty report where no check ran yet.
+    pub(crate) const fn new(certainty: QemuCertainty) -> Self {
+        Self {
+            certainty,
+            score: 0,
+            confidence: 0,
+            hypervisor_id: None,
+            hypervisor_max_leaf: None,
+            interface_version: None,
+            vmm: None,
+            accelerator: None,
+            brand_string: None,
+            management_stack: None,
+            uuid: None,
+            serial_number: None,
+            checks: [None; Check::ALL.len()],
+            #[cfg(not(feature = "alloc"))]
+            probes: [None; MAX_REPORTED_PROBES],
+            #[cfg(feature = "alloc")]
+            probes: Vec::new(),
+        }
+    }
+
+    /// Records the outcome of a check.
+    #[cfg_attr(
+        not(any(target_arch = "x86", target_arch = "x86_64")),
+        allow(dead_code)
+    )]
+    pub(crate) fn record(&mut self, check: Check, outcome: CheckOutcome) {
+        self.checks[check.index()] = Some(outcome);
+    }
+
+    /// Records the identity that a probe found. Fields that an earlier probe already
+    /// provided are kept.
+    pub(crate) fn record_identity(&mut self, identity: VmIdentity) {
+        self.uuid = self.uuid.or(identity.uuid);
+        if self.serial_number.is_none() {
+            self.serial_number = identity.serial_number.map(FixedStr::new);
+        }
+    }
+
+    /// Records the result of a probe. Silently drops the result if the report is full.
+    #[cfg(not(feature = "alloc"))]
+    pub(crate) fn record_probe(&mut self, name: &str, result: ProbeResult) {
+        if let Some(slot) = self.probes.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some((FixedStr::new(name), result));
+        }
+    }
+
+    /// Records the result of a probe.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn record_probe(&mut self, name: &str, result: ProbeResult) {
+        self.probes.push((String::from(name), result));
+    }
+
+    /// Returns the name and result of the probe at `index`.
+    #[cfg(not(feature = "alloc"))]
+    fn probe(&self, index: usize) -> Option<(&str, ProbeResult)> {
+        // probes are stored without gaps, so the first empty slot ends the list
+        let (name, result) = self.probes.get(index)?.as_ref()?;
+        Some((name.as_str(), *result))
+    }
+
+    /// Returns the name and result of the probe at `index`.
+    #[cfg(feature = "alloc")]
+    fn probe(&self, index: usize) -> Option<(&str, ProbeResult)> {
+        let (name, result) = self.probes.get(index)?;
+        Some((name.as_str(), *result))
+    }
+
+    /// Returns the final verdict.
+    pub fn certainty(&self) -> QemuCertainty {
+        self.certainty
+    }
+
+    /// Returns the raw score, i.e. the sum of the [`crate::Weights`] of all evidence. It is
+    /// not clamped and can be negative, see [`Self::confidence`].
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    /// Returns a confidence score in `0..=100` that the code runs inside QEMU. It is
+    /// composed from the weighted evidence (see [`crate::Detector`]) and is finer grained
+    /// than [`Self::certainty`], which is useful to define custom thresholds when multiple
+    /// signals are combined. It is [`Self::score`] clamped to `0..=100`.
+    pub fn confidence(&self) -> u8 {
+        self.confidence
+    }
+
+    /// Returns the hypervisor ID from CPUID leaf `0x4000_0000`, such as `KVMKVMKVM` or
+    /// `TCGTCGTCGTCG`, if a hypervisor info leaf is available. Trailing null bytes
+    /// are removed.
+    pub fn hypervisor_id(&self) -> Option<&str> {
+        self.hypervisor_id
+            .as_ref()
+            .map(|bytes| str_from_padded(bytes))
+    }
+
+    /// Returns the raw 12 byte vendor signature from CPUID leaf `0x4000_0000`, if a
+    /// hypervisor info leaf is available. See [`crate::hypervisor_signature`].
+    pub fn hypervisor_signature(&self) -> Option<[u8; HYPERVISOR_SIGNATURE_LEN]> {
+        self.hypervisor_id
+    }
+
+    /// Returns the highest hypervisor info leaf (`eax` of leaf `0x4000_0000`), if a
+    /// hypervisor info leaf is available. KVM before Linux 2.6.35 reports `0`, which
+    /// means `0x4000_0001`.
+    pub fn hypervisor_max_leaf(&self) -> Option<u32> {
+        self.hypervisor_max_leaf
+    }
+
+    /// Returns the version information of the hypervisor interface in leaf `0x4000_0000`,
+    /// if it is the one of KVM, Xen, or Hyper-V. With Hyper-V enlightenments, this is
+    /// the Hyper-V interface that KVM emulates, see [`crate::hypervisor_interfaces`] for
+    /// the interfaces behind it.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+    /// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+    /// use runs_inside_qemu::{detect_with, InterfaceVersion};
+    ///
+    /// // QEMU/KVM with `hv-relaxed`, which reports Hyper-V 6.2.9200 by default
+    /// let reader = |eax, _ecx| {
+    ///     let (eax, ebx, ecx, edx) = match eax {
+    ///         0x0 => (0x1, 0, 0, 0),
+    ///         0x1 => (0, 0, 1 << 31, 0),
+    ///         // "Microsoft Hv"
+    ///         0x4000_0000 => (0x4000_000b, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+    ///         // "Hv#1"
+    ///         0x4000_0001 => (0x3123_7648, 0, 0, 0),
+    ///         0x4000_0002 => (0x0000_23f0, 0x0006_0002, 0, 0),
+    ///         // "KVMKVMKVM"
+    ///         0x4000_0100 => (0x4000_0101, 0x4b4d_564b, 0x564b_4d56, 0x0000_004d),
+    ///         _ => (0, 0, 0, 0),
+    ///     };
+    ///     CpuIdResult { eax, ebx, ecx, edx }
+    /// };
+    /// let report = detect_with(reader);
+    /// assert_eq!(report.hypervisor_max_leaf(), Some(0x4000_000b));
+    /// let version = InterfaceVersion::HyperV {
+    ///     major: 6,
+    ///     minor: 2,
+    ///     build: 9200,
+    /// };
+    /// assert_eq!(report.interface_version(), Some(version));
+    /// # }
+    /// ```
+    pub fn interface_version(&self) -> Option<InterfaceVersion> {
+        self.interface_version
+    }
+
+    /// Returns the virtual machine monitor (VMM), if a hypervisor info leaf is available.
+    /// See [`crate::detect_hypervisor`]. VMMs that can't be QEMU, such as ACRN, fail the
+    /// [`Check::VmmSignature`].
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+    /// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+    /// use runs_inside_qemu::{detect_with, Check, CheckOutcome, QemuCertainty, VmmKind};
+    ///
+    /// let reader = |eax, _ecx| {
+    ///     let (eax, ebx, ecx, edx) = match eax {
+    ///         0x0 => (0x1, 0, 0, 0),
+    ///         0x1 => (0, 0, 1 << 31, 0),
+    ///         // "ACRNACRNACRN"
+    ///         0x4000_0000 => (0x4000_0010, 0x4e52_4341, 0x4e52_4341, 0x4e52_4341),
+    ///         _ => (0, 0, 0, 0),
+    ///     };
+    ///     CpuIdResult { eax, ebx, ecx, edx }
+    /// };
+    /// let report = detect_with(reader);
+    /// assert_eq!(report.vmm(), Some(VmmKind::Acrn));
+    /// assert_eq!(report.check(Check::VmmSignature), Some(CheckOutcome::Failed));
+    /// assert_eq!(report.certainty(), QemuCertainty::DefinitelyNot);
+    /// # }
+    /// ```
+    pub fn vmm(&self) -> Option<VmmKind> {
+        self.vmm
+    }
+
+    /// Returns the accelerator that QEMU uses, if the VMM is QEMU and the accelerator
+    /// can be told by CPUID. This is the result of [`crate::accelerator_with`] for the
+    /// reader of the detection, and agrees with [`Self::vmm`].
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+    /// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+    /// use runs_inside_qemu::{detect_with, Accelerator, VmmKind};
+    ///
+    /// // QEMU with HVF: no hypervisor signature, but a QEMU CPU model
+    /// let reader = |eax, _ecx| {
+    ///     let (eax, ebx, ecx, edx) = match eax {
+    ///         0x0 => (0x1, 0, 0, 0),
+    ///         0x1 => (0, 0, 1 << 31, 0),
+    ///         0x4000_0000 => (0x4000_0001, 0, 0, 0),
+    ///         0x8000_0000 => (0x8000_0004, 0, 0, 0),
+    ///         // "QEMU Virtual CPU version 2.5+"
+    ///         0x8000_0002 => (0x554d_4551, 0x7269_5620, 0x6c61_7574, 0x5550_4320),
+    ///         0x8000_0003 => (0x7265_7620, 0x6e6f_6973, 0x352e_3220, 0x0000_002b),
+    ///         _ => (0, 0, 0, 0),
+    ///     };
+    ///     CpuIdResult { eax, ebx, ecx, edx }
+    /// };
+    /// let report = detect_with(reader);
+    /// assert_eq!(report.vmm(), Some(VmmKind::Qemu));
+    /// assert_eq!(report.accelerator(), Some(Accelerator::Hvf));
+    /// # }
+    /// ```
+    ///
+    /// QEMU with WHPX reports the Hyper-V signature of the host, but a QEMU CPU model:
+    ///
+    /// ```rust
+    /// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+    /// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+    /// use runs_inside_qemu::{detect_with, Accelerator, QemuCertainty, VmmKind};
+    ///
+    /// let reader = |eax, _ecx| {
+    ///     let (eax, ebx, ecx, edx) = match eax {
+    ///         0x0 => (0x1, 0, 0, 0),
+    ///         0x1 => (0, 0, 1 << 31, 0),
+    ///         // "Microsoft Hv"
+    ///         0x4000_0000 => (0x4000_0006, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+    ///         0x8000_0000 => (0x8000_0004, 0, 0, 0),
+    ///         // "QEMU Virtual CPU version 2.5+"
+    ///         0x8000_0002 => (0x554d_4551, 0x7269_5620, 0x6c61_7574, 0x5550_4320),
+    ///         0x8000_0003 => (0x7265_7620, 0x6e6f_6973, 0x352e_3220, 0x0000_002b),
+    ///         _ => (0, 0, 0, 0),
+    ///     };
+    ///     CpuIdResult { eax, ebx, ecx, edx }
+    /// };
+    /// let report = detect_with(reader);
+    /// assert_eq!(report.vmm(), Some(VmmKind::Qemu));
+    /// assert_eq!(report.accelerator(), Some(Accelerator::Whpx));
+    /// assert_eq!(report.certainty(), QemuCertainty::VeryLikely);
+    /// # }
+    /// ```
+    pub fn accelerator(&self) -> Option<Accelerator> {
+        self.accelerator
+    }
+
+    /// Returns the processor brand string, for example `QEMU Virtual CPU version 2.5+`,
+    /// if it was read during detection.
+    pub fn brand_string(&self) -> Option<&str> {
+        self.brand_string.as_ref().map(FixedStr::as_str)
+    }
+
+    /// Returns the software that manages the virtual machine, if the detector got the DMI
+    /// strings via [`crate::DetectorBuilder::dmi`] and they name known management
+    /// software. See [`crate::management_stack`].
+    pub fn management_stack(&self) -> Option<ManagementStack> {
+        self.management_stack
+    }
+
+    /// Returns if the code runs in QEMU managed by UTM on macOS: the DMI strings name UTM,
+    /// QEMU uses HVF (see [`Self::accelerator`]), and the verdict is at least
+    /// [`QemuCertainty::Maybe`]. On aarch64, where the accelerator can't be told, the DMI
+    /// strings and the verdict suffice.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+    /// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+    /// use runs_inside_qemu::{Accelerator, Detector, ManagementStack};
+    ///
+    /// // QEMU with HVF: no hypervisor signature, but a QEMU CPU model
+    /// let reader = |eax, _ecx| {
+    ///     let (eax, ebx, ecx, edx) = match eax {
+    ///         0x0 => (0x1, 0, 0, 0),
+    ///         0x1 => (0, 0, 1 << 31, 0),
+    ///         0x4000_0000 => (0x4000_0001, 0, 0, 0),
+    ///         0x8000_0000 => (0x8000_0004, 0, 0, 0),
+    ///         // "QEMU Virtual CPU version 2.5+"
+    ///         0x8000_0002 => (0x554d_4551, 0x7269_5620, 0x6c61_7574, 0x5550_4320),
+    ///         0x8000_0003 => (0x7265_7620, 0x6e6f_6973, 0x352e_3220, 0x0000_002b),
+    ///         _ => (0, 0, 0, 0),
+    ///     };
+    ///     CpuIdResult { eax, ebx, ecx, edx }
+    /// };
+    /// let detector = Detector::builder().dmi("UTM", "UTM Virtual Machine").build();
+    /// let report = detector.detect_with(reader);
+    /// assert_eq!(report.accelerator(), Some(Accelerator::Hvf));
+    /// assert_eq!(report.management_stack(), Some(ManagementStack::Utm));
+    /// assert!(report.qemu_via_utm());
+    /// # }
+    /// ```
+    pub fn qemu_via_utm(&self) -> bool {
+        self.management_stack == Some(ManagementStack::Utm)
+            && self.certainty.is_maybe_or_very_likely()
+    }
+
+    /// Returns the identity of the virtual machine, as reported by the probes, e.g.
+    /// [`crate::SmbiosProbe`]. All fields are `None`, if no probe provided it.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::{Detector, Smbios, SmbiosProbe};
+    ///
+    /// # // type 1 (system) with serial number and UUID, followed by type 127
+    /// # let table: &[u8] = &[
+    /// #     0x01, 0x1b, 0x00, 0x01, 0x01, 0x00, 0x00, 0x02, 0x78, 0x56, 0x34, 0x12,
+    /// #     0xbc, 0x9a, 0xf0, 0xde, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0,
+    /// #     0x06, 0x00, 0x00, b'Q', b'E', b'M', b'U', 0x00, b'v', b'm', b'-', b'4',
+    /// #     b'2', 0x00, 0x00, 0x7f, 0x04, 0x00, 0x02, 0x00, 0x00,
+    /// # ];
+    /// let probe = SmbiosProbe::new(Smbios::new(table));
+    /// let report = Detector::new(&[&probe]).detect();
+    /// assert_eq!(report.identity().serial_number, Some("vm-42"));
+    /// assert!(report.identity().uuid.is_some());
+    /// ```
+    pub fn identity(&self) -> VmIdentity<'_> {
+        VmIdentity::new(self.uuid, self.serial_number.as_ref().map(FixedStr::as_str))
+    }
+
+    /// Returns the outcome of the given check or `None`, if the check didn't run
+    /// because an earlier check was already conclusive.
+    pub fn check(&self, check: Check) -> Option<CheckOutcome> {
+        self.checks[check.index()]
+    }
+
+    /// Returns an iterator over all checks that ran, in the order they ran.
+    pub fn checks(&self) -> impl Iterator<Item = (Check, CheckOutcome)> + '_ {
+        Check::ALL
+            .iter()
+            .filter_map(move |&check| self.check(check).map(|outcome| (check, outcome)))
+    }
+
+    /// Returns an iterator over the name and result of each [`crate::Probe`] that ran,
+    /// in the order they ran. Without the `alloc` feature, at most [`MAX_REPORTED_PROBES`]
+    /// entries are reported and names are truncated to [`MAX_PROBE_NAME_LEN`] bytes.
+    pub fn probes(&self) -> impl Iterator<Item = (&str, ProbeResult)> + '_ {
+        (0..).map_while(move |index| self.probe(index))
+    }
+
+    /// Returns an iterator over all evidence in the report: first the checks, then the
+    /// probes, each in the order they ran.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::{detect, EvidenceItem};
+    ///
+    /// for item in detect().evidence() {
+    ///     match item {
+    ///         EvidenceItem::Check(check, outcome) => println!("{:?}: {:?}", check, outcome),
+    ///         EvidenceItem::Probe(name, result) => println!("{}: {:?}", name, result),
+    ///         // future sources of evidence
+    ///         _ => {}
+    ///     }
+    /// }
+    /// ```
+    pub fn evidence(&self) -> Evidence<'_> {
+        Evidence {
+            report: self,
+            position: 0,
+        }
+    }
+}
+
+/// A single piece of evidence of a [`DetectionReport`]. New sources of evidence may be
+/// added in the future, hence the enum is `#[non_exhaustive]`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum EvidenceItem<'a> {
+    /// Outcome of a built-in [`Check`].
+    Check(Check, CheckOutcome),
+    /// Name and result of a [`crate::Probe`].
+    Probe(&'a str, ProbeResult),
+}
+
+/// Iterator over the evidence of a [`DetectionReport`]. See [`DetectionReport::evidence`].
+#[derive(Clone, Debug)]
+pub struct Evidence<'a> {
+    report: &'a DetectionReport,
+    /// Index into the checks followed by the probes.
+    position: usize,
+}
+
+impl<'a> Iterator for Evidence<'a> {
+    type Item = EvidenceItem<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let position = self.position;
+            self.position += 1;
+            if let Some(&check) = Check::ALL.get(position) {
+                if let Some(outcome) = self.report.check(check) {
+                    return Some(EvidenceItem::Check(check, outcome));
+                }
+            } else {
+                let (name, result) = self.report.probe(position - Check::ALL.len())?;
+                return Some(EvidenceItem::Probe(name, result));
+            }
+        }
+    }
+}
+
+/// Interprets a zero-padded byte buffer as string. Stops at the first null byte.
+/// Non-UTF-8 data results in an empty string.
+pub(crate) fn str_from_padded(bytes: &[u8]) -> &str {
+    let bytes = bytes.split(|&b| b == 0).next().unwrap_or(&[]);
+    core::str::from_utf8(bytes).unwrap_or("")
+}
@@ -0,0 +1,114 @@
+//! Module for [`MsrProbe`], which looks for KVM in model-specific registers. Needs the
+//! `ring0` feature, as the probe only works in the kernel. MSRs also reveal KVM if it
+//! hides its CPUID leaves, e.g. with QEMU's `-cpu ...,kvm=off`.
+
+use crate::kvm::KVM_FEATURE_CLOCKSOURCE2;
+use crate::{kvm_features, msr, Probe, ProbeResult};
+use raw_cpuid::CpuId;
+
+/// `MSR_KVM_SYSTEM_TIME_NEW`: the kvmclock MSR of KVM.
+const MSR_KVM_SYSTEM_TIME_NEW: u32 = 0x4b56_4d01;
+/// `IA32_VMX_BASIC`: basic VMX information, such as the VMCS revision.
+const MSR_IA32_VMX_BASIC: u32 = 0x480;
+/// VMCS revision that KVM reports to nested guests (`VMCS12_REVISION`).
+const KVM_VMCS12_REVISION: u32 = 0x11e5_7ed0;
+
+/// Reads an MSR and returns `None`, if the access faults. See [`MsrProbe::with_reader`].
+pub type MsrReadFn = fn(msr: u32) -> Option<u64>;
+
+/// Probe that looks for KVM in MSRs: the kvmclock MSR `MSR_KVM_SYSTEM_TIME_NEW` and the
+/// VMCS revision of KVM's nested VMX in `IA32_VMX_BASIC`.
+///
+/// Without a fault-tolerant reader (see [`Self::with_reader`]), the probe only reads
+/// MSRs that CPUID announces, so it doesn't find KVM with hidden CPUID leaves. Returns
+/// [`ProbeResult::Hint`], if it finds KVM, as other KVM-based VMMs use the same MSRs,
+/// and [`ProbeResult::Inconclusive`] otherwise.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{Detector, MsrProbe};
+///
+/// /// MSR read with an exception table entry, so that a #GP returns `None`.
+/// fn rdmsr_safe(msr: u32) -> Option<u64> {
+///     // kernel-specific
+///     # let _ = msr;
+///     # None
+/// }
+///
+/// // SAFETY: runs in ring 0
+/// let probe = unsafe { MsrProbe::with_reader(rdmsr_safe) };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct MsrProbe {
+    read_fn: Option<MsrReadFn>,
+}
+
+impl MsrProbe {
+    /// Creates a new probe that reads MSRs with `rdmsr`, but only the ones that CPUID
+    /// announces.
+    ///
+    /// # Safety
+    /// The caller must run in ring 0. Otherwise, `rdmsr` raises a general protection
+    /// fault.
+    pub const unsafe fn new() -> Self {
+        Self { read_fn: None }
+    }
+
+    /// Creates a new probe that reads MSRs via `read_fn`, which must handle faults, e.g.
+    /// with an exception table entry like `rdmsr_safe()` of Linux. The probe then also
+    /// reads MSRs that CPUID doesn't announce.
+    ///
+    /// # Safety
+    /// The caller must make sure that `read_fn` may be called, i.e. usually that it runs
+    /// in ring 0.
+    pub const unsafe fn with_reader(read_fn: MsrReadFn) -> Self {
+        Self {
+            read_fn: Some(read_fn),
+        }
+    }
+
+    /// Reads `msr`. Without a fault-tolerant reader, only reads announced MSRs.
+    fn read(&self, msr: u32, announced: bool) -> Option<u64> {
+        match self.read_fn {
+            Some(read_fn) => read_fn(msr),
+            // SAFETY: guaranteed by the caller of `MsrProbe::new`; the MSR exists
+            None if announced => Some(unsafe { msr::rdmsr(msr) }),
+            None => None,
+        }
+    }
+}
+
+impl Probe for MsrProbe {
+    fn name(&self) -> &'static str {
+        "msr"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let kvmclock_announced = kvm_features()
+            .map(|features| features.features & KVM_FEATURE_CLOCKSOURCE2 != 0)
+            .unwrap_or(false);
+        if self
+            .read(MSR_KVM_SYSTEM_TIME_NEW, kvmclock_announced)
+            .is_some()
+        {
+            debug!("kvmclock MSR of KVM is present.");
+            return ProbeResult::Hint;
+        }
+
+        let vmx_announced = CpuId::new()
+            .get_feature_info()
+            .map(|info| info.has_vmx())
+            .unwrap_or(false);
+        let vmcs_revision = self
+            .read(MSR_IA32_VMX_BASIC, vmx_announced)
+            .map(|vmx_basic| vmx_basic as u32 & 0x7fff_ffff);
+        if vmcs_revision == Some(KVM_VMCS12_REVISION) {
+            debug!("VMCS revision of the nested VMX of KVM.");
+            return ProbeResult::Hint;
+        }
+        ProbeResult::Inconclusive
+    }
+}
@@ -0,0 +1,161 @@
+//! riscv64 backend. As there is no CPUID on RISC-V, detection relies on the device tree
+//! (see [`crate::DeviceTreeProbe`]) and on the SBI base extension, which tells which
+//! firmware or hypervisor implements the supervisor binary interface.
+
+use crate::{Probe, ProbeResult};
+
+/// Extension ID of the SBI base extension.
+const SBI_EXT_BASE: usize = 0x10;
+/// Function ID of `sbi_get_impl_id`.
+const SBI_BASE_GET_IMPL_ID: usize = 1;
+/// Function ID of `sbi_get_impl_version`.
+const SBI_BASE_GET_IMPL_VERSION: usize = 2;
+/// Function ID of `sbi_get_mvendorid`.
+const SBI_BASE_GET_MVENDORID: usize = 4;
+/// Function ID of `sbi_get_marchid`.
+const SBI_BASE_GET_MARCHID: usize = 5;
+/// Function ID of `sbi_get_mimpid`.
+const SBI_BASE_GET_MIMPID: usize = 6;
+
+/// Implementation of the supervisor binary interface (SBI), as reported by
+/// `sbi_get_impl_id`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum SbiImplementation {
+    /// Berkeley Boot Loader.
+    Bbl,
+    /// OpenSBI. This is the default firmware of QEMU's `virt` machine.
+    OpenSbi,
+    /// Xvisor.
+    Xvisor,
+    /// KVM. The code runs as a guest, most likely driven by QEMU.
+    Kvm,
+    /// RustSBI.
+    RustSbi,
+    /// Diosix.
+    Diosix,
+    /// Coffer.
+    Coffer,
+    /// Xen Project.
+    Xen,
+    /// PolarFire Hart Software Services.
+    PolarFireHss,
+    /// coreboot.
+    Coreboot,
+    /// oreboot.
+    Oreboot,
+    /// bhyve.
+    Bhyve,
+    /// An implementation ID that this crate doesn't know.
+    Unknown(usize),
+}
+
+impl From<usize> for SbiImplementation {
+    fn from(id: usize) -> Self {
+        match id {
+            0 => Self::Bbl,
+            1 => Self::OpenSbi,
+            2 => Self::Xvisor,
+            3 => Self::Kvm,
+            4 => Self::RustSbi,
+            5 => Self::Diosix,
+            6 => Self::Coffer,
+            7 => Self::Xen,
+            8 => Self::PolarFireHss,
+            9 => Self::Coreboot,
+            10 => Self::Oreboot,
+            11 => Self::Bhyve,
+            id => Self::Unknown(id),
+        }
+    }
+}
+
+/// Information from the SBI base extension. Result of [`sbi_info`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SbiInfo {
+    /// The SBI implementation.
+    pub implementation: SbiImplementation,
+    /// Implementation-specific version.
+    pub implementation_version: usize,
+    /// Value of the `mvendorid` CSR (JEDEC vendor ID). QEMU's generic CPUs report `0`.
+    pub mvendorid: usize,
+    /// Value of the `marchid` CSR.
+    pub marchid: usize,
+    /// Value of the `mimpid` CSR.
+    pub mimpid: usize,
+}
+
+/// Queries the SBI base extension. Returns `None`, if the SBI implementation doesn't
+/// support the base extension (SBI specification < v0.2).
+///
+/// # Safety
+/// The code must run in S-mode (or VS-mode) on top of an SBI implementation, as
+/// `ecall` from U-mode traps into the operating system kernel.
+pub unsafe fn sbi_info() -> Option<SbiInfo> {
+    Some(SbiInfo {
+        implementation: sbi_call(SBI_EXT_BASE, SBI_BASE_GET_IMPL_ID)?.into(),
+        implementation_version: sbi_call(SBI_EXT_BASE, SBI_BASE_GET_IMPL_VERSION)?,
+        mvendorid: sbi_call(SBI_EXT_BASE, SBI_BASE_GET_MVENDORID)?,
+        marchid: sbi_call(SBI_EXT_BASE, SBI_BASE_GET_MARCHID)?,
+        mimpid: sbi_call(SBI_EXT_BASE, SBI_BASE_GET_MIMPID)?,
+    })
+}
+
+/// Issues an SBI call without arguments. Returns the value or `None` on error.
+unsafe fn sbi_call(extension: usize, function: usize) -> Option<usize> {
+    let (error, value): (isize, usize);
+    core::arch::asm!(
+        "ecall",
+        inlateout("a0") 0usize => error,
+        inlateout("a1") 0usize => value,
+        in("a6") function,
+        in("a7") extension,
+        options(nostack)
+    );
+    (error == 0).then_some(value)
+}
+
+/// Probe that queries the SBI implementation (see [`sbi_info`]).
+///
+/// Returns [`ProbeResult::Hint`], if the SBI is implemented by KVM, or if the CPU reports
+/// no JEDEC vendor ID (`mvendorid == 0`) as QEMU's generic CPUs do. Returns
+/// [`ProbeResult::Inconclusive`] otherwise and [`ProbeResult::Unavailable`], if the base
+/// extension is not supported.
+#[derive(Copy, Clone, Debug)]
+pub struct SbiProbe {
+    _private: (),
+}
+
+impl SbiProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// See [`sbi_info`].
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for SbiProbe {
+    fn name(&self) -> &'static str {
+        "sbi"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `SbiProbe::new`
+        let info = match unsafe { sbi_info() } {
+            Some(info) => info,
+            None => return ProbeResult::Unavailable,
+        };
+        debug!("SBI info: {:?}", info);
+        if info.implementation == SbiImplementation::Kvm || info.mvendorid == 0 {
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+}
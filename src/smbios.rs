@@ -0,0 +1,349 @@
+//! Module for [`Smbios`], a minimal reader for SMBIOS (DMI) tables, and [`SmbiosProbe`].
+//! QEMU fills the tables with its own strings, such as the system manufacturer `QEMU` and
+//! the product name `Standard PC (i440FX + PIIX, 1996)`, unless the user overrides them.
+//! Unlike CPUID, the tables also identify QEMU without a hypervisor leaf, e.g. with TCG
+//! and `-cpu ...,-hypervisor`.
+
+use crate::dmi::{self, FirmwareStrings};
+use crate::{Probe, ProbeResult, Uuid, VmIdentity};
+
+/// Anchor of the 32-bit entry point (SMBIOS 2.x).
+const ANCHOR_32: &[u8; 4] = b"_SM_";
+/// Anchor of the 64-bit entry point (SMBIOS 3.x).
+const ANCHOR_64: &[u8; 5] = b"_SM3_";
+/// Size of the 32-bit entry point.
+const ENTRY_POINT_32_LEN: usize = 0x1f;
+/// Size of the 64-bit entry point.
+const ENTRY_POINT_64_LEN: usize = 0x18;
+
+/// Structure type of the BIOS information.
+const TYPE_BIOS: u8 = 0;
+/// Structure type of the system information.
+const TYPE_SYSTEM: u8 = 1;
+/// Structure type of the chassis information.
+const TYPE_CHASSIS: u8 = 3;
+/// Structure type of the OEM strings.
+const TYPE_OEM_STRINGS: u8 = 11;
+/// Structure type that ends the table.
+const TYPE_END: u8 = 127;
+
+/// Read-only view of an SMBIOS structure table, as it is provided by the firmware.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::Smbios;
+///
+/// # // type 0 (BIOS) and type 1 (system) with QEMU's strings, followed by type 127
+/// # let table: &[u8] = &[
+/// #     0x00, 0x12, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+/// #     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, b'S', b'e', b'a', b'B', b'I', b'O',
+/// #     b'S', 0x00, b'1', b'.', b'1', b'6', 0x00, 0x00, 0x01, 0x08, 0x00, 0x01,
+/// #     0x01, 0x02, 0x00, 0x00, b'Q', b'E', b'M', b'U', 0x00, b'S', b't', b'a',
+/// #     b'n', b'd', b'a', b'r', b'd', b' ', b'P', b'C', b' ', b'(', b'Q', b'3',
+/// #     b'5', b' ', b'+', b' ', b'I', b'C', b'H', b'9', b',', b' ', b'2', b'0',
+/// #     b'0', b'9', b')', 0x00, 0x00, 0x7f, 0x04, 0x00, 0x02, 0x00, 0x00,
+/// # ];
+/// let smbios = Smbios::new(table);
+/// assert_eq!(smbios.bios_vendor(), Some("SeaBIOS"));
+/// assert_eq!(smbios.sys_vendor(), Some("QEMU"));
+/// assert_eq!(smbios.product_name(), Some("Standard PC (Q35 + ICH9, 2009)"));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Smbios<'a> {
+    table: &'a [u8],
+}
+
+impl<'a> Smbios<'a> {
+    /// Creates a view of the SMBIOS structure table in `table`. Truncated structures at
+    /// the end are ignored.
+    pub const fn new(table: &'a [u8]) -> Self {
+        Self { table }
+    }
+
+    /// Creates a view of the SMBIOS structure table that the entry point at `ptr`
+    /// references. Supports the 32-bit (`_SM_`) and the 64-bit (`_SM3_`) entry point.
+    /// Returns `None`, if there is no valid entry point at `ptr`.
+    ///
+    /// # Safety
+    /// `ptr` must point to at least 32 readable bytes. If they contain a valid entry
+    /// point, the structure table must be readable at the physical address from the entry
+    /// point, i.e. physical memory must be identity-mapped, and stay unchanged for `'a`.
+    pub unsafe fn from_entry_point(ptr: *const u8) -> Option<Self> {
+        let entry_point = core::slice::from_raw_parts(ptr, ENTRY_POINT_32_LEN + 1);
+        let (address, len) = if entry_point.starts_with(ANCHOR_64) {
+            let entry_point = entry_point.get(..ENTRY_POINT_64_LEN)?;
+            if !checksum_valid(entry_point) {
+                return None;
+            }
+            (
+                read_u64(entry_point, 0x10)?,
+                read_u32(entry_point, 0x0c)? as usize,
+            )
+        } else if entry_point.starts_with(ANCHOR_32) {
+            let entry_point = entry_point.get(..ENTRY_POINT_32_LEN)?;
+            if !checksum_valid(entry_point) {
+                return None;
+            }
+            (
+                u64::from(read_u32(entry_point, 0x18)?),
+                usize::from(read_u16(entry_point, 0x16)?),
+            )
+        } else {
+            return None;
+        };
+        let address = usize::try_from(address).ok()?;
+        Some(Self::new(core::slice::from_raw_parts(
+            address as *const u8,
+            len,
+        )))
+    }
+
+    /// Returns the BIOS vendor, e.g. `SeaBIOS` (type 0).
+    pub fn bios_vendor(&self) -> Option<&'a str> {
+        self.string(TYPE_BIOS, 0x04)
+    }
+
+    /// Returns the BIOS version (type 0).
+    pub fn bios_version(&self) -> Option<&'a str> {
+        self.string(TYPE_BIOS, 0x05)
+    }
+
+    /// Returns the system manufacturer, e.g. `QEMU` (type 1). This is `sys_vendor` in
+    /// the DMI sysfs interface of Linux.
+    pub fn sys_vendor(&self) -> Option<&'a str> {
+        self.string(TYPE_SYSTEM, 0x04)
+    }
+
+    /// Returns the system product name, e.g. `Standard PC (i440FX + PIIX, 1996)` (type 1).
+    pub fn product_name(&self) -> Option<&'a str> {
+        self.string(TYPE_SYSTEM, 0x05)
+    }
+
+    /// Returns the system version (type 1). QEMU reports its versioned machine type,
+    /// e.g. `pc-q35-8.2`.
+    pub fn system_version(&self) -> Option<&'a str> {
+        self.string(TYPE_SYSTEM, 0x06)
+    }
+
+    /// Returns the system serial number (type 1), e.g. from `-smbios type=1,serial=...`.
+    pub fn serial_number(&self) -> Option<&'a str> {
+        self.string(TYPE_SYSTEM, 0x07)
+    }
+
+    /// Returns the system UUID (type 1), e.g. from `-uuid`. Returns `None`, if the
+    /// structure is too short or the UUID is not present (all bytes `0x00`) or not set
+    /// (all bytes `0xff`). The UUID is decoded as of SMBIOS 2.6, which QEMU implements.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::Smbios;
+    ///
+    /// # // type 1 (system) with serial number and UUID, followed by type 127
+    /// # let table: &[u8] = &[
+    /// #     0x01, 0x1b, 0x00, 0x01, 0x01, 0x00, 0x00, 0x02, 0x78, 0x56, 0x34, 0x12,
+    /// #     0xbc, 0x9a, 0xf0, 0xde, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0,
+    /// #     0x06, 0x00, 0x00, b'Q', b'E', b'M', b'U', 0x00, b'v', b'm', b'-', b'4',
+    /// #     b'2', 0x00, 0x00, 0x7f, 0x04, 0x00, 0x02, 0x00, 0x00,
+    /// # ];
+    /// let smbios = Smbios::new(table);
+    /// let uuid = smbios.system_uuid().unwrap();
+    /// assert_eq!(uuid.to_string(), "12345678-9abc-def0-1234-56789abcdef0");
+    /// assert_eq!(smbios.serial_number(), Some("vm-42"));
+    /// ```
+    pub fn system_uuid(&self) -> Option<Uuid> {
+        let structure = self
+            .structures()
+            .find(|structure| structure.ty == TYPE_SYSTEM)?;
+        let bytes: [u8; 16] = structure.formatted.get(0x08..0x18)?.try_into().ok()?;
+        if bytes.iter().all(|&b| b == 0x00) || bytes.iter().all(|&b| b == 0xff) {
+            return None;
+        }
+        Some(Uuid::from_guid_bytes(bytes))
+    }
+
+    /// Returns the chassis manufacturer (type 3).
+    pub fn chassis_vendor(&self) -> Option<&'a str> {
+        self.string(TYPE_CHASSIS, 0x04)
+    }
+
+    /// Returns the chassis asset tag (type 3).
+    pub fn chassis_asset_tag(&self) -> Option<&'a str> {
+        self.string(TYPE_CHASSIS, 0x08)
+    }
+
+    /// Returns an iterator over the OEM strings of all type 11 structures, e.g. from
+    /// `-smbios type=11,value=...`. Empty strings and strings that aren't valid UTF-8
+    /// are skipped.
+    pub fn oem_strings(&self) -> impl Iterator<Item = &'a str> {
+        self.structures()
+            .filter(|structure| structure.ty == TYPE_OEM_STRINGS)
+            .flat_map(|structure| structure.strings.split(|&b| b == 0))
+            .filter_map(|string| core::str::from_utf8(string).ok())
+            .map(str::trim)
+            .filter(|string| !string.is_empty())
+    }
+
+    /// Returns an iterator over the OEM strings (see [`Self::oem_strings`]) of the form
+    /// `key=value`, split at the first `=`. Orchestration software uses them to pass
+    /// configuration into the guest, e.g. systemd credentials as
+    /// `io.systemd.credential:name=value`. Strings without `=` are skipped.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::Smbios;
+    ///
+    /// # // type 11 (OEM strings) from -smbios type=11,value=role=web,value=zone=eu-1
+    /// # let table: &[u8] = &[
+    /// #     0x0b, 0x05, 0x00, 0x01, 0x02, b'r', b'o', b'l', b'e', b'=', b'w', b'e',
+    /// #     b'b', 0x00, b'z', b'o', b'n', b'e', b'=', b'e', b'u', b'-', b'1', 0x00,
+    /// #     0x00, 0x7f, 0x04, 0x00, 0x02, 0x00, 0x00,
+    /// # ];
+    /// let smbios = Smbios::new(table);
+    /// let mut values = smbios.oem_key_values();
+    /// assert_eq!(values.next(), Some(("role", "web")));
+    /// assert_eq!(values.next(), Some(("zone", "eu-1")));
+    /// assert_eq!(values.next(), None);
+    /// ```
+    pub fn oem_key_values(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.oem_strings()
+            .filter_map(|string| string.split_once('='))
+    }
+
+    /// Returns the string that the byte at `offset` of the first structure of type `ty`
+    /// references. Empty strings are reported as `None`.
+    fn string(&self, ty: u8, offset: usize) -> Option<&'a str> {
+        let structure = self.structures().find(|structure| structure.ty == ty)?;
+        let index = *structure.formatted.get(offset)?;
+        if index == 0 {
+            return None;
+        }
+        structure
+            .strings
+            .split(|&b| b == 0)
+            .nth(usize::from(index) - 1)
+            .and_then(|string| core::str::from_utf8(string).ok())
+            .map(str::trim)
+            .filter(|string| !string.is_empty())
+    }
+
+    /// Returns an iterator over all structures up to the end-of-table structure.
+    fn structures(&self) -> Structures<'a> {
+        Structures {
+            table: self.table,
+            offset: 0,
+        }
+    }
+}
+
+/// A single SMBIOS structure.
+#[derive(Copy, Clone, Debug)]
+struct Structure<'a> {
+    ty: u8,
+    /// The formatted area, including the header.
+    formatted: &'a [u8],
+    /// The string set without the terminating double null byte.
+    strings: &'a [u8],
+}
+
+/// Iterator over the structures of an SMBIOS table.
+#[derive(Clone, Debug)]
+struct Structures<'a> {
+    table: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Structures<'a> {
+    type Item = Structure<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.table.get(self.offset..)?;
+        let ty = *rest.first()?;
+        let len = usize::from(*rest.get(1)?);
+        if ty == TYPE_END || len < 4 {
+            return None;
+        }
+        let formatted = rest.get(..len)?;
+        let strings_len = rest
+            .get(len..)?
+            .windows(2)
+            .position(|window| window == [0, 0])?;
+        self.offset += len + strings_len + 2;
+        Some(Structure {
+            ty,
+            formatted,
+            strings: &rest[len..len + strings_len],
+        })
+    }
+}
+
+/// Probe that checks the SMBIOS tables for the strings of QEMU or of other VMMs.
+///
+/// Returns [`ProbeResult::Qemu`] for QEMU's system manufacturer, product name, or chassis
+/// vendor, [`ProbeResult::NotQemu`] for the strings of a VMM that can't be QEMU (see
+/// [`crate::vmm_from_dmi`]), and [`ProbeResult::Hint`] if only the BIOS is SeaBIOS or
+/// Bochs, which isn't specific to QEMU. Otherwise, returns [`ProbeResult::Inconclusive`].
+///
+/// Reports the system UUID and serial number as [`VmIdentity`], see
+/// [`crate::DetectionReport::identity`].
+#[derive(Copy, Clone, Debug)]
+pub struct SmbiosProbe<'a> {
+    smbios: Smbios<'a>,
+}
+
+impl<'a> SmbiosProbe<'a> {
+    /// Creates a new probe that inspects the given SMBIOS tables.
+    pub const fn new(smbios: Smbios<'a>) -> Self {
+        Self { smbios }
+    }
+}
+
+impl Probe for SmbiosProbe<'_> {
+    fn name(&self) -> &'static str {
+        "smbios"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        dmi::firmware_strings_result(&FirmwareStrings {
+            sys_vendor: self.smbios.sys_vendor().unwrap_or(""),
+            product_name: self.smbios.product_name().unwrap_or(""),
+            bios_vendor: self.smbios.bios_vendor().unwrap_or(""),
+            chassis_vendor: self.smbios.chassis_vendor().unwrap_or(""),
+        })
+    }
+
+    fn identity(&self) -> Option<VmIdentity<'_>> {
+        let identity = VmIdentity::new(self.smbios.system_uuid(), self.smbios.serial_number());
+        (identity != VmIdentity::default()).then_some(identity)
+    }
+}
+
+/// Returns if the bytes of an entry point sum up to zero.
+fn checksum_valid(entry_point: &[u8]) -> bool {
+    entry_point.iter().fold(0_u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+/// Reads a little-endian `u16` at `offset`.
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let bytes = bytes.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads a little-endian `u64` at `offset`.
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let low = read_u32(bytes, offset)?;
+    let high = read_u32(bytes, offset.checked_add(4)?)?;
+    Some(u64::from(high) << 32 | u64::from(low))
+}
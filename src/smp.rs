@@ -0,0 +1,69 @@
+//! Module for [`init_bsp`] and [`is_bsp`], which help multi-core early boot code: the
+//! bootstrap processor (BSP) executes the checks once, and the application processors
+//! (APs) read the cached result via [`crate::runs_inside_qemu_irqsafe`] instead of
+//! executing all checks again. Each CPUID instruction of the checks causes a VM exit.
+
+use crate::{cache, QemuCertainty};
+use core::sync::atomic::{AtomicU32, Ordering};
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Leaf with the initial APIC ID in `ebx[31:24]`.
+const FEATURE_INFO_LEAF: u32 = 0x1;
+/// Leaf with the x2APIC ID in `edx`.
+const EXTENDED_TOPOLOGY_LEAF: u32 = 0xb;
+
+/// Marker that the APIC ID of the BSP is not recorded yet.
+const UNKNOWN_APIC_ID: u32 = u32::MAX;
+
+/// APIC ID of the CPU that called [`init_bsp`], or [`UNKNOWN_APIC_ID`].
+static BSP_APIC_ID: AtomicU32 = AtomicU32::new(UNKNOWN_APIC_ID);
+
+/// Executes the checks like [`crate::init`] and records the APIC ID of the calling CPU as
+/// the one of the BSP. Call this on the BSP before the APs are started. The APs then get
+/// the result from [`crate::runs_inside_qemu_irqsafe`] and can tell via [`is_bsp`] that
+/// they are not the BSP.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{init_bsp, is_bsp, runs_inside_qemu_irqsafe};
+///
+/// // on the BSP, before the APs are started
+/// let certainty = init_bsp();
+/// assert_eq!(is_bsp(), Some(true));
+///
+/// // on each AP: no CPUID checks
+/// assert_eq!(runs_inside_qemu_irqsafe(), certainty);
+/// ```
+pub fn init_bsp() -> QemuCertainty {
+    if let Some(apic_id) = apic_id() {
+        BSP_APIC_ID.store(apic_id, Ordering::Relaxed);
+    }
+    cache::init()
+}
+
+/// Returns if the calling CPU is the BSP, i.e. its APIC ID is the one that [`init_bsp`]
+/// recorded. Returns `None`, if [`init_bsp`] didn't run yet or CPUID doesn't report the
+/// APIC ID. Executes at most three CPUID instructions.
+pub fn is_bsp() -> Option<bool> {
+    let bsp_apic_id = bsp_apic_id()?;
+    Some(apic_id()? == bsp_apic_id)
+}
+
+/// Returns the APIC ID of the BSP, if [`init_bsp`] ran.
+pub fn bsp_apic_id() -> Option<u32> {
+    Some(BSP_APIC_ID.load(Ordering::Relaxed)).filter(|&apic_id| apic_id != UNKNOWN_APIC_ID)
+}
+
+/// Returns the APIC ID of the calling CPU, see [`crate::GuestTopology::apic_id`].
+fn apic_id() -> Option<u32> {
+    let reader = CpuIdReaderNative;
+    let max_leaf = reader.cpuid1(0).eax;
+    if max_leaf >= EXTENDED_TOPOLOGY_LEAF {
+        let leaf = reader.cpuid2(EXTENDED_TOPOLOGY_LEAF, 0);
+        if leaf.ebx & 0xffff != 0 {
+            return Some(leaf.edx);
+        }
+    }
+    (max_leaf >= FEATURE_INFO_LEAF).then(|| reader.cpuid1(FEATURE_INFO_LEAF).ebx >> 24)
+}
@@ -0,0 +1,61 @@
+//! Module for [`SysfsDmiProbe`], which reads the DMI strings from the sysfs of Linux.
+//! Needs the `std` feature. Unlike CPUID, the strings are also available if the kernel
+//! masks or traps CPUID for user space (CPUID faulting).
+
+use crate::dmi::{self, FirmwareStrings};
+use crate::{Probe, ProbeResult};
+use std::fs;
+use std::string::String;
+
+/// Directory with one file per DMI string.
+const SYSFS_DMI_DIR: &str = "/sys/class/dmi/id";
+
+/// Probe that reads `sys_vendor`, `product_name`, `bios_vendor`, and `chassis_vendor`
+/// from `/sys/class/dmi/id/`. Files that don't exist or aren't readable count as empty.
+///
+/// Returns [`ProbeResult::Qemu`] for QEMU's system manufacturer, product name, or chassis
+/// vendor, [`ProbeResult::NotQemu`] for the strings of a VMM that can't be QEMU (see
+/// [`crate::vmm_from_dmi`]), [`ProbeResult::Hint`] if only the BIOS is SeaBIOS or Bochs,
+/// and [`ProbeResult::Inconclusive`] otherwise, e.g. on systems without DMI.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, SysfsDmiProbe};
+///
+/// let probe = SysfsDmiProbe;
+/// let report = Detector::builder().cpuid(false).probes(&[&probe]).build().detect();
+/// if report.certainty().is_very_likely() {
+///     println!("QEMU");
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SysfsDmiProbe;
+
+impl Probe for SysfsDmiProbe {
+    fn name(&self) -> &'static str {
+        "sysfs-dmi"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        let sys_vendor = read_dmi_string("sys_vendor");
+        let product_name = read_dmi_string("product_name");
+        let bios_vendor = read_dmi_string("bios_vendor");
+        let chassis_vendor = read_dmi_string("chassis_vendor");
+        dmi::firmware_strings_result(&FirmwareStrings {
+            sys_vendor: &sys_vendor,
+            product_name: &product_name,
+            bios_vendor: &bios_vendor,
+            chassis_vendor: &chassis_vendor,
+        })
+    }
+}
+
+/// Reads the DMI string `name` from sysfs. Returns an empty string on errors.
+fn read_dmi_string(name: &str) -> String {
+    fs::read_to_string(std::format!("{}/{}", SYSFS_DMI_DIR, name)).unwrap_or_default()
+}
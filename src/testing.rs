@@ -0,0 +1,66 @@
+//! Module for [`set_mock_result`], which fakes the verdict in unit tests of applications.
+//! Only available with the `testing` feature.
+
+use crate::cache::{decode, encode, UNINITIALIZED};
+use crate::QemuCertainty;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Mocked verdict. Holds [`UNINITIALIZED`] or an encoded [`QemuCertainty`].
+static MOCK: AtomicU8 = AtomicU8::new(UNINITIALIZED);
+
+/// Makes [`crate::runs_inside_qemu`], [`crate::runs_inside_qemu_cached`],
+/// [`crate::runs_inside_qemu_irqsafe`], [`crate::runs_inside_qemu_with_policy`],
+/// [`crate::try_runs_inside_qemu`], and [`crate::init`] return `certainty` without
+/// executing any check or populating the cache, until the returned [`MockGuard`] is
+/// dropped. This way, application code that branches on the
+/// verdict can be unit-tested on any machine. [`crate::detect`] and [`crate::Detector`]
+/// are not affected.
+///
+/// The mock is global and not thread-local. As `cargo test` runs tests in parallel,
+/// tests that set different mock results must be serialized, for example with a mutex.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{runs_inside_qemu, set_mock_result, QemuCertainty};
+///
+/// fn log_target() -> &'static str {
+///     if runs_inside_qemu().is_very_likely() {
+///         "debugcon"
+///     } else {
+///         "serial"
+///     }
+/// }
+///
+/// let guard = set_mock_result(QemuCertainty::VeryLikely);
+/// assert_eq!(log_target(), "debugcon");
+/// drop(guard);
+///
+/// let _guard = set_mock_result(QemuCertainty::DefinitelyNot);
+/// assert_eq!(log_target(), "serial");
+/// ```
+pub fn set_mock_result(certainty: QemuCertainty) -> MockGuard {
+    // no `swap`, as targets such as thumbv6m only support atomic loads and stores
+    let previous = MOCK.load(Ordering::SeqCst);
+    MOCK.store(encode(certainty), Ordering::SeqCst);
+    MockGuard { previous }
+}
+
+/// Guard of [`set_mock_result`]. Restores the previous mock result, or the real
+/// detection if there was none, when it is dropped.
+#[must_use = "the mock result is reset when the guard is dropped"]
+#[derive(Debug)]
+pub struct MockGuard {
+    previous: u8,
+}
+
+impl Drop for MockGuard {
+    fn drop(&mut self) {
+        MOCK.store(self.previous, Ordering::SeqCst);
+    }
+}
+
+/// Returns the mocked verdict, if [`set_mock_result`] is active.
+pub(crate) fn mock_result() -> Option<QemuCertainty> {
+    decode(MOCK.load(Ordering::SeqCst))
+}
@@ -0,0 +1,95 @@
+//! Module for [`TimingProbe`], a heuristic that measures how long CPUID takes. CPUID
+//! always traps to the hypervisor, so a VM exit makes it dramatically slower than on
+//! bare metal. This also works against hypervisors that hide all of their signatures,
+//! but it is noisy and only gives a weak signal.
+
+use crate::{Probe, ProbeResult, Weights};
+#[cfg(target_arch = "x86")]
+use core::arch::x86::_rdtsc;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::_rdtsc;
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Number of measurements. The minimum is used, as interrupts and other disturbances
+/// only make single measurements slower.
+const ITERATIONS: usize = 16;
+/// Default threshold in TSC ticks. On bare metal, CPUID takes roughly 100 to 300 ticks;
+/// a VM exit and the emulation take at least 1000 ticks.
+const DEFAULT_THRESHOLD: u64 = 750;
+
+/// Probe that measures the latency of CPUID with RDTSC.
+///
+/// Returns [`ProbeResult::Hint`], if the fastest of the measurements exceeds the
+/// threshold, and [`ProbeResult::Inconclusive`] otherwise. Contributes half the weight
+/// of an ordinary hint to the score, as the signal is unreliable: some hypervisors
+/// offset or scale the TSC, and frequency changes distort the measurement. The probe
+/// executes CPUID several times, which costs up to some microseconds in a virtual
+/// machine.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, TimingProbe};
+///
+/// let probe = TimingProbe::new();
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct TimingProbe {
+    threshold: u64,
+}
+
+impl TimingProbe {
+    /// Creates a new probe with the default threshold of 750 TSC ticks.
+    pub const fn new() -> Self {
+        Self::with_threshold(DEFAULT_THRESHOLD)
+    }
+
+    /// Creates a new probe that reports a hint, if CPUID takes more than `threshold`
+    /// TSC ticks.
+    pub const fn with_threshold(threshold: u64) -> Self {
+        Self { threshold }
+    }
+}
+
+impl Default for TimingProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Probe for TimingProbe {
+    fn name(&self) -> &'static str {
+        "timing"
+    }
+
+    fn run(&self) -> ProbeResult {
+        let ticks = min_cpuid_latency();
+        debug!("CPUID takes {} TSC ticks.", ticks);
+        if ticks > self.threshold {
+            ProbeResult::Hint
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+
+    fn weight(&self, result: ProbeResult, weights: &Weights) -> i32 {
+        weights.probe(result) / 2
+    }
+}
+
+/// Returns the TSC ticks of the fastest of several CPUIDs.
+pub(crate) fn min_cpuid_latency() -> u64 {
+    (0..ITERATIONS).map(|_| cpuid_latency()).min().unwrap_or(0)
+}
+
+/// Measures the TSC ticks of a single CPUID.
+fn cpuid_latency() -> u64 {
+    // SAFETY: RDTSC is available on all x86 CPUs that are supported by Rust
+    let start = unsafe { _rdtsc() };
+    let _ = core::hint::black_box(CpuIdReaderNative.cpuid1(0));
+    // SAFETY: see above
+    let end = unsafe { _rdtsc() };
+    end.saturating_sub(start)
+}
@@ -0,0 +1,138 @@
+//! Module for [`guest_topology`], which reads the CPU topology from CPUID. Early boot
+//! code of QEMU guests often needs it before the ACPI or MP tables are parsed. QEMU
+//! derives the leaves from `-smp`, e.g. `-smp 8,sockets=1,cores=4,threads=2`.
+
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Leaf with the feature information and the initial APIC ID.
+const FEATURE_INFO_LEAF: u32 = 0x1;
+/// Leaf with the deterministic cache parameters, which include the cores per package.
+const CACHE_PARAMETERS_LEAF: u32 = 0x4;
+/// Leaf with the extended topology enumeration and the x2APIC ID.
+const EXTENDED_TOPOLOGY_LEAF: u32 = 0xb;
+/// Flag in `edx` of leaf `0x1` that tells that `ebx[23:16]` is valid (HTT).
+const FEATURE_HTT: u32 = 1 << 28;
+/// Flag in `ecx` of leaf `0x1` that announces the x2APIC.
+const FEATURE_X2APIC: u32 = 1 << 21;
+/// Level type of the SMT level in leaf `0xb`.
+const LEVEL_TYPE_SMT: u32 = 1;
+/// Level type of the core level in leaf `0xb`.
+const LEVEL_TYPE_CORE: u32 = 2;
+
+/// The CPU topology of a package, as seen by the current CPU. Result of
+/// [`guest_topology`].
+///
+/// CPUID only describes a single package, so the number of packages (sockets) and hence
+/// the total number of CPUs still requires the ACPI MADT, the MP tables, or fw_cfg.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct GuestTopology {
+    /// Logical processors per package.
+    pub logical_processors: u32,
+    /// Cores per package.
+    pub cores: u32,
+    /// Logical processors (threads) per core.
+    pub threads_per_core: u32,
+    /// The x2APIC ID of the current CPU from leaf `0xb`, or its initial APIC ID from
+    /// leaf `0x1`, if leaf `0xb` isn't available.
+    pub apic_id: u32,
+    /// Whether the CPU supports the x2APIC.
+    pub x2apic: bool,
+}
+
+/// Reads the CPU topology of the current CPU from CPUID. Uses the extended topology leaf
+/// `0xb`, if available, and the leaves `0x1` and `0x4` otherwise. Returns `None`, if
+/// leaf `0x1` isn't available.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::guest_topology;
+///
+/// if let Some(topology) = guest_topology() {
+///     println!(
+///         "{} cores with {} threads each, APIC ID {}",
+///         topology.cores, topology.threads_per_core, topology.apic_id
+///     );
+/// }
+/// ```
+pub fn guest_topology() -> Option<GuestTopology> {
+    guest_topology_with(&CpuIdReaderNative)
+}
+
+/// Like [`guest_topology`] but reads CPUID through the given [`CpuIdReader`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::guest_topology_with;
+///
+/// // QEMU with -smp 8,cores=4,threads=2 on the CPU with APIC ID 3
+/// let reader = |eax, ecx| {
+///     let (eax, ebx, ecx, edx) = match (eax, ecx) {
+///         (0x0, _) => (0xb, 0, 0, 0),
+///         (0x1, _) => (0, 0x0308_0000, 0, 1 << 28),
+///         (0xb, 0) => (1, 2, 0x100, 3),
+///         (0xb, 1) => (3, 8, 0x201, 3),
+///         (0xb, _) => (0, 0, ecx, 3),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// let topology = guest_topology_with(&reader).unwrap();
+/// assert_eq!(topology.logical_processors, 8);
+/// assert_eq!(topology.cores, 4);
+/// assert_eq!(topology.threads_per_core, 2);
+/// assert_eq!(topology.apic_id, 3);
+/// ```
+pub fn guest_topology_with<R: CpuIdReader>(reader: &R) -> Option<GuestTopology> {
+    let max_leaf = reader.cpuid1(0).eax;
+    if max_leaf < FEATURE_INFO_LEAF {
+        return None;
+    }
+    let features = reader.cpuid1(FEATURE_INFO_LEAF);
+    let x2apic = features.ecx & FEATURE_X2APIC != 0;
+    let mut topology = GuestTopology {
+        logical_processors: if features.edx & FEATURE_HTT != 0 {
+            (features.ebx >> 16) & 0xff
+        } else {
+            1
+        },
+        cores: 1,
+        threads_per_core: 1,
+        apic_id: features.ebx >> 24,
+        x2apic,
+    };
+
+    if max_leaf >= EXTENDED_TOPOLOGY_LEAF
+        && reader.cpuid2(EXTENDED_TOPOLOGY_LEAF, 0).ebx & 0xffff != 0
+    {
+        // the number of levels is bounded by the 8 bit level number
+        for level in 0..=u32::from(u8::MAX) {
+            let res = reader.cpuid2(EXTENDED_TOPOLOGY_LEAF, level);
+            let processors = res.ebx & 0xffff;
+            match (res.ecx >> 8) & 0xff {
+                0 => break,
+                LEVEL_TYPE_SMT => topology.threads_per_core = processors,
+                LEVEL_TYPE_CORE => topology.logical_processors = processors,
+                _ => {}
+            }
+            topology.apic_id = res.edx;
+        }
+        topology.threads_per_core = topology.threads_per_core.max(1);
+        topology.cores = (topology.logical_processors / topology.threads_per_core).max(1);
+    } else if max_leaf >= CACHE_PARAMETERS_LEAF
+        && reader.cpuid2(CACHE_PARAMETERS_LEAF, 0).eax & 0x1f != 0
+    {
+        topology.cores = (reader.cpuid2(CACHE_PARAMETERS_LEAF, 0).eax >> 26) + 1;
+        topology.threads_per_core = (topology.logical_processors / topology.cores).max(1);
+    } else {
+        topology.cores = topology.logical_processors.max(1);
+    }
+    topology.logical_processors = topology.logical_processors.max(1);
+    debug!("CPU topology: {:?}", topology);
+    Some(topology)
+}
@@ -0,0 +1,178 @@
+//! Module for [`UefiTables`], which locates the SMBIOS and ACPI tables via the
+//! configuration table of the UEFI system table and runs [`SmbiosProbe`] and
+//! [`AcpiProbe`] on them, together with an [`EfiVendorProbe`] for the firmware vendor.
+//! Needs the `uefi` feature. Only the layout of the system table is needed, so the crate
+//! doesn't depend on a UEFI crate: pass the raw pointer that the firmware passes to the
+//! entry point of the application.
+
+use crate::{AcpiProbe, DetectionReport, Detector, EfiVendorProbe, Probe, Smbios, SmbiosProbe};
+use core::ffi::c_void;
+
+/// `EFI_GUID` in its in-memory layout.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Guid(u32, u16, u16, [u8; 8]);
+
+/// `SMBIOS3_TABLE_GUID`: the 64-bit SMBIOS entry point.
+const SMBIOS3_TABLE_GUID: Guid = Guid(
+    0xf2fd_1544,
+    0x9794,
+    0x4a2c,
+    [0x99, 0x2e, 0xe5, 0xbb, 0xcf, 0x20, 0xe3, 0x94],
+);
+/// `SMBIOS_TABLE_GUID`: the 32-bit SMBIOS entry point.
+const SMBIOS_TABLE_GUID: Guid = Guid(
+    0xeb9d_2d31,
+    0x2d88,
+    0x11d3,
+    [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
+);
+/// `EFI_ACPI_20_TABLE_GUID`: the RSDP of ACPI 2.0 and later.
+const ACPI_20_TABLE_GUID: Guid = Guid(
+    0x8868_e871,
+    0xe4f1,
+    0x11d3,
+    [0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81],
+);
+/// `ACPI_TABLE_GUID`: the RSDP of ACPI 1.0.
+const ACPI_TABLE_GUID: Guid = Guid(
+    0xeb9d_2d30,
+    0x2d88,
+    0x11d3,
+    [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
+);
+
+/// `EFI_CONFIGURATION_TABLE`.
+#[repr(C)]
+struct ConfigurationTable {
+    vendor_guid: Guid,
+    vendor_table: *const c_void,
+}
+
+/// `EFI_SYSTEM_TABLE`, without the fields that aren't needed here.
+#[repr(C)]
+struct SystemTable {
+    header: [u64; 3],
+    firmware_vendor: *const u16,
+    firmware_revision: u32,
+    console_in_handle: *const c_void,
+    con_in: *const c_void,
+    console_out_handle: *const c_void,
+    con_out: *const c_void,
+    standard_error_handle: *const c_void,
+    std_err: *const c_void,
+    runtime_services: *const c_void,
+    boot_services: *const c_void,
+    number_of_table_entries: usize,
+    configuration_table: *const ConfigurationTable,
+}
+
+/// The SMBIOS and ACPI tables that the UEFI firmware provides.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::UefiTables;
+/// # let system_table = core::ptr::null();
+///
+/// // `system_table` is the pointer that the firmware passed to the entry point
+/// // SAFETY: boot services are active
+/// let tables = unsafe { UefiTables::from_system_table(system_table) };
+/// let report = tables.detect();
+/// if report.certainty().is_very_likely() {
+///     // use the debugcon
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct UefiTables {
+    smbios: Option<Smbios<'static>>,
+    rsdp: Option<*const u8>,
+    firmware_vendor: &'static [u16],
+}
+
+impl UefiTables {
+    /// Locates the SMBIOS and ACPI tables in the configuration table of the given
+    /// `EFI_SYSTEM_TABLE`. The 64-bit SMBIOS entry point and the RSDP of ACPI 2.0 are
+    /// preferred.
+    ///
+    /// # Safety
+    /// `system_table` must point to a valid `EFI_SYSTEM_TABLE`. The tables must be
+    /// readable at their physical addresses while this and the returned value are used,
+    /// which is the case while the boot services are active, as UEFI identity-maps
+    /// memory.
+    pub unsafe fn from_system_table(system_table: *const c_void) -> Self {
+        let system_table = &*system_table.cast::<SystemTable>();
+        let entries = core::slice::from_raw_parts(
+            system_table.configuration_table,
+            system_table.number_of_table_entries,
+        );
+        let find = |guids: [Guid; 2]| {
+            guids.into_iter().find_map(|guid| {
+                entries
+                    .iter()
+                    .find(|entry| entry.vendor_guid == guid)
+                    .map(|entry| entry.vendor_table.cast::<u8>())
+            })
+        };
+        Self {
+            smbios: find([SMBIOS3_TABLE_GUID, SMBIOS_TABLE_GUID])
+                .and_then(|entry_point| Smbios::from_entry_point(entry_point)),
+            rsdp: find([ACPI_20_TABLE_GUID, ACPI_TABLE_GUID]),
+            firmware_vendor: ucs2_str(system_table.firmware_vendor),
+        }
+    }
+
+    /// Returns the SMBIOS tables, if the firmware provides them.
+    pub const fn smbios(&self) -> Option<Smbios<'static>> {
+        self.smbios
+    }
+
+    /// Returns the pointer to the RSDP, if the firmware provides ACPI tables.
+    pub const fn rsdp(&self) -> Option<*const u8> {
+        self.rsdp
+    }
+
+    /// Returns the UCS-2 firmware vendor, e.g. `EDK II` for OVMF, without the terminating
+    /// null character. Empty, if the firmware doesn't report a vendor.
+    pub const fn firmware_vendor(&self) -> &'static [u16] {
+        self.firmware_vendor
+    }
+
+    /// Runs a [`Detector`] with [`SmbiosProbe`] and [`AcpiProbe`] for the tables that
+    /// are present and with an [`EfiVendorProbe`], in addition to the CPUID checks.
+    pub fn detect(&self) -> DetectionReport {
+        let smbios_probe = self.smbios.map(SmbiosProbe::new);
+        // SAFETY: guaranteed by the caller of `UefiTables::from_system_table`
+        let acpi_probe = self.rsdp.map(|rsdp| unsafe { AcpiProbe::new(rsdp) });
+        let vendor_probe = EfiVendorProbe::new(self.firmware_vendor);
+        let mut probes: [&dyn Probe; 3] = [&vendor_probe; 3];
+        let mut len = 1;
+        let present = [
+            smbios_probe.as_ref().map(|probe| probe as &dyn Probe),
+            acpi_probe.as_ref().map(|probe| probe as &dyn Probe),
+        ];
+        for probe in present.into_iter().flatten() {
+            probes[len] = probe;
+            len += 1;
+        }
+        Detector::new(&probes[..len]).detect()
+    }
+}
+
+/// Maximum length of a firmware vendor, to not read arbitrary memory if the string is
+/// corrupt.
+const MAX_VENDOR_LEN: usize = 256;
+
+/// Returns the null-terminated UCS-2 string at `ptr` without the null character.
+///
+/// # Safety
+/// `ptr` must be null or point to a null-terminated string that stays valid and unchanged.
+unsafe fn ucs2_str(ptr: *const u16) -> &'static [u16] {
+    if ptr.is_null() {
+        return &[];
+    }
+    let len = (0..MAX_VENDOR_LEN)
+        .position(|i| *ptr.add(i) == 0)
+        .unwrap_or(MAX_VENDOR_LEN);
+    core::slice::from_raw_parts(ptr, len)
+}
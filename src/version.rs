@@ -0,0 +1,138 @@
+//! Module for [`QemuVersion`] and [`qemu_version`]. QEMU doesn't report its version
+//! directly, but the versioned machine type in the SMBIOS system version, e.g.
+//! `pc-q35-8.2`, and the version in the brand string of its CPU models, e.g.
+//! `QEMU Virtual CPU version 2.5+`, give a lower bound.
+
+use crate::Smbios;
+use core::fmt;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use raw_cpuid::CpuId;
+
+/// Prefix of the version in the brand string of QEMU's CPU models.
+const BRAND_STRING_VERSION_PREFIX: &str = "QEMU Virtual CPU version ";
+/// Prefixes of QEMU's versioned machine types.
+const MACHINE_TYPE_PREFIXES: &[&str] = &["pc-i440fx-", "pc-q35-", "pc-", "virt-"];
+
+/// Path of the SMBIOS system version in the sysfs of Linux.
+#[cfg(feature = "std")]
+const SYSFS_PRODUCT_VERSION: &str = "/sys/class/dmi/id/product_version";
+
+/// A QEMU version, ordered like semantic versions. Result of [`qemu_version`].
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::QemuVersion;
+///
+/// let version = QemuVersion::from_machine_type("pc-q35-8.2").unwrap();
+/// assert_eq!(version, QemuVersion::new(8, 2, 0));
+/// assert!(version >= QemuVersion::new(7, 0, 0));
+/// assert_eq!(version.to_string(), "8.2.0");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct QemuVersion {
+    /// Major version.
+    pub major: u16,
+    /// Minor version.
+    pub minor: u16,
+    /// Patch version. Machine types and brand strings don't carry one, so it is usually
+    /// zero.
+    pub patch: u16,
+}
+
+impl QemuVersion {
+    /// Creates a new version.
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses a version such as `8.2` or `2.5+`. The patch version is optional and a
+    /// trailing `+` is ignored.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().trim_end_matches('+').split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = parts.next().map(str::parse).unwrap_or(Ok(0)).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, patch))
+    }
+
+    /// Parses the version from a CPU brand string of QEMU, such as
+    /// `QEMU Virtual CPU version 2.5+`. This is the version of the CPU model, i.e. QEMU
+    /// is at least this version.
+    pub fn from_brand_string(brand_string: &str) -> Option<Self> {
+        Self::parse(
+            brand_string
+                .trim()
+                .strip_prefix(BRAND_STRING_VERSION_PREFIX)?,
+        )
+    }
+
+    /// Parses the version from a versioned machine type, such as `pc-i440fx-8.2` or
+    /// `virt-9.0`, as reported in the SMBIOS system version. QEMU is at least this
+    /// version.
+    pub fn from_machine_type(machine_type: &str) -> Option<Self> {
+        let machine_type = machine_type.trim();
+        MACHINE_TYPE_PREFIXES
+            .iter()
+            .find_map(|prefix| Self::parse(machine_type.strip_prefix(prefix)?))
+    }
+
+    /// Parses the version from the system version of the SMBIOS tables (see
+    /// [`Self::from_machine_type`]).
+    pub fn from_smbios(smbios: &Smbios) -> Option<Self> {
+        Self::from_machine_type(smbios.system_version()?)
+    }
+}
+
+impl fmt::Display for QemuVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Returns the QEMU version, if it can be found. With the `std` feature, the machine
+/// type from `/sys/class/dmi/id/product_version` is used first. Otherwise, or if it isn't
+/// available, the version is parsed from the CPU brand string on x86. Both are lower
+/// bounds, as newer QEMU versions still provide the older machine types and CPU models.
+/// Returns `None`, if neither is available or QEMU's, e.g. with `-cpu host`. Use
+/// [`QemuVersion::from_smbios`] in `no_std` environments with access to the SMBIOS
+/// tables.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{qemu_version, QemuVersion};
+///
+/// if qemu_version().is_some_and(|version| version < QemuVersion::new(6, 0, 0)) {
+///     println!("old QEMU, enable workarounds");
+/// }
+/// ```
+pub fn qemu_version() -> Option<QemuVersion> {
+    #[cfg(feature = "std")]
+    if let Some(version) = std::fs::read_to_string(SYSFS_PRODUCT_VERSION)
+        .ok()
+        .and_then(|version| QemuVersion::from_machine_type(&version))
+    {
+        debug!("QEMU version from the machine type: {:?}", version);
+        return Some(version);
+    }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if let Some(version) = CpuId::new()
+        .get_processor_brand_string()
+        .and_then(|brand_string| QemuVersion::from_brand_string(brand_string.as_str()))
+    {
+        debug!("QEMU version from the brand string: {:?}", version);
+        return Some(version);
+    }
+    None
+}
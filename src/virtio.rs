@@ -0,0 +1,119 @@
+//! Module for [`VirtioProbe`], which looks for virtio devices. Virtio devices show that
+//! the code runs in a virtual machine, but not which one: QEMU, cloud-hypervisor,
+//! Firecracker, kvmtool, crosvm, and others provide them. Only the vendor ID of
+//! virtio-mmio devices identifies QEMU.
+
+use crate::pci::ConfigAccess;
+use crate::{Probe, ProbeResult};
+
+/// PCI vendor ID of virtio devices.
+const VIRTIO_PCI_VENDOR_ID: u16 = 0x1af4;
+/// Magic value `virt` at offset 0 of each virtio-mmio region.
+pub(crate) const VIRTIO_MMIO_MAGIC: u32 = 0x7472_6976;
+/// Offset of the device ID in a virtio-mmio region. `0` marks an unused slot.
+pub(crate) const VIRTIO_MMIO_DEVICE_ID: usize = 0x8;
+/// Offset of the vendor ID in a virtio-mmio region.
+const VIRTIO_MMIO_VENDOR_ID: usize = 0xc;
+/// Vendor ID `QEMU` of the virtio-mmio devices of QEMU.
+const VIRTIO_MMIO_VENDOR_QEMU: u32 = 0x554d_4551;
+
+/// Probe that looks for virtio devices on PCI or in caller-provided virtio-mmio regions,
+/// e.g. from the device tree or the kernel command line.
+///
+/// Returns [`ProbeResult::Qemu`] for a virtio-mmio device with QEMU's vendor ID,
+/// [`ProbeResult::Hint`] for other virtio devices, as they don't imply QEMU, and
+/// [`ProbeResult::Inconclusive`] if there are none. Together with the KVM signature,
+/// which alone only gives [`crate::QemuCertainty::Maybe`], the hint raises the score.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::{Detector, VirtioProbe};
+///
+/// // from the device tree of QEMU's "virt" machine for aarch64
+/// let regions = [0x0a00_0000 as *const u8, 0x0a00_0200 as *const u8];
+/// // SAFETY: the regions are identity-mapped as device memory
+/// let probe = unsafe { VirtioProbe::with_mmio(&regions) };
+/// let report = Detector::new(&[&probe]).detect();
+/// println!("{:?}", report.certainty());
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct VirtioProbe<'a> {
+    pci: Option<ConfigAccess>,
+    mmio_regions: &'a [*const u8],
+}
+
+impl<'a> VirtioProbe<'a> {
+    /// Creates a new probe that looks for virtio devices on PCI via the I/O ports
+    /// `0xcf8` and `0xcfc`.
+    ///
+    /// # Safety
+    /// See [`crate::QemuPciProbe::new`].
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub const unsafe fn new() -> Self {
+        Self {
+            pci: Some(ConfigAccess::Ports),
+            mmio_regions: &[],
+        }
+    }
+
+    /// Creates a new probe that looks for virtio devices on PCI via the ECAM region at
+    /// `base` for the buses `0..=last_bus`.
+    ///
+    /// # Safety
+    /// See [`crate::QemuPciProbe::with_ecam`].
+    pub const unsafe fn with_ecam(base: *const u8, last_bus: u8) -> Self {
+        Self {
+            pci: Some(ConfigAccess::Ecam { base, last_bus }),
+            mmio_regions: &[],
+        }
+    }
+
+    /// Creates a new probe that inspects the given virtio-mmio regions.
+    ///
+    /// # Safety
+    /// The first 16 bytes of each region must be mapped as device memory and readable.
+    pub const unsafe fn with_mmio(mmio_regions: &'a [*const u8]) -> Self {
+        Self {
+            pci: None,
+            mmio_regions,
+        }
+    }
+}
+
+impl Probe for VirtioProbe<'_> {
+    fn name(&self) -> &'static str {
+        "virtio"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        let mut result = ProbeResult::Inconclusive;
+        for &base in self.mmio_regions {
+            // SAFETY: guaranteed by the caller of `VirtioProbe::with_mmio`
+            let read = |offset| unsafe { base.add(offset).cast::<u32>().read_volatile() };
+            if read(0) != VIRTIO_MMIO_MAGIC || read(VIRTIO_MMIO_DEVICE_ID) == 0 {
+                continue;
+            }
+            if read(VIRTIO_MMIO_VENDOR_ID) == VIRTIO_MMIO_VENDOR_QEMU {
+                debug!("virtio-mmio device of QEMU at {:?}.", base);
+                return ProbeResult::Qemu;
+            }
+            result = ProbeResult::Hint;
+        }
+        if let Some(access) = self.pci {
+            // SAFETY: guaranteed by the caller of `VirtioProbe::new` or `with_ecam`
+            let mut devices = unsafe { access.devices() };
+            if devices.any(|device| device.vendor_id == VIRTIO_PCI_VENDOR_ID) {
+                result = ProbeResult::Hint;
+            }
+        }
+        if result == ProbeResult::Hint {
+            debug!("virtio devices found.");
+        }
+        result
+    }
+}
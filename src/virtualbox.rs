@@ -0,0 +1,55 @@
+//! VirtualBox backend. Besides its `VBoxVBoxVBox` hypervisor signature, which the CPUID
+//! checks already evaluate, VirtualBox is identified by its guest PCI device, see
+//! [`VirtualBoxProbe`]. VirtualBox only reports its own signature with the "minimal"
+//! paravirtualization interface; with the "KVM" or "Hyper-V" interface, it reports
+//! the signature of these.
+
+use crate::{pci, Probe, ProbeResult};
+
+/// PCI vendor ID of VirtualBox (InnoTek).
+const VIRTUALBOX_VENDOR_ID: u16 = 0x80ee;
+/// PCI device ID of the VirtualBox guest device (VMMDev).
+const VIRTUALBOX_GUEST_DEVICE_ID: u16 = 0xcafe;
+
+/// Probe that looks for the VirtualBox guest PCI device `80ee:cafe`.
+///
+/// Returns [`ProbeResult::NotQemu`], if the device is present, as QEMU doesn't emulate
+/// it, and [`ProbeResult::Inconclusive`] otherwise.
+#[derive(Copy, Clone, Debug)]
+pub struct VirtualBoxProbe {
+    _private: (),
+}
+
+impl VirtualBoxProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+    /// sufficient I/O privilege level, and nobody else may access the PCI configuration
+    /// space while the probe runs. Otherwise, the probe raises a general protection fault
+    /// or corrupts concurrent configuration space accesses.
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for VirtualBoxProbe {
+    fn name(&self) -> &'static str {
+        "virtualbox"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `VirtualBoxProbe::new`
+        let device = unsafe { pci::find_device(VIRTUALBOX_VENDOR_ID, VIRTUALBOX_GUEST_DEVICE_ID) };
+        match device {
+            Some(device) => {
+                debug!(
+                    "VirtualBox guest device found at {}:{}.{}.",
+                    device.bus, device.device, device.function
+                );
+                ProbeResult::NotQemu
+            }
+            None => ProbeResult::Inconclusive,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Module for [`VmGenId`], which reads the VM Generation ID of QEMU's `vmgenid` device
+//! (`-device vmgenid`). The ID changes whenever the VM is restored from a snapshot or
+//! cloned, so guests can reseed random number generators or renew their identity.
+//!
+//! QEMU describes the device in a separate SSDT with the OEM table ID `VMGENID`: the
+//! device `\_SB.VGEN` with the hardware ID `QEMUVGID` and the named DWORD `VGIA`, which
+//! the firmware patches with the physical address of the guest memory that holds the ID.
+//! The ID itself is located 40 bytes after this address.
+
+use crate::{acpi, Uuid};
+
+/// OEM table ID of the SSDT that describes the device, padded with spaces.
+const OEM_TABLE_ID: &[u8; 8] = b"VMGENID ";
+/// Hardware ID of the device.
+const HARDWARE_ID: &[u8; 8] = b"QEMUVGID";
+/// AML encoding of `Name (VGIA, <DWordConst>)` up to the value.
+const VGIA_NAME_DECLARATION: [u8; 6] = [0x08, b'V', b'G', b'I', b'A', 0x0c];
+/// Offset of the ID from the address in `VGIA`.
+const GUID_OFFSET: u64 = 40;
+
+/// The VM Generation ID device of QEMU.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::VmGenId;
+///
+/// // e.g. passed by the boot loader
+/// let rsdp = 0xf_5a90 as *const u8;
+/// // SAFETY: runs with identity-mapped memory
+/// if let Some(vmgenid) = unsafe { VmGenId::from_rsdp(rsdp) } {
+///     // SAFETY: physical memory is identity-mapped
+///     let generation = unsafe { vmgenid.read() };
+///     println!("VM generation {}", generation);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VmGenId {
+    address: u64,
+}
+
+impl VmGenId {
+    /// Finds the device in the ACPI tables of the given RSDP. Returns `None`, if there
+    /// is no such device, i.e. QEMU runs without `-device vmgenid`.
+    ///
+    /// # Safety
+    /// See [`crate::AcpiProbe::new`].
+    pub unsafe fn from_rsdp(rsdp: *const u8) -> Option<Self> {
+        let ssdt = acpi::find_table(rsdp, b"SSDT", |table| {
+            acpi::sdt_oem_table_id(table) == OEM_TABLE_ID
+        })?;
+        Self::from_ssdt(ssdt)
+    }
+
+    /// Finds the device in the given SSDT, including its header. Returns `None`, if the
+    /// table doesn't describe the device or the firmware didn't patch the address.
+    ///
+    /// ## Example Usage
+    ///
+    /// ```rust
+    /// use runs_inside_qemu::VmGenId;
+    ///
+    /// let mut ssdt = [0; 36].to_vec();
+    /// ssdt[..4].copy_from_slice(b"SSDT");
+    /// ssdt[16..24].copy_from_slice(b"VMGENID ");
+    /// // Name (VGIA, 0x7fff1000)
+    /// ssdt.extend([0x08, b'V', b'G', b'I', b'A', 0x0c, 0x00, 0x10, 0xff, 0x7f]);
+    /// // Name (_HID, "QEMUVGID")
+    /// ssdt.extend([0x08, b'_', b'H', b'I', b'D', 0x0d]);
+    /// ssdt.extend(b"QEMUVGID\0");
+    ///
+    /// let vmgenid = VmGenId::from_ssdt(&ssdt).unwrap();
+    /// assert_eq!(vmgenid.address(), 0x7fff_1000 + 40);
+    /// ```
+    pub fn from_ssdt(ssdt: &[u8]) -> Option<Self> {
+        if !ssdt.starts_with(b"SSDT") || ssdt.len() < 36 {
+            return None;
+        }
+        let body = acpi::sdt_body(ssdt);
+        if !body
+            .windows(HARDWARE_ID.len())
+            .any(|window| window == HARDWARE_ID)
+        {
+            return None;
+        }
+        let offset = body
+            .windows(VGIA_NAME_DECLARATION.len())
+            .position(|window| window == VGIA_NAME_DECLARATION)?
+            + VGIA_NAME_DECLARATION.len();
+        let vgia = u32::from_le_bytes(body.get(offset..offset + 4)?.try_into().unwrap());
+        if vgia == 0 {
+            debug!("vmgenid found, but VGIA isn't patched.");
+            return None;
+        }
+        let address = u64::from(vgia) + GUID_OFFSET;
+        debug!("vmgenid found, ID at {}.", address);
+        Some(Self { address })
+    }
+
+    /// Returns the physical address of the ID.
+    pub const fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Reads the current ID. QEMU stores it little-endian, like the UUIDs of SMBIOS.
+    /// QEMU notifies the device via ACPI (`Notify (\_SB.VGEN, 0x80)`) when it changes,
+    /// but polling it works as well.
+    ///
+    /// # Safety
+    /// The 16 bytes at [`Self::address`] must be readable at their physical address, i.e.
+    /// physical memory must be identity-mapped.
+    pub unsafe fn read(&self) -> Uuid {
+        let bytes = core::ptr::read_volatile(self.address as usize as *const [u8; 16]);
+        Uuid::from_guid_bytes(bytes)
+    }
+}
@@ -0,0 +1,109 @@
+//! VMware backend. VMware is identified by its `VMwareVMware` hypervisor signature and
+//! optionally via the VMware backdoor I/O port, see [`vmware_backdoor_version`].
+
+use crate::hypervisor::SIGNATURE_VMWARE;
+use crate::{cpuid, Probe, ProbeResult};
+
+/// Magic value (`VMXh`) that is passed in `eax` and returned in `ebx` by the backdoor.
+const BACKDOOR_MAGIC: u32 = 0x564d_5868;
+/// I/O port of the VMware backdoor.
+const BACKDOOR_PORT: u16 = 0x5658;
+/// Backdoor command that returns the version of the backdoor protocol.
+const BACKDOOR_CMD_GET_VERSION: u32 = 10;
+
+/// Queries the version of the VMware backdoor protocol via I/O port `0x5658`. Returns
+/// `None`, if nobody answers on the port.
+///
+/// Be aware, that QEMU also emulates the backdoor (`-machine vmport=on`, the default
+/// for TCG), so an answer alone doesn't prove VMware. Compare the hypervisor signature,
+/// as [`VmwareBackdoorProbe`] does.
+///
+/// # Safety
+/// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+/// sufficient I/O privilege level, unless it runs under VMware, which also permits the
+/// backdoor from ring 3. Otherwise, the `in` instruction raises a general protection
+/// fault. On bare metal, the port might belong to a real device.
+pub unsafe fn vmware_backdoor_version() -> Option<u32> {
+    let (version, magic) = backdoor_call(BACKDOOR_CMD_GET_VERSION);
+    (magic == BACKDOOR_MAGIC && version != u32::MAX).then_some(version)
+}
+
+/// Issues a backdoor command and returns `eax` and `ebx`.
+#[cfg(target_arch = "x86_64")]
+unsafe fn backdoor_call(command: u32) -> (u32, u32) {
+    let eax: u32;
+    let ebx: u64;
+    // `rbx` is reserved by LLVM and must be saved manually
+    core::arch::asm!(
+        "xchg {ebx}, rbx",
+        "in eax, dx",
+        "xchg {ebx}, rbx",
+        ebx = inout(reg) u64::from(u32::MAX) => ebx,
+        inout("eax") BACKDOOR_MAGIC => eax,
+        inout("ecx") command => _,
+        inout("edx") u32::from(BACKDOOR_PORT) => _,
+        options(nomem, nostack, preserves_flags)
+    );
+    (eax, ebx as u32)
+}
+
+/// Issues a backdoor command and returns `eax` and `ebx`.
+#[cfg(target_arch = "x86")]
+unsafe fn backdoor_call(command: u32) -> (u32, u32) {
+    let eax: u32;
+    let ebx: u32;
+    core::arch::asm!(
+        "in eax, dx",
+        inout("eax") BACKDOOR_MAGIC => eax,
+        inout("ebx") u32::MAX => ebx,
+        inout("ecx") command => _,
+        inout("edx") u32::from(BACKDOOR_PORT) => _,
+        options(nomem, nostack, preserves_flags)
+    );
+    (eax, ebx)
+}
+
+/// Probe that queries the VMware backdoor (see [`vmware_backdoor_version`]).
+///
+/// Returns [`ProbeResult::NotQemu`], if the backdoor answers and the hypervisor signature
+/// is `VMwareVMware`, and [`ProbeResult::Hint`], if the backdoor answers under another
+/// hypervisor signature, which is the case for QEMU's emulation of the backdoor.
+/// Otherwise, returns [`ProbeResult::Inconclusive`].
+#[derive(Copy, Clone, Debug)]
+pub struct VmwareBackdoorProbe {
+    _private: (),
+}
+
+impl VmwareBackdoorProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// See [`vmware_backdoor_version`].
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for VmwareBackdoorProbe {
+    fn name(&self) -> &'static str {
+        "vmware-backdoor"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `VmwareBackdoorProbe::new`
+        let version = match unsafe { vmware_backdoor_version() } {
+            Some(version) => version,
+            None => return ProbeResult::Inconclusive,
+        };
+        if cpuid::hypervisor_signature().as_ref() == Some(SIGNATURE_VMWARE) {
+            debug!("VMware backdoor version {} answers under VMware.", version);
+            ProbeResult::NotQemu
+        } else {
+            debug!(
+                "VMware backdoor version {} answers, but the VMM isn't VMware.",
+                version
+            );
+            ProbeResult::Hint
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Module for [`WindowsFirmwareProbe`], which reads the SMBIOS tables and the BIOS
+//! strings of the registry on Windows. Needs the `std` feature. Like the DMI probes on
+//! Linux, it identifies QEMU without CPUID, e.g. TCG guests without the hypervisor flag.
+
+use crate::dmi::{self, FirmwareStrings};
+use crate::{Probe, ProbeResult, Smbios};
+use core::ffi::c_void;
+use core::ptr;
+use std::string::String;
+use std::vec;
+use std::vec::Vec;
+
+/// Provider signature `RSMB` of the raw SMBIOS tables for `GetSystemFirmwareTable`.
+const PROVIDER_RSMB: u32 = u32::from_be_bytes(*b"RSMB");
+/// Size of the header of `RawSMBIOSData` in front of the structure table.
+const RAW_SMBIOS_HEADER_LEN: usize = 8;
+/// Predefined registry key `HKEY_LOCAL_MACHINE`. Windows sign-extends it to the size of
+/// a pointer.
+const HKEY_LOCAL_MACHINE: isize = 0x8000_0002_u32 as i32 as isize;
+/// Registry key with the DMI strings that Windows copies from the SMBIOS tables.
+const SYSTEM_BIOS_KEY: &str = "HARDWARE\\DESCRIPTION\\System\\BIOS";
+/// Restricts `RegGetValueW` to values of type `REG_SZ`.
+const RRF_RT_REG_SZ: u32 = 0x2;
+/// Return value of successful registry functions.
+const ERROR_SUCCESS: i32 = 0;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetSystemFirmwareTable(
+        provider_signature: u32,
+        table_id: u32,
+        buffer: *mut c_void,
+        buffer_size: u32,
+    ) -> u32;
+}
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn RegGetValueW(
+        key: isize,
+        sub_key: *const u16,
+        value: *const u16,
+        flags: u32,
+        ty: *mut u32,
+        data: *mut c_void,
+        data_len: *mut u32,
+    ) -> i32;
+}
+
+/// Probe that reads the SMBIOS tables via `GetSystemFirmwareTable` and, if they aren't
+/// conclusive, the values `SystemManufacturer`, `SystemProductName`, and `BIOSVendor` of
+/// the registry key `HKLM\HARDWARE\DESCRIPTION\System\BIOS`.
+///
+/// Returns [`ProbeResult::Qemu`] for QEMU's system manufacturer, product name, or chassis
+/// vendor, [`ProbeResult::NotQemu`] for the strings of a VMM that can't be QEMU (see
+/// [`crate::vmm_from_dmi`]), [`ProbeResult::Hint`] if only the BIOS is SeaBIOS or Bochs,
+/// and [`ProbeResult::Inconclusive`] otherwise.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::{Detector, WindowsFirmwareProbe};
+///
+/// let probe = WindowsFirmwareProbe;
+/// let report = Detector::builder().probes(&[&probe]).build().detect();
+/// if report.certainty().is_very_likely() {
+///     println!("QEMU");
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WindowsFirmwareProbe;
+
+impl Probe for WindowsFirmwareProbe {
+    fn name(&self) -> &'static str {
+        "windows-firmware"
+    }
+
+    fn unambiguous(&self) -> bool {
+        true
+    }
+
+    fn run(&self) -> ProbeResult {
+        let smbios_result = read_smbios()
+            .map(|table| {
+                let smbios = Smbios::new(&table);
+                dmi::firmware_strings_result(&FirmwareStrings {
+                    sys_vendor: smbios.sys_vendor().unwrap_or(""),
+                    product_name: smbios.product_name().unwrap_or(""),
+                    bios_vendor: smbios.bios_vendor().unwrap_or(""),
+                    chassis_vendor: smbios.chassis_vendor().unwrap_or(""),
+                })
+            })
+            .unwrap_or(ProbeResult::Inconclusive);
+        if smbios_result != ProbeResult::Inconclusive {
+            return smbios_result;
+        }
+        debug!("SMBIOS tables are inconclusive, reading the registry.");
+        let sys_vendor = read_bios_value("SystemManufacturer");
+        let product_name = read_bios_value("SystemProductName");
+        let bios_vendor = read_bios_value("BIOSVendor");
+        dmi::firmware_strings_result(&FirmwareStrings {
+            sys_vendor: &sys_vendor,
+            product_name: &product_name,
+            bios_vendor: &bios_vendor,
+            chassis_vendor: "",
+        })
+    }
+}
+
+/// Returns the SMBIOS structure table, or `None` if Windows doesn't provide it.
+fn read_smbios() -> Option<Vec<u8>> {
+    // SAFETY: a null buffer of size zero only queries the required size.
+    let len = unsafe { GetSystemFirmwareTable(PROVIDER_RSMB, 0, ptr::null_mut(), 0) };
+    let mut buffer = vec![0_u8; len as usize];
+    // SAFETY: the buffer is valid for `len` bytes.
+    let written =
+        unsafe { GetSystemFirmwareTable(PROVIDER_RSMB, 0, buffer.as_mut_ptr().cast(), len) };
+    if written == 0 || written > len {
+        return None;
+    }
+    buffer.truncate(written as usize);
+    let header = buffer.get(..RAW_SMBIOS_HEADER_LEN)?;
+    let table_len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let table = buffer.get(RAW_SMBIOS_HEADER_LEN..RAW_SMBIOS_HEADER_LEN.checked_add(table_len)?)?;
+    Some(table.to_vec())
+}
+
+/// Reads the string value `name` of the registry key [`SYSTEM_BIOS_KEY`]. Returns an
+/// empty string on errors.
+fn read_bios_value(name: &str) -> String {
+    let sub_key = to_wide(SYSTEM_BIOS_KEY);
+    let value = to_wide(name);
+    let mut len = 0_u32;
+    // SAFETY: the strings are null-terminated and a null buffer only queries the size.
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            sub_key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_SZ,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut len,
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return String::new();
+    }
+    let mut data = vec![0_u16; (len as usize).div_ceil(2)];
+    // SAFETY: the buffer is valid for `len` bytes.
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            sub_key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_SZ,
+            ptr::null_mut(),
+            data.as_mut_ptr().cast(),
+            &mut len,
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return String::new();
+    }
+    let end = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+    String::from_utf16_lossy(&data[..end])
+}
+
+/// Encodes `string` as null-terminated UTF-16.
+fn to_wide(string: &str) -> Vec<u16> {
+    string.encode_utf16().chain(core::iter::once(0)).collect()
+}
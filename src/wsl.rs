@@ -0,0 +1,62 @@
+//! WSL2 backend. WSL2 runs a Linux kernel from Microsoft in a Hyper-V virtual machine.
+//! Needs the `std` feature, as the kernel release is read from `/proc`.
+
+use crate::{detect_hypervisor, Probe, ProbeResult, VmmKind};
+use std::fs;
+
+/// Files with the release of the running kernel, in the order they are read.
+const KERNEL_RELEASE_FILES: [&str; 2] = ["/proc/sys/kernel/osrelease", "/proc/version"];
+
+/// Returns if the code runs inside WSL2: the hypervisor is Hyper-V and the kernel
+/// release mentions Microsoft, for example `5.15.153.1-microsoft-standard-WSL2`. On
+/// architectures without CPUID, only the kernel release is checked.
+///
+/// WSL1 also reports a Microsoft kernel release but has no Hyper-V virtual machine.
+/// Hosts with virtualization-based security still expose the Hyper-V signature to WSL1,
+/// so WSL1 might be reported as WSL2 there.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::runs_inside_wsl2;
+///
+/// if runs_inside_wsl2() {
+///     println!("WSL2, not QEMU");
+/// }
+/// ```
+pub fn runs_inside_wsl2() -> bool {
+    let hyperv = cfg!(not(any(target_arch = "x86", target_arch = "x86_64")))
+        || detect_hypervisor() == Some(VmmKind::HyperV);
+    hyperv && kernel_release_mentions_microsoft()
+}
+
+/// Returns if the first readable kernel release file mentions Microsoft.
+fn kernel_release_mentions_microsoft() -> bool {
+    KERNEL_RELEASE_FILES
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|release| release.to_ascii_lowercase().contains("microsoft"))
+        .unwrap_or(false)
+}
+
+/// Probe that checks if the code runs inside WSL2 (see [`runs_inside_wsl2`]).
+///
+/// Returns [`ProbeResult::NotQemu`] inside WSL2 and [`ProbeResult::Inconclusive`]
+/// otherwise.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Wsl2Probe;
+
+impl Probe for Wsl2Probe {
+    fn name(&self) -> &'static str {
+        "wsl2"
+    }
+
+    fn run(&self) -> ProbeResult {
+        if runs_inside_wsl2() {
+            debug!("Runs inside WSL2.");
+            ProbeResult::NotQemu
+        } else {
+            ProbeResult::Inconclusive
+        }
+    }
+}
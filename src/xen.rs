@@ -0,0 +1,225 @@
+//! Xen backend. Reads the Xen CPUID leaves and tells PV, PVH, and HVM guests apart. For
+//! HVM guests, QEMU acts as device model, so device-side mechanisms such as fw_cfg may
+//! still work, although the hypervisor is Xen. [`xen_hvm_with_qemu`] confirms this via
+//! devices that only QEMU emulates.
+
+use crate::cpuid::{self, HYPERVISOR_INFO_LEAF, HYPERV_EMULATOR_INFO_LEAF};
+use crate::hypervisor::SIGNATURE_XEN;
+use crate::{fw_cfg, io, pci, Probe, ProbeResult};
+use raw_cpuid::{CpuIdReader, CpuIdReaderNative};
+
+/// Flag in `eax` of the HVM leaf (base + 4) that Xen sets for HVM and PVH guests.
+const XEN_HVM_CPUID_VCPU_ID_PRESENT: u32 = 1 << 3;
+
+/// I/O port of the Xen platform device that QEMU provides as device model of HVM guests.
+const XEN_IOPORT_MAGIC: u16 = 0x10;
+/// Value that the Xen platform device returns on [`XEN_IOPORT_MAGIC`].
+const XEN_IOPORT_MAGIC_VAL: u16 = 0x49d2;
+/// PCI vendor ID and device ID of the Xen platform device, which QEMU emulates.
+const XEN_PLATFORM_DEVICE: (u16, u16) = (0x5853, 0x0001);
+/// PCI vendor ID of the QEMU devices of Red Hat.
+const REDHAT_QEMU_VENDOR_ID: u16 = 0x1b36;
+
+/// Guest mode of a Xen domain. Result of [`xen_guest_mode`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum XenGuestMode {
+    /// Paravirtualized guest without hardware virtualization and without device model.
+    Pv,
+    /// PVH guest: hardware virtualization, but no emulated devices and no device model.
+    Pvh,
+    /// Fully virtualized guest with QEMU as device model.
+    Hvm,
+}
+
+impl XenGuestMode {
+    /// Returns if QEMU acts as device model, so that QEMU devices such as fw_cfg may be
+    /// present.
+    pub const fn has_qemu_device_model(self) -> bool {
+        matches!(self, Self::Hvm)
+    }
+}
+
+/// Information from the Xen CPUID leaves. Result of [`xen_info`].
+///
+/// ## Example Usage
+///
+/// Xen with Viridian enlightenments reports the Hyper-V signature in leaf `0x4000_0000`
+/// but is still identified as Xen:
+///
+/// ```rust
+/// use runs_inside_qemu::raw_cpuid::CpuIdResult;
+/// use runs_inside_qemu::{detect_with, VmmKind};
+///
+/// let reader = |eax, _ecx| {
+///     let (eax, ebx, ecx, edx) = match eax {
+///         0x0 => (0x1, 0, 0, 0),
+///         0x1 => (0, 0, 1 << 31, 0),
+///         // "Microsoft Hv"
+///         0x4000_0000 => (0x4000_0005, 0x7263_694d, 0x666f_736f, 0x7648_2074),
+///         // "XenVMMXenVMM"
+///         0x4000_0100 => (0x4000_0105, 0x566e_6558, 0x6558_4d4d, 0x4d4d_566e),
+///         _ => (0, 0, 0, 0),
+///     };
+///     CpuIdResult { eax, ebx, ecx, edx }
+/// };
+/// assert_eq!(detect_with(reader).vmm(), Some(VmmKind::Xen));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct XenInfo {
+    /// Base leaf of the Xen leaves. `0x4000_0100`, if Xen also emulates the Hyper-V
+    /// interface (Viridian), `0x4000_0000` otherwise.
+    pub base_leaf: u32,
+    /// Major version of Xen (leaf base + 1, `eax[31:16]`).
+    pub major_version: u16,
+    /// Minor version of Xen (leaf base + 1, `eax[15:0]`).
+    pub minor_version: u16,
+    /// Whether the guest uses hardware virtualization, i.e. is an HVM or PVH guest
+    /// (leaf base + 4). See [`xen_guest_mode`] to distinguish both.
+    pub hvm_container: bool,
+}
+
+/// Reads the Xen CPUID leaves. Returns `None`, if the hypervisor is not Xen or CPUID is
+/// not available on this architecture.
+///
+/// PV guests only see the Xen leaves, if the CPU supports CPUID faulting. Otherwise,
+/// CPUID returns the values of the host and this returns `None` as well.
+///
+/// ## Example Usage
+///
+/// ```rust
+/// use runs_inside_qemu::xen_info;
+///
+/// if let Some(info) = xen_info() {
+///     println!("Xen {}.{}", info.major_version, info.minor_version);
+/// }
+/// ```
+pub fn xen_info() -> Option<XenInfo> {
+    xen_info_with(&CpuIdReaderNative)
+}
+
+/// Like [`xen_info`] but reads CPUID through the given reader.
+pub(crate) fn xen_info_with<R: CpuIdReader>(reader: &R) -> Option<XenInfo> {
+    cpuid::hypervisor_signature_with(reader)?;
+    let base_leaf = [HYPERVISOR_INFO_LEAF, HYPERV_EMULATOR_INFO_LEAF]
+        .into_iter()
+        .find(|&leaf| &cpuid::read_signature(reader, leaf) == SIGNATURE_XEN)?;
+    let max_leaf = reader.cpuid1(base_leaf).eax;
+    let version = reader.cpuid1(base_leaf + 1).eax;
+    let hvm_container = max_leaf >= base_leaf + 4
+        && reader.cpuid1(base_leaf + 4).eax & XEN_HVM_CPUID_VCPU_ID_PRESENT != 0;
+    Some(XenInfo {
+        base_leaf,
+        major_version: (version >> 16) as u16,
+        minor_version: version as u16,
+        hvm_container,
+    })
+}
+
+/// Determines the guest mode of a Xen domain. Returns `None`, if [`xen_info`] doesn't
+/// find Xen. For HVM and PVH guests, PVH is told apart by the absence of the Xen
+/// platform device on I/O port `0x10`, which QEMU provides for HVM guests.
+///
+/// # Safety
+/// The caller must be allowed to access I/O ports, i.e. run in ring 0 or with a
+/// sufficient I/O privilege level. Otherwise, the port access raises a general protection
+/// fault. The port is only accessed for HVM and PVH guests.
+pub unsafe fn xen_guest_mode() -> Option<XenGuestMode> {
+    let info = xen_info()?;
+    if !info.hvm_container {
+        return Some(XenGuestMode::Pv);
+    }
+    if io::inw(XEN_IOPORT_MAGIC) == XEN_IOPORT_MAGIC_VAL {
+        Some(XenGuestMode::Hvm)
+    } else {
+        Some(XenGuestMode::Pvh)
+    }
+}
+
+/// Returns if the code runs in a Xen HVM guest with QEMU as device model. For HVM guests
+/// (see [`xen_guest_mode`]), this is confirmed by fw_cfg, the Xen platform PCI device
+/// (`5853:0001`), or a Red Hat QEMU PCI device (vendor `1b36`). In this configuration,
+/// QEMU's debug devices, such as the debugcon, work although the hypervisor is Xen.
+///
+/// ## Example Usage
+///
+/// ```rust,no_run
+/// use runs_inside_qemu::xen_hvm_with_qemu;
+///
+/// // SAFETY: runs in ring 0 before other CPUs are started
+/// if unsafe { xen_hvm_with_qemu() } {
+///     println!("Xen HVM with QEMU device model, use debugcon");
+/// }
+/// ```
+///
+/// # Safety
+/// See [`xen_guest_mode`]. Additionally, nobody else may access fw_cfg or the PCI
+/// configuration space while this runs. They are only accessed for HVM guests.
+pub unsafe fn xen_hvm_with_qemu() -> bool {
+    xen_guest_mode() == Some(XenGuestMode::Hvm) && qemu_devices_present()
+}
+
+/// Returns if fw_cfg, the Xen platform PCI device, or a Red Hat QEMU PCI device is
+/// present. See [`xen_hvm_with_qemu`].
+///
+/// # Safety
+/// See [`xen_hvm_with_qemu`]. Only call this for HVM guests.
+unsafe fn qemu_devices_present() -> bool {
+    fw_cfg::signature_present()
+        || pci::devices().any(|device| {
+            (device.vendor_id, device.device_id) == XEN_PLATFORM_DEVICE
+                || device.vendor_id == REDHAT_QEMU_VENDOR_ID
+        })
+}
+
+/// Probe that determines the Xen guest mode (see [`xen_guest_mode`]).
+///
+/// Returns [`ProbeResult::Qemu`] for HVM guests with confirmed QEMU devices (see
+/// [`xen_hvm_with_qemu`]), [`ProbeResult::Hint`] for other HVM guests, as QEMU acts as
+/// device model, and [`ProbeResult::NotQemu`] for PV and PVH guests. Returns
+/// [`ProbeResult::Inconclusive`], if the hypervisor is not Xen.
+#[derive(Copy, Clone, Debug)]
+pub struct XenProbe {
+    _private: (),
+}
+
+impl XenProbe {
+    /// Creates a new probe.
+    ///
+    /// # Safety
+    /// See [`xen_hvm_with_qemu`].
+    pub const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl Probe for XenProbe {
+    fn name(&self) -> &'static str {
+        "xen"
+    }
+
+    fn run(&self) -> ProbeResult {
+        // SAFETY: guaranteed by the caller of `XenProbe::new`
+        match unsafe { xen_guest_mode() } {
+            // SAFETY: guaranteed by the caller of `XenProbe::new`
+            Some(XenGuestMode::Hvm) if unsafe { qemu_devices_present() } => {
+                debug!("Xen HVM guest with QEMU devices.");
+                ProbeResult::Qemu
+            }
+            Some(mode) if mode.has_qemu_device_model() => {
+                debug!("Xen {:?} guest with QEMU as device model.", mode);
+                ProbeResult::Hint
+            }
+            Some(mode) => {
+                debug!("Xen {:?} guest without device model.", mode);
+                ProbeResult::NotQemu
+            }
+            None => ProbeResult::Inconclusive,
+        }
+    }
+}